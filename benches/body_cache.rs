@@ -0,0 +1,44 @@
+//! Compare a 1 Hz updater's 2 KB body being parsed on every update against it being cached. Run
+//! with `cargo bench --bench body_cache`
+
+// Only the body parsing is shared with the daemon, which has no library to depend on
+#![allow(dead_code)]
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+#[path = "../src/body.rs"]
+mod body;
+#[path = "../src/markup.rs"]
+mod markup;
+
+use body::{body_elements, BodyCache};
+
+/// An hour of updates
+const UPDATES: u32 = 3600;
+
+fn time(mut update: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..UPDATES {
+        update();
+    }
+    start.elapsed()
+}
+
+fn main() {
+    let body = "<b>Progress</b> <i>update</i> with a <a href=\"https://example.com\">link</a>\n"
+        .repeat(2048 / 64);
+    let parse = || body_elements(body.clone(), "App", None, false, &[], 1);
+
+    let uncached = time(|| {
+        black_box(parse());
+    });
+    let mut cache = BodyCache::default();
+    let cached = time(|| {
+        black_box(cache.get_or_parse(&body, "App", None, parse));
+    });
+
+    println!("{UPDATES} updates of a {} byte body", body.len());
+    println!("uncached: {uncached:?} ({:?} each)", uncached / UPDATES);
+    println!("cached: {cached:?} ({:?} each)", cached / UPDATES);
+}
@@ -0,0 +1,113 @@
+use iced::futures::{SinkExt, Stream, StreamExt};
+use log::{debug, warn};
+use zbus::proxy;
+use zbus::zvariant::{OwnedValue, Value};
+
+use crate::config::ColorScheme;
+
+/// The settings namespace and key of the color scheme preference
+const NAMESPACE: &str = "org.freedesktop.appearance";
+const KEY: &str = "color-scheme";
+
+#[proxy(
+    interface = "org.freedesktop.portal.Settings",
+    default_service = "org.freedesktop.portal.Desktop",
+    default_path = "/org/freedesktop/portal/desktop"
+)]
+trait Settings {
+    fn read_one(&self, namespace: &str, key: &str) -> zbus::Result<OwnedValue>;
+
+    #[zbus(signal)]
+    fn setting_changed(
+        &self,
+        namespace: String,
+        key: String,
+        value: OwnedValue,
+    ) -> zbus::Result<()>;
+}
+
+/// The color scheme from the appearance portal's setting, 1 preferring dark and 2 light, or
+/// `None` for no preference
+fn portal_color_scheme(value: &Value) -> Option<ColorScheme> {
+    match value {
+        // Older portals wrap the setting in another variant
+        Value::Value(value) => portal_color_scheme(value),
+        Value::U32(1) => Some(ColorScheme::Dark),
+        Value::U32(2) => Some(ColorScheme::Light),
+        _ => None,
+    }
+}
+
+/// The color scheme of a GTK theme name, like Adwaita:dark or Adwaita-dark
+fn gtk_color_scheme(theme: &str) -> ColorScheme {
+    let theme = theme.to_lowercase();
+    if theme.ends_with(":dark") || theme.ends_with("-dark") {
+        ColorScheme::Dark
+    } else {
+        ColorScheme::Light
+    }
+}
+
+/// The color scheme from the appearance portal, and its changes from then on
+async fn portal() -> zbus::Result<(Option<ColorScheme>, SettingChangedStream)> {
+    let connection = zbus::Connection::session().await?;
+    let proxy = SettingsProxy::new(&connection).await?;
+    // Listen before reading, so no change is missed between
+    let changes = proxy.receive_setting_changed().await?;
+    let scheme = proxy.read_one(NAMESPACE, KEY).await?;
+    Ok((portal_color_scheme(&scheme), changes))
+}
+
+/// The system's color scheme, from the appearance portal and then each time it changes, or else
+/// once from `$GTK_THEME`. `None` is no preference
+pub fn color_scheme() -> impl Stream<Item = Option<ColorScheme>> {
+    iced::stream::channel(10, async move |mut output| {
+        let mut changes = match portal().await {
+            Ok((scheme, changes)) => {
+                let _ = output.send(scheme).await;
+                changes
+            }
+            Err(err) => {
+                debug!("Unable to read the color scheme from the portal with error {err}");
+                let theme = std::env::var("GTK_THEME").ok();
+                let _ = output.send(theme.as_deref().map(gtk_color_scheme)).await;
+                return;
+            }
+        };
+
+        while let Some(change) = changes.next().await {
+            match change.args() {
+                Ok(args) if args.namespace() == NAMESPACE && args.key() == KEY => {
+                    let scheme = portal_color_scheme(args.value());
+                    debug!("Color scheme changed to {:?}", scheme);
+                    let _ = output.send(scheme).await;
+                }
+                Ok(_) => {}
+                Err(err) => warn!("Invalid SettingChanged signal with error {err}"),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_portal_color_scheme() {
+        assert_eq!(portal_color_scheme(&Value::U32(1)), Some(ColorScheme::Dark));
+        assert_eq!(
+            portal_color_scheme(&Value::Value(Box::new(Value::U32(2)))),
+            Some(ColorScheme::Light)
+        );
+        assert_eq!(portal_color_scheme(&Value::U32(0)), None);
+        assert_eq!(portal_color_scheme(&Value::from("dark")), None);
+    }
+
+    #[test]
+    fn test_gtk_color_scheme() {
+        assert_eq!(gtk_color_scheme("Adwaita:dark"), ColorScheme::Dark);
+        assert_eq!(gtk_color_scheme("Adwaita-Dark"), ColorScheme::Dark);
+        assert_eq!(gtk_color_scheme("Adwaita"), ColorScheme::Light);
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::VecDeque;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Arc;
+
+use log::debug;
+
+use crate::markup::{markup, plain, BodyElement};
+
+/// Parse the body, unless it is empty, or markup is disabled with `no_markup`, or the app name or
+/// desktop entry is one of the `plain_apps` whose bodies are shown without markup
+pub fn body_elements(
+    body: String,
+    app_name: &str,
+    desktop_entry: Option<&str>,
+    no_markup: bool,
+    plain_apps: &[String],
+    max_blank_lines: usize,
+) -> Option<Vec<BodyElement>> {
+    if body.is_empty() {
+        return None;
+    }
+
+    let is_plain = no_markup
+        || plain_apps.iter().any(|app| {
+            app.eq_ignore_ascii_case(app_name)
+                || desktop_entry.is_some_and(|entry| app.eq_ignore_ascii_case(entry))
+        });
+    if is_plain {
+        debug!("Markup disabled for {app_name}");
+        Some(plain(body, max_blank_lines))
+    } else {
+        Some(markup(body, max_blank_lines))
+    }
+}
+
+/// The number of recently parsed bodies to keep
+const BODY_CACHE_SIZE: usize = 16;
+
+/// A parsed body, with the raw body and who sent it
+struct CachedBody {
+    /// A hash of the raw body and who sent it, to skip comparing them for most other bodies
+    hash: u64,
+    body: String,
+    app_name: String,
+    desktop_entry: Option<String>,
+    parsed: Option<Arc<Vec<BodyElement>>>,
+}
+
+/// Recently parsed bodies, most recent first, matched by the raw body and who sent it, so a client
+/// updating a notification every second with the same body doesn't re-run the parser
+#[derive(Default)]
+pub struct BodyCache(VecDeque<CachedBody>);
+
+impl BodyCache {
+    /// Get the cached parsed body, or parse and cache it
+    pub fn get_or_parse(
+        &mut self,
+        body: &str,
+        app_name: &str,
+        desktop_entry: Option<&str>,
+        parse: impl FnOnce() -> Option<Vec<BodyElement>>,
+    ) -> Option<Arc<Vec<BodyElement>>> {
+        let hash = body_hash(body, app_name, desktop_entry);
+        let cached = self.0.iter().position(|cached| {
+            cached.hash == hash
+                && cached.body == body
+                && cached.app_name == app_name
+                && cached.desktop_entry.as_deref() == desktop_entry
+        });
+        let entry = match cached {
+            Some(index) => self.0.remove(index).expect("Cache index exists"),
+            None => CachedBody {
+                hash,
+                body: body.to_string(),
+                app_name: app_name.to_string(),
+                desktop_entry: desktop_entry.map(String::from),
+                parsed: parse().map(Arc::new),
+            },
+        };
+        let parsed = entry.parsed.clone();
+        self.0.push_front(entry);
+        self.0.truncate(BODY_CACHE_SIZE);
+
+        parsed
+    }
+}
+
+fn body_hash(body: &str, app_name: &str, desktop_entry: Option<&str>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (body, app_name, desktop_entry).hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_plain_apps() {
+        let plain_apps = vec!["Broken".to_string(), "org.example.App".to_string()];
+        let body = "<b>bold</b>".to_string();
+
+        assert_eq!(
+            body_elements(body.clone(), "broken", None, false, &plain_apps, 1),
+            Some(plain(body.clone(), 1))
+        );
+        assert_eq!(
+            body_elements(
+                body.clone(),
+                "App",
+                Some("org.example.app"),
+                false,
+                &plain_apps,
+                1
+            ),
+            Some(plain(body.clone(), 1))
+        );
+        assert_eq!(
+            body_elements(body.clone(), "Other", None, false, &plain_apps, 1),
+            Some(markup(body, 1))
+        );
+        assert_eq!(
+            body_elements(String::new(), "Broken", None, false, &plain_apps, 1),
+            None
+        );
+    }
+
+    #[test]
+    fn test_body_cache() {
+        let mut cache = BodyCache::default();
+        let first = cache.get_or_parse("<b>a</b>", "App", None, || {
+            body_elements("<b>a</b>".to_string(), "App", None, false, &[], 1)
+        });
+        let again = cache.get_or_parse("<b>a</b>", "App", None, || panic!("Parsed again"));
+        assert!(Arc::ptr_eq(&first.unwrap(), &again.unwrap()));
+
+        // The least recently used body is evicted
+        for i in 0..BODY_CACHE_SIZE {
+            let body = i.to_string();
+            cache.get_or_parse(&body, "App", None, || {
+                body_elements(body.clone(), "App", None, false, &[], 1)
+            });
+        }
+        assert!(cache
+            .get_or_parse("<b>a</b>", "App", None, || None)
+            .is_none());
+    }
+
+    #[test]
+    fn test_body_cache_collision() {
+        // Another body with the same hash, as a client could search for, isn't a hit
+        let mut cache = BodyCache::default();
+        cache.0.push_front(CachedBody {
+            hash: body_hash("mine", "App", None),
+            body: "theirs".into(),
+            app_name: "App".into(),
+            desktop_entry: None,
+            parsed: Some(Arc::new(markup("theirs".into(), 1))),
+        });
+        let parsed = cache.get_or_parse("mine", "App", None, || {
+            body_elements("mine".to_string(), "App", None, false, &[], 1)
+        });
+        assert_eq!(parsed.as_deref(), Some(&markup("mine".into(), 1)));
+    }
+}
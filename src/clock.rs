@@ -0,0 +1,58 @@
+#[cfg(test)]
+use std::sync::{Arc, Mutex};
+
+#[cfg(test)]
+use chrono::TimeDelta;
+use chrono::{DateTime, Local};
+
+/// Where the current time comes from, so time-dependent logic can be tested without waiting
+#[derive(Clone, Debug, Default)]
+pub enum Clock {
+    /// The system's clock
+    #[default]
+    System,
+    /// A time that only moves when moved, shared between the clones of the clock
+    #[cfg(test)]
+    Fixed(Arc<Mutex<DateTime<Local>>>),
+}
+
+impl Clock {
+    pub fn now(&self) -> DateTime<Local> {
+        match self {
+            Clock::System => Local::now(),
+            #[cfg(test)]
+            Clock::Fixed(time) => *time.lock().expect("Clock poisoned"),
+        }
+    }
+
+    /// A clock stopped at a time
+    #[cfg(test)]
+    pub fn fixed(time: DateTime<Local>) -> Self {
+        Clock::Fixed(Arc::new(Mutex::new(time)))
+    }
+
+    /// Move a fixed clock forward, or do nothing to the system's clock
+    #[cfg(test)]
+    pub fn advance(&self, delta: TimeDelta) {
+        if let Clock::Fixed(time) = self {
+            *time.lock().expect("Clock poisoned") += delta;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fixed_clock() {
+        let start = Local::now();
+        let clock = Clock::fixed(start);
+        let shared = clock.clone();
+        assert_eq!(clock.now(), start);
+
+        shared.advance(TimeDelta::seconds(5));
+        assert_eq!(clock.now(), start + TimeDelta::seconds(5));
+        assert!(Clock::System.now() >= start);
+    }
+}
@@ -0,0 +1,846 @@
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use chrono::format::{Item, StrftimeItems};
+use clap::{Args, ValueEnum};
+use regex::Regex;
+
+use crate::markup::parse_color;
+use crate::notification::Urgency;
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Options controlling the behavior of the daemon
+#[derive(Args, Clone, Debug)]
+pub struct Config {
+    /// Minimum time in milliseconds to display a notification that has a timeout
+    #[arg(long, default_value_t = 1000)]
+    pub min_display_time: u32,
+    /// Write every Notify and CloseNotification call, with full arguments, as JSON lines to this
+    /// file. Off by default since notifications can contain sensitive content
+    #[arg(long, value_name = "PATH")]
+    pub dbus_trace: Option<PathBuf>,
+    /// Save the notifications to ~/.local/state, or $XDG_STATE_HOME, to show the recent ones again
+    /// with the ReplayHistory DBus method. Off by default since notifications can contain
+    /// sensitive content
+    #[arg(long)]
+    pub history: bool,
+    /// Command to run when a notification arrives, split into words on whitespace. %id, %app,
+    /// and %summary in a word are replaced with the notification's, and %% with %. It isn't run
+    /// by a shell, so a replaced value is always part of a single argument
+    #[arg(long, value_name = "COMMAND")]
+    pub on_notify: Option<String>,
+    /// Command to run when a notification is dismissed, with the same placeholders as --on-notify
+    #[arg(long, value_name = "COMMAND")]
+    pub on_dismiss: Option<String>,
+    /// Command to run when a notification's action is invoked, with the same placeholders as
+    /// --on-notify and %action for the action's key
+    #[arg(long, value_name = "COMMAND")]
+    pub on_action: Option<String>,
+    /// Command to play a notification's sound with, split into words on whitespace like
+    /// --on-notify, with %file in a word replaced with the sound's path
+    #[arg(long, value_name = "COMMAND", default_value = "paplay %file")]
+    pub sound_player: String,
+    /// Disable body markup for all notifications, showing tags literally, and don't advertise it
+    /// so clients send plain text
+    #[arg(long)]
+    pub no_markup: bool,
+    /// Disable body markup for notifications from this app, matched against the app name or
+    /// desktop entry. Can be given multiple times
+    #[arg(long = "plain-app", value_name = "APP")]
+    pub plain_apps: Vec<String>,
+    /// Maximum number of consecutive blank lines to show in a body
+    #[arg(long, default_value_t = 1)]
+    pub max_blank_lines: usize,
+    /// Maximum number of body lines to show before truncating, or 0 to never truncate
+    #[arg(long, default_value_t = 6)]
+    pub max_body_lines: usize,
+    /// Download http(s) images in notification bodies. Off by default since fetching URLs from
+    /// notifications can reveal when and where they were read
+    #[arg(long)]
+    pub allow_remote_images: bool,
+    /// The layer shell layer to show notifications on. Overlay shows notifications above
+    /// fullscreen windows, but can also cover fullscreen video
+    #[arg(long, value_enum, default_value_t = Layer::Top)]
+    pub layer: Layer,
+    /// The layer to show notifications on while a critical notification is displayed
+    #[arg(long, value_enum, default_value_t = Layer::Overlay)]
+    pub critical_layer: Layer,
+    /// How the notifications treat the space other layer shells, like bars, reserve
+    #[arg(long, value_enum, default_value_t = ExclusiveZone::Avoid)]
+    pub exclusive_zone: ExclusiveZone,
+    /// When the notifications take keyboard focus, to type replies and to navigate them with Tab,
+    /// Enter, Escape to dismiss one, and Shift+Escape to dismiss all
+    #[arg(long, value_enum, default_value_t = KeyboardInteractivity::OnDemand)]
+    pub keyboard_interactivity: KeyboardInteractivity,
+    /// Size in pixels of the square notification icons
+    #[arg(long, default_value_t = 80.0)]
+    pub icon_size: f32,
+    /// How non-square icons are scaled into the square
+    #[arg(long, value_enum, default_value_t = IconFit::Contain)]
+    pub icon_fit: IconFit,
+    /// Hide notification icons entirely
+    #[arg(long)]
+    pub no_icon: bool,
+    /// How to show the alt text of images in notification bodies
+    #[arg(long, value_enum, default_value_t = ImageAlt::Tooltip)]
+    pub image_alt: ImageAlt,
+    /// Show a button to copy the notification text to the clipboard
+    #[arg(long)]
+    pub copy_button: bool,
+    /// Show a button to copy a 4 to 8 digit code, like a 2FA code, found in the notification
+    #[arg(long)]
+    pub copy_code: bool,
+    /// Color notification borders with the dominant color of their icon. Off by default since it
+    /// decodes every icon
+    #[arg(long)]
+    pub accent_color: bool,
+    /// Height of body lines, relative to the font size
+    #[arg(long, default_value_t = 1.3)]
+    pub line_height: f32,
+    /// Spacing in pixels between body paragraphs and images
+    #[arg(long, default_value_t = 0.0)]
+    pub paragraph_spacing: f32,
+    /// Spacing in pixels between the header and the body
+    #[arg(long, default_value_t = 10.0)]
+    pub header_spacing: f32,
+    /// A font file to fall back to for characters the default font lacks, like emoji or CJK. Can
+    /// be given multiple times
+    #[arg(long = "fallback-font", value_name = "PATH")]
+    pub fallback_fonts: Vec<PathBuf>,
+    /// Only show the close button while the pointer is over the notification
+    #[arg(long)]
+    pub close_on_hover: bool,
+    /// Dismiss notifications older than this many seconds, even ones that never expire, or 0 to
+    /// keep them
+    #[arg(long, default_value_t = 0)]
+    pub max_age: u64,
+    /// How often, in milliseconds, to update the times shown while notifications are displayed.
+    /// Notifications are removed when they expire regardless
+    #[arg(long, value_name = "MILLISECONDS", default_value_t = 1000)]
+    pub tick_interval: u64,
+    /// The corner of the screen to show notifications in
+    #[arg(long, value_enum, default_value_t = Position::TopRight)]
+    pub position: Position,
+    /// Place notifications this percent of the screen's height from the top or bottom and of its
+    /// width from the side, rather than a fixed number of pixels, so they sit alike on screens of
+    /// any size
+    #[arg(long, value_name = "PERCENT")]
+    pub margin_percent: Option<f32>,
+    /// Maximum height in pixels of the notifications, past which they scroll, or 0 for as tall as
+    /// the screen allows
+    #[arg(long, default_value_t = 0)]
+    pub max_height: u32,
+    /// Hide the bar along expiring notifications showing their remaining time
+    #[arg(long)]
+    pub no_time_bar: bool,
+    /// Which edge of expiring notifications the bar showing their remaining time is along
+    #[arg(long, value_enum, default_value_t = TimeBarPosition::Bottom)]
+    pub time_bar_position: TimeBarPosition,
+    /// Show and remove notifications at once, rather than fading them in and out and sliding the
+    /// others to their new positions
+    #[arg(long)]
+    pub reduce_motion: bool,
+    /// Don't dismiss every notification on a middle click, which is easy to do by accident when
+    /// middle clicking to paste
+    #[arg(long)]
+    pub no_middle_click_dismiss: bool,
+    /// Don't dismiss the oldest notification on scrolling down over them, or bring back the last
+    /// dismissed on scrolling up. While they scroll, this takes holding Ctrl
+    #[arg(long)]
+    pub no_wheel_dismiss: bool,
+    /// Don't dismiss notifications by dragging or swiping them to the side
+    #[arg(long)]
+    pub no_drag_dismiss: bool,
+    /// Give each notification a window of its own, stacked one past another, rather than showing
+    /// them all in one window
+    #[arg(long)]
+    pub window_per_notification: bool,
+    /// Hold notifications while a window is fullscreen, like a presentation or a game, and show
+    /// them once it isn't. Needs the compositor to support wlr-foreign-toplevel-management
+    #[arg(long)]
+    pub pause_when_fullscreen: bool,
+    /// Hold critical notifications while a window is fullscreen too, rather than showing them
+    #[arg(long)]
+    pub hold_critical_when_fullscreen: bool,
+    /// Stop notifications expiring while the session is idle or the screen is locked, so they are
+    /// still there on return
+    #[arg(long)]
+    pub pause_expiry_when_idle: bool,
+    /// The time without input in seconds before the session counts as idle. Expiry is held from
+    /// the last input, but notifications that expire before the session counts as idle are gone
+    #[arg(long, default_value_t = 30)]
+    pub idle_timeout: u64,
+    /// Exit after the first notification is dismissed or expires, for scripted demos and
+    /// screenshots
+    #[arg(long)]
+    pub once: bool,
+    /// Collapse consecutive notifications from the same app under one header
+    #[arg(long)]
+    pub group: bool,
+    /// Whether invoking an action dismisses the notification
+    #[arg(long, value_enum, default_value_t = ActionDismiss::UnlessResident)]
+    pub action_dismiss: ActionDismiss,
+    /// Merge a notification that exactly duplicates a displayed one's app, summary, and body into
+    /// it, with a count, instead of showing another
+    #[arg(long)]
+    pub collapse_duplicates: bool,
+    /// Refresh a displayed notification that duplicates a new one's app, summary, and body,
+    /// giving the new one its id, instead of showing another. Takes precedence over
+    /// --collapse-duplicates, but not over an app replacing a notification
+    #[arg(long)]
+    pub refresh_duplicates: bool,
+    /// Maximum number of notifications to show at once, with a count of the rest, or 0 to show
+    /// them all. Critical notifications are always shown, even past the maximum
+    #[arg(long, default_value_t = 5)]
+    pub max_visible: usize,
+    #[command(flatten)]
+    pub time_format: TimeFormatConfig,
+    #[command(flatten)]
+    pub urgency_style: UrgencyStyleConfig,
+    #[command(flatten)]
+    pub filter: FilterConfig,
+    /// Settings for one app's notifications, like "Spotify:timeout=2000,sound=off", matched
+    /// against the app name or desktop entry. The settings are timeout, in milliseconds like the
+    /// app's own with -1 for the default and 0 for never, sound and show, on or off, and
+    /// min-urgency and max-urgency, low, normal, or critical. Can be given multiple times, with
+    /// later settings for an app overriding earlier ones
+    #[arg(long = "app-settings", value_name = "APP:SETTINGS", value_parser = parse_app_settings)]
+    pub app_settings: Vec<AppSettings>,
+    /// What to do when the notification window can't be created, like on a compositor without
+    /// layer shell support
+    #[arg(long, value_enum, default_value_t = LayerShellFallback::Log)]
+    pub layer_shell_fallback: LayerShellFallback,
+    /// Maximum number of action buttons to show, with the rest behind a button that shows them,
+    /// or 0 to show them all
+    #[arg(long, default_value_t = 3)]
+    pub max_actions: usize,
+    /// Show notifications with more than this many action buttons as one button that opens them
+    /// in a list, or 0 to show them in rows
+    #[arg(long, value_name = "COUNT", default_value_t = 0)]
+    pub actions_dropdown: usize,
+    /// Whether action buttons fill their row or are only as wide as their labels
+    #[arg(long, value_enum, default_value_t = ActionWidth::Fill)]
+    pub action_width: ActionWidth,
+    /// Which side action buttons only as wide as their labels are on
+    #[arg(long, value_enum, default_value_t = ActionAlign::Right)]
+    pub action_align: ActionAlign,
+    /// Which urgencies of notifications to show compactly, as one row without the body or actions
+    #[arg(long, value_enum, default_value_t = CompactUrgency::Low)]
+    pub compact: CompactUrgency,
+    /// Show notifications from this app compactly, unless critical, matched against the app's
+    /// displayed name. Can be given multiple times
+    #[arg(long = "compact-app", value_name = "APP")]
+    pub compact_apps: Vec<String>,
+    /// The monitor to show notifications on, by output name like DP-1, or focused (or primary) for
+    /// the one the compositor picks, usually the focused one, which is picked again once the
+    /// notifications shown when it was picked are gone. The connected outputs are logged at startup
+    #[arg(long, default_value = "focused", value_parser = parse_output)]
+    pub output: Output,
+    /// Show the notifications on every connected monitor, rather than just the active one
+    #[arg(long)]
+    pub all_monitors: bool,
+    /// The order to stack notifications in. The default puts the newest nearest the top or bottom
+    /// edge the window is anchored to. Notifications past the maximum visible are held back from
+    /// the end
+    #[arg(long, value_enum, default_value_t = Order::NewestFirst)]
+    pub order: Order,
+    /// Show critical notifications above normal ones, and normal above low, before ordering by
+    /// time
+    #[arg(long)]
+    pub sort_by_urgency: bool,
+    /// How to show notifications sent with an empty summary
+    #[arg(long, value_enum, default_value_t = EmptySummary::Promote)]
+    pub empty_summary: EmptySummary,
+    /// When to show the app name above the summary
+    #[arg(long, value_enum, default_value_t = ShowAppName::Auto)]
+    pub show_app_name: ShowAppName,
+    /// The color scheme, or the one to fall back to when following the system's and it has no
+    /// preference
+    #[arg(long, value_enum, default_value_t = ColorScheme::Dark)]
+    pub color_scheme: ColorScheme,
+    /// Follow the system's color scheme, from the appearance portal or else $GTK_THEME, switching
+    /// when it changes
+    #[arg(long)]
+    pub follow_color_scheme: bool,
+    /// The theme for the dark color scheme
+    #[arg(long, value_enum, default_value_t = ThemeName::GruvboxDark)]
+    pub dark_theme: ThemeName,
+    /// The theme for the light color scheme
+    #[arg(long, value_enum, default_value_t = ThemeName::GruvboxLight)]
+    pub light_theme: ThemeName,
+    /// The glyph shown, colored by urgency, in place of an icon that can't be shown
+    #[arg(long, default_value = "●")]
+    pub fallback_glyph: String,
+    /// The icon for notifications without one, as a name in the icon theme or a path. A bundled
+    /// icon is used if it can't be found
+    #[arg(long, default_value = "notifications")]
+    pub default_icon: String,
+}
+
+/// How the time a notification was sent is shown
+#[derive(Args, Clone, Debug)]
+pub struct TimeFormatConfig {
+    /// Show times relative to now, like "5m ago", for this many hours
+    #[arg(long, default_value_t = 4)]
+    pub relative_hours: i64,
+    /// The chrono format for times after that within a day
+    #[arg(long, default_value = "%H:%M", value_parser = parse_time_format)]
+    pub time_format_day: String,
+    /// The chrono format for times within a week
+    #[arg(long, default_value = "%a %H:%M", value_parser = parse_time_format)]
+    pub time_format_week: String,
+    /// The chrono format for older times
+    #[arg(long, default_value = "%a %h %e", value_parser = parse_time_format)]
+    pub time_format_older: String,
+}
+
+impl Default for TimeFormatConfig {
+    fn default() -> Self {
+        Self {
+            relative_hours: 4,
+            time_format_day: "%H:%M".into(),
+            time_format_week: "%a %H:%M".into(),
+            time_format_older: "%a %h %e".into(),
+        }
+    }
+}
+
+/// Which notifications are dropped without being shown
+#[derive(Args, Clone, Debug, Default)]
+pub struct FilterConfig {
+    /// Drop notifications whose app name matches this regex. Can be given multiple times
+    #[arg(long = "filter-app", value_name = "REGEX")]
+    pub filter_apps: Vec<Regex>,
+    /// Drop notifications whose summary matches this regex, like "^Now playing". Can be given
+    /// multiple times
+    #[arg(long = "filter-summary", value_name = "REGEX")]
+    pub filter_summaries: Vec<Regex>,
+    /// Show critical notifications even when they match a filter
+    #[arg(long)]
+    pub filter_skip_critical: bool,
+}
+
+impl FilterConfig {
+    /// Whether a notification with this app name, summary, and urgency should be dropped
+    pub fn drops(&self, app_name: &str, summary: &str, urgency: Urgency) -> bool {
+        if self.filter_skip_critical && urgency == Urgency::Critical {
+            return false;
+        }
+
+        self.filter_apps
+            .iter()
+            .any(|regex| regex.is_match(app_name))
+            || self
+                .filter_summaries
+                .iter()
+                .any(|regex| regex.is_match(summary))
+    }
+}
+
+/// Settings for one app's notifications, overriding the global ones where given
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AppSettings {
+    /// The app name or desktop entry the settings are for
+    pub app: String,
+    /// The expire timeout in milliseconds to use instead of the app's
+    pub timeout: Option<i32>,
+    /// Whether to play the notifications' sounds
+    pub sound: Option<bool>,
+    /// Whether to show the notifications at all
+    pub show: Option<bool>,
+    /// The least urgent the notifications can be
+    pub min_urgency: Option<Urgency>,
+    /// The most urgent the notifications can be
+    pub max_urgency: Option<Urgency>,
+}
+
+impl AppSettings {
+    /// The settings for an app, from every entry for its name or desktop entry in order, so later
+    /// ones override earlier ones
+    pub fn for_app(
+        settings: &[AppSettings],
+        app_name: &str,
+        desktop_entry: Option<&str>,
+    ) -> AppSettings {
+        settings
+            .iter()
+            .filter(|settings| {
+                settings.app.eq_ignore_ascii_case(app_name)
+                    || desktop_entry.is_some_and(|entry| settings.app.eq_ignore_ascii_case(entry))
+            })
+            .fold(AppSettings::default(), |merged, settings| AppSettings {
+                app: settings.app.clone(),
+                timeout: settings.timeout.or(merged.timeout),
+                sound: settings.sound.or(merged.sound),
+                show: settings.show.or(merged.show),
+                min_urgency: settings.min_urgency.or(merged.min_urgency),
+                max_urgency: settings.max_urgency.or(merged.max_urgency),
+            })
+    }
+
+    /// The urgency raised to the minimum and lowered to the maximum
+    pub fn clamp_urgency(&self, urgency: Urgency) -> Urgency {
+        let urgency = self.min_urgency.map_or(urgency, |min| urgency.max(min));
+        self.max_urgency.map_or(urgency, |max| urgency.min(max))
+    }
+}
+
+/// Parse app settings like "Spotify:timeout=2000,sound=off"
+fn parse_app_settings(settings: &str) -> Result<AppSettings, String> {
+    let (app, list) = settings
+        .rsplit_once(':')
+        .ok_or_else(|| format!("missing settings in {settings:?}, like APP:sound=off"))?;
+    if app.is_empty() {
+        return Err(format!("missing app in {settings:?}"));
+    }
+
+    let switch = |value: &str| match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(format!("invalid setting {value:?}, expected on or off")),
+    };
+    let urgency = |value: &str| match value {
+        "low" => Ok(Urgency::Low),
+        "normal" => Ok(Urgency::Normal),
+        "critical" => Ok(Urgency::Critical),
+        _ => Err(format!(
+            "invalid urgency {value:?}, expected low, normal, or critical"
+        )),
+    };
+
+    let mut parsed = AppSettings {
+        app: app.to_string(),
+        ..AppSettings::default()
+    };
+    for setting in list.split(',').filter(|setting| !setting.is_empty()) {
+        let (key, value) = setting
+            .split_once('=')
+            .ok_or_else(|| format!("invalid setting {setting:?}, like sound=off"))?;
+        match key {
+            "timeout" => {
+                let timeout = value
+                    .parse()
+                    .map_err(|_| format!("invalid timeout {value:?}"))?;
+                parsed.timeout = Some(timeout);
+            }
+            "sound" => parsed.sound = Some(switch(value)?),
+            "show" => parsed.show = Some(switch(value)?),
+            "min-urgency" => parsed.min_urgency = Some(urgency(value)?),
+            "max-urgency" => parsed.max_urgency = Some(urgency(value)?),
+            _ => return Err(format!("unknown setting {key:?}")),
+        }
+    }
+    Ok(parsed)
+}
+
+/// How notifications are styled by their urgency
+#[derive(Args, Clone, Debug)]
+pub struct UrgencyStyleConfig {
+    /// Border width in pixels of low and normal urgency notifications
+    #[arg(long, default_value_t = 2.0)]
+    pub border_width: f32,
+    /// Border width in pixels of critical notifications
+    #[arg(long, default_value_t = 2.0)]
+    pub critical_border_width: f32,
+    /// Border color of critical notifications, like #fb4934, instead of the theme's danger color
+    #[arg(long, value_name = "COLOR", value_parser = parse_color_arg)]
+    pub critical_border_color: Option<[u8; 3]>,
+    /// Background color of low urgency notifications, instead of the theme's background color
+    #[arg(long, value_name = "COLOR", value_parser = parse_color_arg)]
+    pub low_background: Option<[u8; 3]>,
+    /// Background color of normal urgency notifications, instead of the theme's background color
+    #[arg(long, value_name = "COLOR", value_parser = parse_color_arg)]
+    pub normal_background: Option<[u8; 3]>,
+    /// Background color of critical notifications, like #3c1f1e for a faint red wash, instead of
+    /// the theme's background color
+    #[arg(long, value_name = "COLOR", value_parser = parse_color_arg)]
+    pub critical_background: Option<[u8; 3]>,
+    /// How much to tint the background of critical notifications with their border color, from 0
+    /// to 1
+    #[arg(long, default_value_t = 0.0)]
+    pub critical_tint: f32,
+    /// Color the summary of critical notifications like their border
+    #[arg(long)]
+    pub critical_summary_color: bool,
+    /// Show a badge in the corner of critical notifications' icons
+    #[arg(long)]
+    pub critical_badge: bool,
+    /// Opacity of the text of low urgency notifications, from 0 to 1
+    #[arg(long, default_value_t = 0.7)]
+    pub low_text_opacity: f32,
+}
+
+impl Default for UrgencyStyleConfig {
+    fn default() -> Self {
+        Self {
+            border_width: 2.0,
+            critical_border_width: 2.0,
+            critical_border_color: None,
+            low_background: None,
+            normal_background: None,
+            critical_background: None,
+            critical_tint: 0.0,
+            critical_summary_color: false,
+            critical_badge: false,
+            low_text_opacity: 0.7,
+        }
+    }
+}
+
+impl UrgencyStyleConfig {
+    /// The background color of notifications of the urgency, if not the theme's
+    pub fn background(&self, urgency: Urgency) -> Option<[u8; 3]> {
+        match urgency {
+            Urgency::Low => self.low_background,
+            Urgency::Normal => self.normal_background,
+            Urgency::Critical => self.critical_background,
+        }
+    }
+}
+
+/// Parse a color given as an argument, like #fb4934 or red
+fn parse_color_arg(color: &str) -> Result<[u8; 3], String> {
+    parse_color(color).ok_or_else(|| format!("invalid color {color:?}"))
+}
+
+/// Check a chrono format string at startup, since formatting a time with an invalid one panics
+fn parse_time_format(format: &str) -> Result<String, String> {
+    if StrftimeItems::new(format).any(|item| item == Item::Error) {
+        Err(format!("invalid time format {format:?}"))
+    } else {
+        Ok(format.to_string())
+    }
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImageAlt {
+    /// Show the alt text when hovering over the image
+    Tooltip,
+    /// Show the alt text as a caption under the image
+    Caption,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IconFit {
+    /// Scale to fit inside the square, keeping the aspect ratio
+    Contain,
+    /// Scale to cover the square, keeping the aspect ratio and cropping the overflow
+    Cover,
+    /// Stretch to fill the square, ignoring the aspect ratio
+    Stretch,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ActionDismiss {
+    /// Always dismiss the notification
+    Always,
+    /// Dismiss the notification unless the app asked for it to stay with the resident hint
+    UnlessResident,
+    /// Leave the notification for the app to close
+    Never,
+}
+
+impl ActionDismiss {
+    /// Whether invoking an action on a notification dismisses it
+    pub fn dismisses(self, resident: bool) -> bool {
+        match self {
+            ActionDismiss::Always => true,
+            ActionDismiss::UnlessResident => !resident,
+            ActionDismiss::Never => false,
+        }
+    }
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExclusiveZone {
+    /// Stay out of the space reserved by bars
+    Avoid,
+    /// Extend into the space reserved by bars, overlapping them
+    Ignore,
+}
+
+impl ExclusiveZone {
+    /// The layer shell exclusive zone
+    pub fn size(self) -> i32 {
+        match self {
+            ExclusiveZone::Avoid => 0,
+            ExclusiveZone::Ignore => -1,
+        }
+    }
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum KeyboardInteractivity {
+    /// Never take keyboard focus
+    None,
+    /// Take keyboard focus when clicked
+    OnDemand,
+    /// Take keyboard focus whenever notifications are shown, which interrupts typing elsewhere
+    Exclusive,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LayerShellFallback {
+    /// Log the notifications, and try to create the window again for the next one
+    Log,
+    /// Exit with an error, so another notification daemon can take over
+    Exit,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EmptySummary {
+    /// Show the first line of the body as the summary
+    Promote,
+    /// Leave out the summary, showing just the app name above the body
+    Hide,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TimeBarPosition {
+    Top,
+    Bottom,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ActionWidth {
+    /// Spread the buttons evenly across the notification
+    Fill,
+    /// Make each button as wide as its label
+    Shrink,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ActionAlign {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColorScheme {
+    Dark,
+    Light,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ThemeName {
+    GruvboxDark,
+    GruvboxLight,
+    SolarizedDark,
+    SolarizedLight,
+    CatppuccinMocha,
+    CatppuccinLatte,
+    TokyoNight,
+    TokyoNightLight,
+    Nord,
+    Dracula,
+    Dark,
+    Light,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ShowAppName {
+    /// Always show the app name
+    Always,
+    /// Never show the app name
+    Never,
+    /// Show the app name unless it is empty or the same as the summary
+    Auto,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CompactUrgency {
+    /// Show every notification in full
+    Never,
+    /// Show low urgency notifications compactly
+    Low,
+    /// Show low and normal urgency notifications compactly
+    Normal,
+}
+
+impl CompactUrgency {
+    /// Whether notifications of the urgency are shown compactly. Critical ones never are
+    pub fn includes(self, urgency: Urgency) -> bool {
+        match self {
+            CompactUrgency::Never => false,
+            CompactUrgency::Low => urgency == Urgency::Low,
+            CompactUrgency::Normal => urgency <= Urgency::Normal,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Output {
+    /// The output the compositor picks, usually the focused one
+    Focused,
+    /// The output with the name
+    Named(String),
+}
+
+fn parse_output(output: &str) -> Result<Output, String> {
+    match output {
+        "" => Err("empty output name".into()),
+        "focused" | "primary" => Ok(Output::Focused),
+        name => Ok(Output::Named(name.into())),
+    }
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Order {
+    /// The newest notification at the top
+    NewestFirst,
+    /// The oldest notification at the top
+    OldestFirst,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Position {
+    TopRight,
+    TopLeft,
+    BottomRight,
+    BottomLeft,
+}
+
+impl Position {
+    /// Whether the window is anchored to the bottom edge, so notifications stack upwards from it
+    pub fn is_bottom(self) -> bool {
+        matches!(self, Position::BottomRight | Position::BottomLeft)
+    }
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Layer {
+    Background,
+    Bottom,
+    Top,
+    Overlay,
+}
+
+/// Set the global config, which can only be done once
+pub fn init(config: Config) {
+    CONFIG.set(config).expect("Config already initialized");
+}
+
+/// Get the global config
+pub fn config() -> &'static Config {
+    CONFIG.get().expect("Config not initialized")
+}
+
+/// Initialize the global config to the defaults, for tests of code that reads it
+#[cfg(test)]
+pub fn init_default() {
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        config: Config,
+    }
+
+    CONFIG.get_or_init(|| Cli::parse_from(["test"]).config);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_time_format() {
+        assert_eq!(
+            parse_time_format("%d/%m %H:%M"),
+            Ok("%d/%m %H:%M".to_string())
+        );
+        assert!(parse_time_format("%Q").is_err());
+        assert!(parse_time_format("%").is_err());
+    }
+
+    #[test]
+    fn test_parse_color_arg() {
+        assert_eq!(parse_color_arg("#fb4934"), Ok([0xfb, 0x49, 0x34]));
+        assert_eq!(parse_color_arg("red"), Ok([255, 0, 0]));
+        assert!(parse_color_arg("#12").is_err());
+    }
+
+    #[test]
+    fn test_filter() {
+        let filter = FilterConfig {
+            filter_apps: vec![Regex::new("^Spotify$").unwrap()],
+            filter_summaries: vec![Regex::new("^Now playing").unwrap()],
+            filter_skip_critical: false,
+        };
+
+        assert!(filter.drops("Spotify", "Song", Urgency::Normal));
+        assert!(!filter.drops("Spotify Helper", "Song", Urgency::Normal));
+        assert!(filter.drops("Music", "Now playing: Song", Urgency::Low));
+        assert!(!filter.drops("Music", "Paused", Urgency::Low));
+        assert!(filter.drops("Spotify", "Battery low", Urgency::Critical));
+        assert!(!FilterConfig::default().drops("Spotify", "Song", Urgency::Normal));
+
+        let filter = FilterConfig {
+            filter_skip_critical: true,
+            ..filter
+        };
+        assert!(!filter.drops("Spotify", "Battery low", Urgency::Critical));
+        assert!(filter.drops("Spotify", "Song", Urgency::Normal));
+    }
+
+    #[test]
+    fn test_app_settings() {
+        let settings = [
+            parse_app_settings("Spotify:timeout=2000,max-urgency=normal").unwrap(),
+            parse_app_settings("discord:sound=off").unwrap(),
+            parse_app_settings("Spotify:timeout=-1").unwrap(),
+        ];
+
+        // Later settings override earlier ones, and the rest are kept
+        let spotify = AppSettings::for_app(&settings, "Spotify", None);
+        assert_eq!(spotify.timeout, Some(-1));
+        assert_eq!(spotify.sound, None);
+        assert_eq!(spotify.clamp_urgency(Urgency::Critical), Urgency::Normal);
+        assert_eq!(spotify.clamp_urgency(Urgency::Low), Urgency::Low);
+
+        // Matched against the desktop entry too
+        let discord = AppSettings::for_app(&settings, "Discord Canary", Some("Discord"));
+        assert_eq!(discord.sound, Some(false));
+        assert_eq!(discord.timeout, None);
+        assert_eq!(discord.show, None);
+
+        let other = AppSettings::for_app(&settings, "Mail", Some("org.gnome.Evolution"));
+        assert_eq!(other, AppSettings::default());
+    }
+
+    #[test]
+    fn test_parse_app_settings() {
+        assert_eq!(
+            parse_app_settings("org.app.Chat:show=off,min-urgency=critical"),
+            Ok(AppSettings {
+                app: "org.app.Chat".into(),
+                show: Some(false),
+                min_urgency: Some(Urgency::Critical),
+                ..AppSettings::default()
+            })
+        );
+        assert!(parse_app_settings("Spotify").is_err());
+        assert!(parse_app_settings(":sound=off").is_err());
+        assert!(parse_app_settings("Spotify:sound=loud").is_err());
+        assert!(parse_app_settings("Spotify:volume=2").is_err());
+        assert!(parse_app_settings("Spotify:timeout=soon").is_err());
+    }
+
+    #[test]
+    fn test_parse_output() {
+        assert_eq!(parse_output("focused"), Ok(Output::Focused));
+        assert_eq!(parse_output("primary"), Ok(Output::Focused));
+        assert_eq!(parse_output("DP-1"), Ok(Output::Named("DP-1".into())));
+        assert!(parse_output("").is_err());
+    }
+
+    #[test]
+    fn test_action_dismiss() {
+        assert!(ActionDismiss::UnlessResident.dismisses(false));
+        assert!(!ActionDismiss::UnlessResident.dismisses(true));
+        assert!(ActionDismiss::Always.dismisses(true));
+        assert!(!ActionDismiss::Never.dismisses(false));
+    }
+}
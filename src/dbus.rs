@@ -1,27 +1,43 @@
 use std::collections::HashSet;
-use std::path::PathBuf;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
-use chrono::{Local, TimeDelta};
+use chrono::{DateTime, Local, TimeDelta};
 use derive_more::Debug;
-use iced::futures::channel::mpsc;
+use iced::futures::channel::{mpsc, oneshot};
 use iced::futures::{SinkExt, Stream, StreamExt};
+use iced::Color;
 use log::{debug, info, warn};
 use serde::{Deserialize, Serialize};
 use zbus::object_server::SignalEmitter;
-use zbus::zvariant::{DeserializeDict, SerializeDict, Type};
-use zbus::{connection, interface};
+use zbus::zvariant::{DeserializeDict, OwnedValue, SerializeDict, Type, Value};
+use zbus::{connection, fdo, interface};
 
-use crate::freedesktop::{find_app_name, find_icon_path, tmp_image_from_data};
-use crate::markup::markup;
-use crate::notification::{Notification, Urgency};
+use crate::body::{body_elements, BodyCache};
+use crate::clock::Clock;
+use crate::config::{config, AppSettings};
+use crate::freedesktop::{
+    dominant_color, download_image, find_app_icon, find_app_name, find_default_icon,
+    find_icon_path, find_sound_path, is_data_uri, is_remote_image, tmp_image_from_data,
+    tmp_image_from_data_uri,
+};
+use crate::history::{self, History, HistoryEntry};
+use crate::hooks::{self, Hook};
+use crate::markup::{parse_color, BodyElement};
+use crate::notification::{content_hash, Content, Notification, Urgency, INLINE_REPLY};
 
 pub fn dbus() -> impl Stream<Item = DbusMessage> {
     iced::stream::channel(100, async move |mut output| {
-        // Create the channel to communicate with the GUI
-        let (sender, mut receiver) = mpsc::channel(100);
+        // Create the channel to communicate with the GUI, unbounded so dismissing many
+        // notifications at once can't fill it
+        let (sender, mut receiver) = mpsc::unbounded();
 
         // Create the NotificationInterface and connect to the DBUS
-        let interface = NotificationInterface::new(output.clone());
+        let active = ActiveNotifications::default();
+        let interface = NotificationInterface::new(output.clone(), active.clone());
         let dbus_connection = connection::Builder::session()
             .expect("Unable to connect to session bus")
             .name("org.freedesktop.Notifications")
@@ -41,21 +57,19 @@ pub fn dbus() -> impl Stream<Item = DbusMessage> {
 
         // Let the GUI know the DBUS interface is initialized
         let _ = output
-            .send(DbusMessage::Initialized(NotificationSignaller(sender)))
+            .send(DbusMessage::Initialized(NotificationSignaller {
+                sender,
+                active,
+            }))
             .await;
 
         tokio::task::spawn(async move {
             let signal_emitter = interface_ref.signal_emitter();
-            let mut interval = tokio::time::interval(std::time::Duration::from_millis(100));
-
-            loop {
-                interval.tick().await;
-                let Some(message) = receiver.next().await else {
-                    continue;
-                };
 
+            while let Some(message) = receiver.next().await {
                 match message {
                     DbusSignal::NotificationClosed(id, reason) => {
+                        interface_ref.get_mut().await.release_id(id);
                         NotificationInterface::notification_closed(
                             signal_emitter,
                             id,
@@ -69,6 +83,22 @@ pub fn dbus() -> impl Stream<Item = DbusMessage> {
                             .await
                             .expect("Unable to send action invoked signal")
                     }
+                    DbusSignal::NotificationReplied(id, text) => {
+                        NotificationInterface::notification_replied(signal_emitter, id, text)
+                            .await
+                            .expect("Unable to send notification replied signal")
+                    }
+                    DbusSignal::Shutdown => {
+                        // Signals sent before this one have been sent, so release the name and exit
+                        info!("Shutting down");
+                        if let Err(err) = dbus_connection
+                            .release_name("org.freedesktop.Notifications")
+                            .await
+                        {
+                            warn!("Unable to release name with error {}", err);
+                        }
+                        std::process::exit(0);
+                    }
                 }
             }
         })
@@ -77,26 +107,83 @@ pub fn dbus() -> impl Stream<Item = DbusMessage> {
     })
 }
 
+/// The (id, app name, summary, content hash) of each notification currently displayed, shared
+/// between the GUI, which updates it, and the DBUS interface, which reports it
+#[derive(Debug, Clone, Default)]
+pub struct ActiveNotifications(Arc<Mutex<Vec<(u32, String, String, u64)>>>);
+
+impl ActiveNotifications {
+    fn set<'a>(&self, notifications: impl Iterator<Item = &'a Notification>) {
+        *self.0.lock().expect("Active notifications poisoned") = notifications
+            .map(|notification| {
+                (
+                    notification.id,
+                    notification.name.clone(),
+                    notification.summary.clone(),
+                    notification.content_hash(),
+                )
+            })
+            .collect();
+    }
+
+    /// The id of the displayed notification with the same content, if any
+    fn duplicate_of(&self, content: Content) -> Option<u32> {
+        let content = content_hash(content);
+        self.0
+            .lock()
+            .expect("Active notifications poisoned")
+            .iter()
+            .find(|(.., other)| *other == content)
+            .map(|&(id, ..)| id)
+    }
+}
+
 #[derive(Debug, Clone)]
-pub struct NotificationSignaller(mpsc::Sender<DbusSignal>);
+pub struct NotificationSignaller {
+    sender: mpsc::UnboundedSender<DbusSignal>,
+    active: ActiveNotifications,
+}
 impl NotificationSignaller {
     pub fn close_notification(&mut self, id: u32, reason: NotificationClosedReason) {
-        self.0
-            .try_send(DbusSignal::NotificationClosed(id, reason))
+        self.sender
+            .unbounded_send(DbusSignal::NotificationClosed(id, reason))
             .expect("Unable to send NotificationClosed signal message")
     }
 
     pub fn action_invoked(&mut self, id: u32, key: String) {
-        self.0
-            .try_send(DbusSignal::ActionInvoked(id, key))
+        self.sender
+            .unbounded_send(DbusSignal::ActionInvoked(id, key))
             .expect("Unable to send ActionInvoked signal message")
     }
+
+    /// Send the reply, and then invoke the inline reply action for apps that only listen for
+    /// actions
+    pub fn notification_replied(&mut self, id: u32, text: String) {
+        self.sender
+            .unbounded_send(DbusSignal::NotificationReplied(id, text))
+            .expect("Unable to send NotificationReplied signal message");
+        self.action_invoked(id, INLINE_REPLY.into());
+    }
+
+    /// Release the bus name and exit, once the signals already sent are sent
+    pub fn shutdown(&mut self) {
+        self.sender
+            .unbounded_send(DbusSignal::Shutdown)
+            .expect("Unable to send Shutdown signal message")
+    }
+
+    /// Report the currently displayed notifications to the DBUS interface
+    pub fn set_active<'a>(&self, notifications: impl Iterator<Item = &'a Notification>) {
+        self.active.set(notifications);
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum DbusSignal {
     NotificationClosed(u32, NotificationClosedReason),
     ActionInvoked(u32, String),
+    NotificationReplied(u32, String),
+    Shutdown,
 }
 
 impl From<NotificationClosedReason> for u32 {
@@ -125,8 +212,25 @@ struct NotificationInterface {
     next_id: u32,
     /// Set of already used ids
     used_ids: HashSet<u32>,
-    /// The path to the default icon
-    default_icon: PathBuf,
+    /// The path to the default icon, if the icon theme has one
+    default_icon: Option<PathBuf>,
+    /// The file DBus calls are traced to, if enabled
+    trace: Option<File>,
+    /// The history the notifications are saved to, if enabled
+    history: Option<History>,
+    /// The id for the next notification replayed from the history, counting down from the top so
+    /// replays don't use up the ids for apps' notifications
+    next_historical_id: u32,
+    /// The notifications currently displayed by the GUI
+    active: ActiveNotifications,
+    /// Recently parsed bodies
+    body_cache: BodyCache,
+    /// When the interface started
+    start_time: Instant,
+    /// The number of Notify calls served
+    served: u32,
+    /// Where the current time comes from
+    clock: Clock,
 }
 
 #[derive(Clone, Debug)]
@@ -134,30 +238,185 @@ pub(crate) enum DbusMessage {
     Initialized(NotificationSignaller),
     Notify(Notification),
     CloseNotification(u32),
+    /// The icon for a notification, saved from its image data, and the time of the notification
+    /// it is for
+    IconLoaded {
+        id: u32,
+        time: DateTime<Local>,
+        icon: PathBuf,
+        accent: Option<Color>,
+    },
+    /// The body of a notification, with its remote images downloaded, and the original body
+    BodyImagesLoaded {
+        id: u32,
+        original: Arc<Vec<BodyElement>>,
+        body: Arc<Vec<BodyElement>>,
+    },
+    /// Hold new notifications in a queue, or show the ones held
+    SetPaused(bool),
+    TogglePaused,
+    /// Dismiss the newest displayed notification, answering with its id
+    DismissLatest(Reply<Option<u32>>),
+    /// Dismiss the oldest displayed notification, answering with its id
+    DismissOldest(Reply<Option<u32>>),
+    /// Dismiss every displayed notification, answering with their ids
+    DismissAll(Reply<Vec<u32>>),
+    /// Bring back the notification dismissed last, answering with its id
+    ShowLatestFromHistory(Reply<Option<u32>>),
+}
+
+/// The GUI's answer to a DBUS call, which can be cloned along with the message it is in but only
+/// answered once
+#[derive(Debug, Clone)]
+pub(crate) struct Reply<T>(Arc<Mutex<Option<oneshot::Sender<T>>>>);
+
+impl<T> Reply<T> {
+    pub fn new() -> (Self, oneshot::Receiver<T>) {
+        let (sender, receiver) = oneshot::channel();
+        (Self(Arc::new(Mutex::new(Some(sender)))), receiver)
+    }
+
+    /// Answer the call, unless it was already answered
+    pub fn send(&self, answer: T) {
+        let sender = self.0.lock().expect("Reply poisoned").take();
+        if let Some(sender) = sender {
+            // The caller may have given up waiting
+            let _ = sender.send(answer);
+        }
+    }
 }
 
 impl NotificationInterface {
     /// Construct a new NotificationInterface
-    fn new(sender: mpsc::Sender<DbusMessage>) -> Self {
+    fn new(sender: mpsc::Sender<DbusMessage>, active: ActiveNotifications) -> Self {
         Self {
             sender,
+            active,
             next_id: 1,
             used_ids: HashSet::new(),
-            default_icon: find_icon_path("notifications").expect("Unable to find default icon"),
+            body_cache: BodyCache::default(),
+            start_time: Instant::now(),
+            served: 0,
+            clock: Clock::default(),
+            default_icon: find_default_icon(&config().default_icon),
+            trace: config().dbus_trace.as_ref().and_then(|path| {
+                File::options()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .inspect_err(|err| {
+                        warn!(
+                            "Unable to open DBus trace {} with error {}",
+                            path.display(),
+                            err
+                        )
+                    })
+                    .ok()
+            }),
+            history: config().history.then(History::open).flatten(),
+            next_historical_id: u32::MAX,
+        }
+    }
+
+    /// A notification from the history to show again, without actions or an inline reply since
+    /// the app that sent it may be gone
+    fn historical_notification(&mut self, entry: HistoryEntry) -> Notification {
+        let id = self.next_historical_id;
+        self.next_historical_id = self.next_historical_id.checked_sub(1).unwrap_or(u32::MAX);
+        let time = entry.time();
+
+        let body = self.body_cache.get_or_parse(
+            &entry.body,
+            &entry.app_name,
+            entry.desktop_entry.as_deref(),
+            || {
+                body_elements(
+                    entry.body.clone(),
+                    &entry.app_name,
+                    entry.desktop_entry.as_deref(),
+                    config().no_markup,
+                    &config().plain_apps,
+                    config().max_blank_lines,
+                )
+            },
+        );
+        let icon = Some(entry.icon)
+            .filter(|icon| icon.exists())
+            .or_else(|| self.default_icon.clone())
+            .unwrap_or_default();
+
+        Notification {
+            id,
+            time,
+            // Shown until dismissed, like a review of what was missed
+            expire_time: None,
+            name: entry.name,
+            accent: icon_accent(&icon),
+            border_color: None,
+            icon,
+            summary: entry.summary,
+            body,
+            actions: None,
+            urgency: entry.urgency,
+            resident: false,
+            reply: None,
+            merged: Vec::new(),
+            historical: true,
+            sound: None,
+        }
+    }
+
+    /// Send the GUI a message with a way to answer, and wait for the answer
+    async fn ask<T>(&mut self, message: impl FnOnce(Reply<T>) -> DbusMessage) -> fdo::Result<T> {
+        let (reply, answer) = Reply::new();
+        self.sender
+            .send(message(reply))
+            .await
+            .expect("Unable to send message to GUI");
+        answer
+            .await
+            .map_err(|_| fdo::Error::Failed("The GUI didn't answer".into()))
+    }
+
+    /// Write a DBus call as a line of JSON to the trace file, if enabled
+    fn trace_call(&mut self, method: &str, arguments: serde_json::Value) {
+        let Some(trace) = &mut self.trace else {
+            return;
+        };
+
+        let line = serde_json::json!({
+            "time": self.clock.now().to_rfc3339(),
+            "method": method,
+            "arguments": arguments,
+        });
+        if let Err(err) = writeln!(trace, "{}", line) {
+            warn!("Unable to write to DBus trace with error {}", err);
         }
     }
 
     /// Get the next available id
     fn get_next_id(&mut self) -> u32 {
         while self.used_ids.contains(&self.next_id) {
-            if self.next_id == u32::MAX {
-                self.next_id = 1;
-            } else {
-                self.next_id += 1;
-            }
+            self.advance_next_id();
+        }
+        let id = self.next_id;
+        self.used_ids.insert(id);
+        // Move on, so a released id isn't used again until the ids wrap around
+        self.advance_next_id();
+        id
+    }
+
+    fn advance_next_id(&mut self) {
+        if self.next_id == u32::MAX {
+            self.next_id = 1;
+        } else {
+            self.next_id += 1;
         }
-        self.used_ids.insert(self.next_id);
-        self.next_id
+    }
+
+    /// Free the id of a closed notification, so the used ids are only those still open
+    fn release_id(&mut self, id: u32) {
+        self.used_ids.remove(&id);
     }
 }
 
@@ -165,13 +424,7 @@ impl NotificationInterface {
 impl NotificationInterface {
     fn get_capabilities(&self) -> Vec<String> {
         info!("GetCapabilities called");
-        vec![
-            "actions".into(),
-            "body".into(),
-            "body-markup".into(),
-            "body-images".into(),
-            "persistence".into(),
-        ]
+        capabilities(config().no_markup)
     }
 
     fn get_server_information(&self) -> (String, String, String, String) {
@@ -184,6 +437,114 @@ impl NotificationInterface {
         )
     }
 
+    /// Non-standard method returning the (id, app name, summary) of every notification currently
+    /// displayed. Notifications that are not on screen are not included.
+    fn get_active_notifications(&self) -> Vec<(u32, String, String)> {
+        info!("GetActiveNotifications called");
+        self.active
+            .0
+            .lock()
+            .expect("Active notifications poisoned")
+            .iter()
+            .map(|(id, name, summary, _)| (*id, name.clone(), summary.clone()))
+            .collect()
+    }
+
+    /// Non-standard method returning the daemon's uptime in seconds, the number of notifications
+    /// served, including replacements, and the number currently displayed, as reported by
+    /// GetActiveNotifications.
+    fn get_status(&self) -> (u64, u32, u32) {
+        info!("GetStatus called");
+        let active = self
+            .active
+            .0
+            .lock()
+            .expect("Active notifications poisoned")
+            .len();
+        (
+            self.start_time.elapsed().as_secs(),
+            self.served,
+            active as u32,
+        )
+    }
+
+    /// Non-standard method to pause notifications, holding new ones until resumed rather than
+    /// showing them, or to resume and show the ones held.
+    async fn set_paused(&mut self, paused: bool) {
+        info!("SetPaused called with {paused}");
+        self.sender
+            .send(DbusMessage::SetPaused(paused))
+            .await
+            .expect("Unable to send message to GUI");
+    }
+
+    /// Non-standard method to show the last `count` notifications from the history again, which
+    /// only works with the history enabled. They are shown without their actions, and closing
+    /// them isn't signalled, since the apps that sent them may be gone.
+    async fn replay_history(&mut self, count: u32) {
+        info!("ReplayHistory called with {count}");
+        if !config().history {
+            warn!("Unable to replay the history, which isn't enabled");
+            return;
+        }
+
+        for entry in history::recent(&history::path(), count as usize) {
+            let notification = self.historical_notification(entry);
+            self.sender
+                .send(DbusMessage::Notify(notification))
+                .await
+                .expect("Unable to send message to GUI");
+        }
+    }
+
+    /// Non-standard method to pause notifications if they aren't paused, or else resume them.
+    async fn toggle_paused(&mut self) {
+        info!("TogglePaused called");
+        self.sender
+            .send(DbusMessage::TogglePaused)
+            .await
+            .expect("Unable to send message to GUI");
+    }
+
+    /// Non-standard method to dismiss the newest displayed notification, returning its id, for
+    /// binding to a key.
+    async fn dismiss_latest(&mut self) -> fdo::Result<u32> {
+        info!("DismissLatest called");
+        self.ask(DbusMessage::DismissLatest)
+            .await?
+            .ok_or_else(|| fdo::Error::Failed("No notification to dismiss".into()))
+    }
+
+    /// Non-standard method to dismiss the oldest displayed notification, returning its id, for
+    /// binding to a key.
+    async fn dismiss_oldest(&mut self) -> fdo::Result<u32> {
+        info!("DismissOldest called");
+        self.ask(DbusMessage::DismissOldest)
+            .await?
+            .ok_or_else(|| fdo::Error::Failed("No notification to dismiss".into()))
+    }
+
+    /// Non-standard method to dismiss every displayed notification, returning their ids, for
+    /// binding to a key.
+    async fn dismiss_all(&mut self) -> fdo::Result<Vec<u32>> {
+        info!("DismissAll called");
+        let ids = self.ask(DbusMessage::DismissAll).await?;
+        if ids.is_empty() {
+            return Err(fdo::Error::Failed("No notification to dismiss".into()));
+        }
+        Ok(ids)
+    }
+
+    /// Non-standard method to bring back the notification dismissed last, if it was within the
+    /// last minute, returning its id, for binding to a key. Like with ReplayHistory, it comes back
+    /// without its actions, and closing it isn't signalled.
+    async fn show_latest_from_history(&mut self) -> fdo::Result<u32> {
+        info!("ShowLatestFromHistory called");
+        self.ask(DbusMessage::ShowLatestFromHistory)
+            .await?
+            .ok_or_else(|| fdo::Error::Failed("No recently dismissed notification".into()))
+    }
+
     #[allow(clippy::too_many_arguments)]
     async fn notify(
         &mut self,
@@ -211,38 +572,71 @@ impl NotificationInterface {
             app_name, replaces_id, app_icon, summary, body, actions, hints, expire_timeout
         );
 
-        let body = if body.is_empty() {
-            None
-        } else {
-            Some(markup(body))
-        };
+        if self.trace.is_some() {
+            self.trace_call(
+                "Notify",
+                serde_json::json!({
+                    "app_name": app_name,
+                    "replaces_id": replaces_id,
+                    "app_icon": app_icon,
+                    "summary": summary,
+                    "body": body,
+                    "actions": actions,
+                    "hints": hints,
+                    "expire_timeout": expire_timeout,
+                }),
+            );
+        }
 
-        let id = if replaces_id == 0 {
-            self.get_next_id()
-        } else {
-            replaces_id
-        };
+        // Keep the body as sent for the history, which is parsed again when replayed
+        let raw_body = self.history.is_some().then(|| body.clone());
 
-        let urgency = match hints.urgency {
-            None => Urgency::Normal,
-            Some(0) => Urgency::Low,
-            Some(1) => Urgency::Normal,
-            Some(2) => Urgency::Critical,
-            Some(level) => {
-                warn!("Unexpected urgency level {}", level);
-                Urgency::Normal
-            }
-        };
+        // The app's own settings override the global ones and what it asked for
+        let settings = AppSettings::for_app(
+            &config().app_settings,
+            &app_name,
+            hints.desktop_entry.as_deref(),
+        );
+        let urgency = settings.clamp_urgency(hints.urgency());
+        let expire_timeout = settings.timeout.unwrap_or(expire_timeout);
+        let hidden = settings.show == Some(false);
 
-        let time = Local::now();
+        if hidden || config().filter.drops(&app_name, &summary, urgency) {
+            // The dropped notification is never shown, so never closed to free its id
+            let id = if replaces_id == 0 {
+                let id = self.get_next_id();
+                self.release_id(id);
+                id
+            } else {
+                replaces_id
+            };
+            let reason = if hidden {
+                "its app is hidden"
+            } else {
+                "it matches a filter"
+            };
+            info!("Dropping notification {id} from {app_name}, since {reason}");
+            return id;
+        }
 
-        let expire_time = if urgency == Urgency::Critical || expire_timeout == 0 {
-            None
-        } else if expire_timeout == -1 {
-            Some(time + TimeDelta::minutes(1))
-        } else {
-            Some(time + TimeDelta::milliseconds(expire_timeout as i64))
-        };
+        let body =
+            self.body_cache
+                .get_or_parse(&body, &app_name, hints.desktop_entry.as_deref(), || {
+                    body_elements(
+                        body.clone(),
+                        &app_name,
+                        hints.desktop_entry.as_deref(),
+                        config().no_markup,
+                        &config().plain_apps,
+                        config().max_blank_lines,
+                    )
+                });
+
+        self.served = self.served.saturating_add(1);
+
+        let time = self.clock.now();
+
+        let expire_time = expire_time(time, expire_timeout, urgency, config().min_display_time);
 
         let name = hints
             .desktop_entry
@@ -250,25 +644,68 @@ impl NotificationInterface {
             .and_then(|entry| find_app_name(entry))
             .unwrap_or(app_name.clone());
 
-        let icon = hints
-            .image_data
-            .as_ref()
-            .and_then(tmp_image_from_data)
-            .or_else(|| hints.image_path.clone())
-            .or_else(|| find_icon_path(&app_icon))
-            .or_else(|| hints.icon_data.as_ref().and_then(tmp_image_from_data))
-            .unwrap_or(self.default_icon.clone());
-
-        let actions: Vec<(String, String)> = actions
-            .chunks_exact(2)
-            .map(|pair| (pair[0].clone(), pair[1].clone()))
-            .collect();
+        // Image data is saved to a file in the background, since encoding a large image can take a
+        // while, so until then the notification uses the next icon in the chain
+        let mut hints = hints;
+        let (icon, image_data) = hints.take_icon(&app_icon);
+        let icon = icon
+            .or_else(|| {
+                hints
+                    .desktop_entry
+                    .as_ref()
+                    .and_then(|entry| find_app_icon(entry))
+                    .and_then(|icon| find_icon_path(&icon))
+            })
+            .or_else(|| self.default_icon.clone())
+            .unwrap_or_default();
+        let accent = icon_accent(&icon);
+
+        // The sound is played once the notification is displayed, so not while it's held
+        let sound = (settings.sound != Some(false) && !hints.suppress_sound.unwrap_or(false))
+            .then(|| {
+                hints
+                    .sound_file
+                    .as_ref()
+                    .map(|file| PathBuf::from(file.replace("file://", "")))
+                    .or_else(|| {
+                        hints
+                            .sound_name
+                            .as_ref()
+                            .and_then(|name| find_sound_path(name))
+                    })
+            })
+            .flatten();
+
+        let (actions, reply) = split_inline_reply(
+            actions
+                .chunks_exact(2)
+                .map(|pair| (pair[0].clone(), pair[1].clone()))
+                .collect(),
+        );
+        let reply = reply.map(|label| hints.x_kde_reply_placeholder_text.clone().unwrap_or(label));
         let actions = if actions.is_empty() {
             None
         } else {
             Some(actions)
         };
 
+        // A duplicate of a displayed notification replaces it, refreshing its time and expiry,
+        // unless the app is replacing a notification itself
+        let duplicate = (replaces_id == 0 && config().refresh_duplicates)
+            .then(|| {
+                self.active
+                    .duplicate_of((&name, &summary, body.as_deref().map(Vec::as_slice)))
+            })
+            .flatten();
+        if let Some(duplicate) = duplicate {
+            debug!("Refreshing notification {duplicate} with its duplicate");
+        }
+        let id = match (replaces_id, duplicate) {
+            (0, Some(duplicate)) => duplicate,
+            (0, None) => self.get_next_id(),
+            (replaces_id, _) => replaces_id,
+        };
+
         let notification = Notification {
             id,
             time,
@@ -279,16 +716,51 @@ impl NotificationInterface {
             body,
             actions,
             urgency,
+            accent,
+            border_color: hints.border_color(),
+            resident: hints.resident.unwrap_or(false),
+            reply,
+            merged: Vec::new(),
+            historical: false,
+            sound,
         };
 
+        if let Some(body) = &notification.body {
+            load_body_images(
+                id,
+                body.clone(),
+                config().allow_remote_images,
+                self.sender.clone(),
+            );
+        }
+
         debug!("Notification created: {:#?}", notification);
 
+        if let (Some(history), Some(body)) = (&mut self.history, raw_body) {
+            history.record(&HistoryEntry {
+                time: time.to_rfc3339(),
+                app_name,
+                desktop_entry: hints.desktop_entry.clone(),
+                name: notification.name.clone(),
+                icon: notification.icon.clone(),
+                summary: notification.summary.clone(),
+                body,
+                urgency,
+            });
+        }
+
+        hooks::run(Hook::Notify, &notification);
+
         // Inform the GUI of the new notification
         self.sender
             .send(DbusMessage::Notify(notification))
             .await
             .expect("Unable to send message to GUI");
 
+        if !image_data.is_empty() {
+            load_image_data(id, time, image_data, self.sender.clone());
+        }
+
         id
     }
 
@@ -298,6 +770,7 @@ impl NotificationInterface {
         id: u32,
     ) {
         info!("CloseNotification called for {id}");
+        self.trace_call("CloseNotification", serde_json::json!({ "id": id }));
         self.sender
             .send(DbusMessage::CloseNotification(id))
             .await
@@ -322,9 +795,164 @@ impl NotificationInterface {
         id: u32,
         string_key: String,
     ) -> zbus::Result<()>;
+
+    /// Non-standard signal, from KDE, with the text the user replied to a notification with
+    #[zbus(signal)]
+    async fn notification_replied(
+        emitter: &SignalEmitter<'_>,
+        id: u32,
+        text: String,
+    ) -> zbus::Result<()>;
+}
+
+/// The capabilities of the server, without the markup ones if markup is disabled, so clients send
+/// plain text
+fn capabilities(no_markup: bool) -> Vec<String> {
+    let markup = ["body-hyperlinks", "body-markup", "body-images"];
+    [
+        "actions",
+        "body",
+        "body-hyperlinks",
+        "body-markup",
+        "body-images",
+        "inline-reply",
+        "persistence",
+        "sound",
+    ]
+    .into_iter()
+    .filter(|capability| !(no_markup && markup.contains(capability)))
+    .map(String::from)
+    .collect()
+}
+
+/// The accent color for a notification with this icon, if enabled
+fn icon_accent(icon: &Path) -> Option<Color> {
+    if !config().accent_color {
+        return None;
+    }
+
+    dominant_color(icon).map(|[r, g, b]| Color::from_rgb8(r, g, b))
 }
 
-#[derive(Debug, DeserializeDict, SerializeDict, Type)]
+/// Save the first of the image data that can be to a file in the background, and send the GUI
+/// the path to use as the notification's icon. If none can be saved, the notification keeps the
+/// next icon in the chain it was given in the meantime
+fn load_image_data(
+    id: u32,
+    time: DateTime<Local>,
+    image_data: Vec<ImageData>,
+    mut sender: mpsc::Sender<DbusMessage>,
+) {
+    tokio::task::spawn(async move {
+        // Encoding a large image can take a while, so it's done on the blocking pool
+        let saved = tokio::task::spawn_blocking(move || {
+            let icon = image_data.iter().find_map(tmp_image_from_data)?;
+            let accent = icon_accent(&icon);
+            Some((icon, accent))
+        })
+        .await;
+        let (icon, accent) = match saved {
+            Ok(Some(saved)) => saved,
+            Ok(None) => {
+                warn!("Unable to save the image data of notification {id}, keeping its other icon");
+                return;
+            }
+            Err(err) => {
+                warn!("Unable to save the image data of notification {id} with error {err}");
+                return;
+            }
+        };
+
+        // Wait for room in the channel rather than dropping the icon when the GUI is busy
+        let loaded = DbusMessage::IconLoaded {
+            id,
+            time,
+            icon,
+            accent,
+        };
+        if let Err(err) = sender.send(loaded).await {
+            warn!("Unable to send loaded icon to GUI with error {}", err);
+        }
+    });
+}
+
+/// Save the images given inline as `data:` URIs, and download the remote images if allowed, in
+/// the background, and send the GUI the body with the images replaced by their saved paths. Until
+/// then, and for any that fail, the images show their alt text
+fn load_body_images(
+    id: u32,
+    original: Arc<Vec<BodyElement>>,
+    allow_remote: bool,
+    mut sender: mpsc::Sender<DbusMessage>,
+) {
+    let loads = move |src: &Path| is_data_uri(src) || (allow_remote && is_remote_image(src));
+    let has_images_to_load = original
+        .iter()
+        .any(|element| matches!(element, BodyElement::Image { src, .. } if loads(src)));
+    if !has_images_to_load {
+        return;
+    }
+
+    tokio::task::spawn(async move {
+        // Saving and downloading the images blocks, so it's done on the blocking pool
+        let images = original.clone();
+        let body = tokio::task::spawn_blocking(move || {
+            images
+                .iter()
+                .cloned()
+                .map(|element| match element {
+                    BodyElement::Image { src, alt } if loads(&src) => {
+                        let saved = src.to_str().and_then(|src| {
+                            if is_data_uri(Path::new(src)) {
+                                tmp_image_from_data_uri(src)
+                            } else {
+                                download_image(src)
+                            }
+                        });
+                        BodyElement::Image {
+                            src: saved.unwrap_or(src),
+                            alt,
+                        }
+                    }
+                    element => element,
+                })
+                .collect::<Vec<_>>()
+        })
+        .await;
+        let body = match body {
+            Ok(body) => Arc::new(body),
+            Err(err) => {
+                warn!("Unable to load the body images of notification {id} with error {err}");
+                return;
+            }
+        };
+
+        let loaded = DbusMessage::BodyImagesLoaded { id, original, body };
+        if let Err(err) = sender.send(loaded).await {
+            warn!("Unable to send loaded images to GUI with error {}", err);
+        }
+    });
+}
+
+/// Compute when a notification expires from its `expire_timeout`, where -1 means the default
+/// timeout and 0 means never. Positive timeouts are raised to at least `min_display_time` ms.
+fn expire_time(
+    time: DateTime<Local>,
+    expire_timeout: i32,
+    urgency: Urgency,
+    min_display_time: u32,
+) -> Option<DateTime<Local>> {
+    if urgency == Urgency::Critical || expire_timeout == 0 {
+        None
+    } else if expire_timeout == -1 {
+        Some(time + TimeDelta::minutes(1))
+    } else {
+        let timeout = (expire_timeout as i64).max(min_display_time as i64);
+        Some(time + TimeDelta::milliseconds(timeout))
+    }
+}
+
+#[derive(Debug, Default, DeserializeDict, SerializeDict, Type)]
 #[zvariant(signature = "dict", rename_all = "kebab-case")]
 struct Hints {
     action_icons: Option<bool>,
@@ -345,6 +973,155 @@ struct Hints {
     x: Option<i32>,
     y: Option<i32>,
     urgency: Option<u8>,
+    // Vendor urgency hints some apps set instead of the standard urgency, with a level that can be
+    // a number or a name
+    #[zvariant(rename = "x-kde-urgencyHint")]
+    x_kde_urgency_hint: Option<OwnedValue>,
+    x_kde_urgency: Option<OwnedValue>,
+    x_gnome_priority: Option<OwnedValue>,
+    x_kde_reply_placeholder_text: Option<String>,
+    // Vendor hints for the color to border the notification with, as a string or a structure
+    // ending in the red, green, blue, and alpha bytes
+    border_color: Option<OwnedValue>,
+    frame_color: Option<OwnedValue>,
+}
+
+impl Hints {
+    /// The urgency from the standard hint, or else the first recognized vendor hint, defaulting to
+    /// Normal
+    fn urgency(&self) -> Urgency {
+        if let Some(level) = self.urgency {
+            return urgency_level(level.into()).unwrap_or_else(|| {
+                warn!("Unexpected urgency level {}", level);
+                Urgency::Normal
+            });
+        }
+
+        [
+            &self.x_kde_urgency_hint,
+            &self.x_kde_urgency,
+            &self.x_gnome_priority,
+        ]
+        .into_iter()
+        .flatten()
+        .find_map(|level| vendor_urgency(level))
+        .unwrap_or(Urgency::Normal)
+    }
+
+    /// The icon to show, in the spec's order: the image data, the image path, the app_icon
+    /// parameter, and for compatibility the deprecated icon data, with the image hints' older
+    /// names counting the same as the current ones. Returns the first of the image path and
+    /// app_icon that resolves to a path, and the image data to save to a file, in order, each
+    /// taking the place of the icons after it once saved
+    fn take_icon(&mut self, app_icon: &str) -> (Option<PathBuf>, Vec<ImageData>) {
+        let image_data = self
+            .image_data
+            .take()
+            .or_else(|| self.image_data_deprecated.take());
+
+        let image_path = self
+            .image_path
+            .as_ref()
+            .map(|path| path.to_string_lossy().into_owned())
+            .or_else(|| self.image_path_deprecated.clone());
+        let icon = [image_path.as_deref(), Some(app_icon)]
+            .into_iter()
+            .flatten()
+            .filter(|icon| !icon.is_empty())
+            .find_map(find_icon_path);
+        let icon_data = match icon {
+            Some(_) => None,
+            None => self.icon_data.take(),
+        };
+
+        (icon, image_data.into_iter().chain(icon_data).collect())
+    }
+
+    fn border_color(&self) -> Option<Color> {
+        [&self.border_color, &self.frame_color]
+            .into_iter()
+            .flatten()
+            .find_map(|color| hint_color(color))
+    }
+}
+
+/// Take out the inline reply action, which is shown as a text field rather than a button, and its
+/// label
+fn split_inline_reply(actions: Vec<(String, String)>) -> (Vec<(String, String)>, Option<String>) {
+    let mut reply = None;
+    let actions = actions
+        .into_iter()
+        .filter_map(|(key, label)| {
+            if key == INLINE_REPLY {
+                reply = Some(label);
+                None
+            } else {
+                Some((key, label))
+            }
+        })
+        .collect();
+    (actions, reply)
+}
+
+/// The urgency for a standard level, or one of KDE's levels
+fn urgency_level(level: i64) -> Option<Urgency> {
+    match level {
+        0 | 10 => Some(Urgency::Low),
+        1 | 50 | 70 => Some(Urgency::Normal),
+        2 | 90 => Some(Urgency::Critical),
+        _ => None,
+    }
+}
+
+/// A color from a hint, either a string like `#rrggbb` or a structure ending in RGBA bytes
+fn hint_color(color: &Value) -> Option<Color> {
+    let parsed = match color {
+        Value::Str(color) => parse_color(color.as_str()).map(|[r, g, b]| Color::from_rgb8(r, g, b)),
+        Value::Structure(fields) => {
+            let bytes: Vec<u8> = fields
+                .fields()
+                .iter()
+                .filter_map(|field| match field {
+                    Value::U8(byte) => Some(*byte),
+                    _ => None,
+                })
+                .collect();
+            match bytes[..] {
+                [.., r, g, b, a] => Some(Color::from_rgba8(r, g, b, f32::from(a) / 255.0)),
+                _ => None,
+            }
+        }
+        Value::Value(color) => hint_color(color),
+        _ => None,
+    };
+    if parsed.is_none() {
+        debug!("Unrecognized color hint {:?}", color);
+    }
+
+    parsed
+}
+
+/// The urgency for a vendor urgency hint, given as a level or a name like GNOME's priorities
+fn vendor_urgency(level: &Value) -> Option<Urgency> {
+    let urgency = match level {
+        Value::U8(level) => urgency_level((*level).into()),
+        Value::I32(level) => urgency_level((*level).into()),
+        Value::U32(level) => urgency_level((*level).into()),
+        Value::I64(level) => urgency_level(*level),
+        Value::Str(level) => match level.as_str().to_lowercase().as_str() {
+            "low" => Some(Urgency::Low),
+            "normal" | "high" => Some(Urgency::Normal),
+            "critical" | "urgent" => Some(Urgency::Critical),
+            _ => None,
+        },
+        Value::Value(level) => vendor_urgency(level),
+        _ => None,
+    };
+    if urgency.is_none() {
+        debug!("Unrecognized vendor urgency {:?}", level);
+    }
+
+    urgency
 }
 
 #[derive(Debug, Deserialize, Serialize, Type)]
@@ -352,10 +1129,373 @@ struct Hints {
 pub struct ImageData {
     pub width: i32,
     pub height: i32,
-    rowstride: i32,
+    pub rowstride: i32,
     pub has_alpha: bool,
-    bits_per_sample: i32,
-    channels: i32,
+    pub bits_per_sample: i32,
+    pub channels: i32,
     #[debug("Vec[{}]", data.len())]
     pub data: Vec<u8>,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::freedesktop::remove_tmp_image;
+    use crate::markup::{markup, plain};
+
+    #[test]
+    fn test_release_id() {
+        crate::config::init_default();
+        let (sender, _receiver) = mpsc::channel(1);
+        let mut interface = NotificationInterface::new(sender, ActiveNotifications::default());
+
+        let first = interface.get_next_id();
+        let second = interface.get_next_id();
+        assert_ne!(first, second);
+        assert_eq!(interface.used_ids.len(), 2);
+
+        // Closing notifications shrinks the used ids, without their ids being reused right away
+        interface.release_id(first);
+        interface.release_id(second);
+        assert!(interface.used_ids.is_empty());
+        assert_ne!(interface.get_next_id(), first);
+    }
+
+    #[test]
+    fn test_signal_many() {
+        let (sender, mut receiver) = mpsc::unbounded();
+        let mut signaller = NotificationSignaller {
+            sender,
+            active: ActiveNotifications::default(),
+        };
+
+        // Dismissing far more notifications than were ever queued at once before
+        for id in 1..=1000 {
+            signaller.close_notification(id, NotificationClosedReason::DismissedByUser);
+        }
+        let mut signals = 0;
+        while let Ok(Some(_)) = receiver.try_next() {
+            signals += 1;
+        }
+        assert_eq!(signals, 1000);
+    }
+
+    #[test]
+    fn test_notification_replied() {
+        let (sender, mut receiver) = mpsc::unbounded();
+        let mut signaller = NotificationSignaller {
+            sender,
+            active: ActiveNotifications::default(),
+        };
+
+        signaller.notification_replied(4, "On my way".into());
+        assert!(matches!(
+            receiver.try_next(),
+            Ok(Some(DbusSignal::NotificationReplied(4, text))) if text == "On my way"
+        ));
+        assert!(matches!(
+            receiver.try_next(),
+            Ok(Some(DbusSignal::ActionInvoked(4, key))) if key == INLINE_REPLY
+        ));
+        assert!(receiver.try_next().is_err());
+    }
+
+    #[test]
+    fn test_min_display_time() {
+        let time = Local::now();
+        assert_eq!(
+            expire_time(time, 1, Urgency::Normal, 2000),
+            Some(time + TimeDelta::seconds(2))
+        );
+        assert_eq!(
+            expire_time(time, 5000, Urgency::Normal, 2000),
+            Some(time + TimeDelta::seconds(5))
+        );
+        assert_eq!(
+            expire_time(time, -1, Urgency::Normal, 2000),
+            Some(time + TimeDelta::minutes(1))
+        );
+        assert_eq!(expire_time(time, 0, Urgency::Normal, 2000), None);
+        assert_eq!(expire_time(time, 1, Urgency::Critical, 2000), None);
+    }
+
+    #[test]
+    fn test_duplicate_of() {
+        let active = ActiveNotifications::default();
+        active.set(
+            [
+                Notification::test(1, "Summary", Some("body")),
+                Notification::test(2, "Other", Some("body")),
+            ]
+            .iter(),
+        );
+
+        // Two identical notifications collapse to the first's id
+        let body = markup("body".into(), 1);
+        assert_eq!(
+            active.duplicate_of(("App", "Summary", Some(&body[..]))),
+            Some(1)
+        );
+        assert_eq!(
+            active.duplicate_of(("App", "Different", Some(&body[..]))),
+            None
+        );
+        // Bodies with the same text but different markup aren't duplicates
+        let bold = markup("<b>body</b>".into(), 1);
+        assert_eq!(
+            active.duplicate_of(("App", "Summary", Some(&bold[..]))),
+            None
+        );
+    }
+
+    #[test]
+    fn test_no_markup() {
+        let markup_capabilities = ["body-hyperlinks", "body-markup", "body-images"];
+        assert!(markup_capabilities
+            .iter()
+            .all(|capability| capabilities(false).contains(&capability.to_string())));
+        assert_eq!(
+            capabilities(true),
+            vec!["actions", "body", "inline-reply", "persistence", "sound"]
+        );
+
+        // Tags stay literal
+        let body = "<b>bold</b>".to_string();
+        assert_eq!(
+            body_elements(body.clone(), "App", None, true, &[], 1),
+            Some(plain(body, 1))
+        );
+    }
+
+    #[test]
+    fn test_take_icon() {
+        let image_data = || ImageData {
+            width: 1,
+            height: 1,
+            rowstride: 4,
+            has_alpha: true,
+            bits_per_sample: 8,
+            channels: 4,
+            data: vec![0xd7, 0x99, 0x21, 0xff],
+        };
+
+        // Apps that only send the deprecated icon data, with an icon name that isn't installed
+        let mut hints = Hints {
+            icon_data: Some(image_data()),
+            ..Hints::default()
+        };
+        let (icon, icon_data) = hints.take_icon("not-an-installed-icon");
+        assert_eq!(icon, None);
+        let [icon_data] = &icon_data[..] else {
+            panic!("Icon data not chosen");
+        };
+        let saved = tmp_image_from_data(icon_data).expect("Unable to save the icon data");
+        assert!(saved.exists());
+        remove_tmp_image(&saved);
+
+        // An image path that exists comes before the icon data
+        let dir = tempfile::tempdir().expect("Unable to create the test directory");
+        let path = dir.path().join("image-path.png");
+        std::fs::write(&path, b"").expect("Unable to write image path");
+        let mut hints = Hints {
+            image_path_deprecated: Some(format!("file://{}", path.display())),
+            icon_data: Some(image_data()),
+            ..Hints::default()
+        };
+        let (icon, icon_data) = hints.take_icon("not-an-installed-icon");
+        assert_eq!(icon, Some(path.clone()));
+        assert!(icon_data.is_empty());
+
+        // Image data comes first, with the image path shown until it is saved
+        let mut hints = Hints {
+            image_data: Some(image_data()),
+            image_path: Some(path.clone()),
+            icon_data: Some(image_data()),
+            ..Hints::default()
+        };
+        let (icon, image_data_to_save) = hints.take_icon("not-an-installed-icon");
+        assert_eq!(icon, Some(path));
+        assert_eq!(image_data_to_save.len(), 1);
+
+        // Image data under its older name counts the same, with the icon data after it when
+        // nothing else resolves
+        let mut hints = Hints {
+            image_data_deprecated: Some(image_data()),
+            image_path: Some("not-an-installed-icon".into()),
+            icon_data: Some(image_data()),
+            ..Hints::default()
+        };
+        let (icon, image_data_to_save) = hints.take_icon("");
+        assert_eq!(icon, None);
+        assert_eq!(image_data_to_save.len(), 2);
+    }
+
+    #[test]
+    fn test_hint_color() {
+        let color = Color::from_rgb8(0x45, 0x85, 0x88);
+        assert_eq!(hint_color(&Value::from("#458588")), Some(color));
+        assert_eq!(
+            hint_color(&Value::from((0u32, 0x45u8, 0x85u8, 0x88u8, 0xffu8))),
+            Some(color)
+        );
+        assert_eq!(hint_color(&Value::from("not a color")), None);
+        assert_eq!(hint_color(&Value::from(3u32)), None);
+
+        // The hint borders the notification
+        let hints = Hints {
+            frame_color: Some(Value::from("#458588").try_into().unwrap()),
+            ..Hints::default()
+        };
+        assert_eq!(hints.border_color(), Some(color));
+    }
+
+    #[tokio::test]
+    async fn test_load_body_images() {
+        let uri = "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR4\
+            nGP4z8DwHwAFAAH/iZk9HQAAAABJRU5ErkJggg==";
+        let body = body_elements(
+            format!(r#"<img src="{uri}" alt="dot"/>"#),
+            "App",
+            None,
+            false,
+            &[],
+            1,
+        )
+        .unwrap();
+        // Parsing leaves the image to be saved in the background
+        assert!(matches!(
+            &body[..],
+            [BodyElement::Image { src, .. }] if is_data_uri(src)
+        ));
+
+        let (sender, mut receiver) = mpsc::channel(1);
+        load_body_images(7, Arc::new(body), false, sender);
+        let Some(DbusMessage::BodyImagesLoaded { id, body, .. }) = receiver.next().await else {
+            panic!("Body images not loaded");
+        };
+        assert_eq!(id, 7);
+        match &body[..] {
+            [BodyElement::Image { src, alt }] => {
+                assert!(src.is_file());
+                assert_eq!(alt, "dot");
+                let _ = std::fs::remove_file(src);
+            }
+            body => panic!("Unexpected body {body:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_load_image_data() {
+        let image_data = || ImageData {
+            width: 1,
+            height: 1,
+            rowstride: 4,
+            has_alpha: true,
+            bits_per_sample: 8,
+            channels: 4,
+            data: vec![0xd7, 0x99, 0x21, 0xff],
+        };
+        let bad_data = || ImageData {
+            width: 0,
+            height: 1,
+            rowstride: 0,
+            has_alpha: true,
+            bits_per_sample: 8,
+            channels: 4,
+            data: vec![],
+        };
+        let time = Local::now();
+
+        // The icon waits for the GUI to make room rather than being dropped
+        let (mut sender, mut receiver) = mpsc::channel(0);
+        sender
+            .try_send(DbusMessage::SetPaused(true))
+            .expect("Unable to fill the channel");
+        load_image_data(3, time, vec![image_data()], sender);
+        assert!(matches!(
+            receiver.next().await,
+            Some(DbusMessage::SetPaused(true))
+        ));
+        let Some(DbusMessage::IconLoaded { id, icon, .. }) = receiver.next().await else {
+            panic!("Icon not loaded");
+        };
+        assert_eq!(id, 3);
+        assert!(icon.is_file());
+        let _ = std::fs::remove_file(icon);
+
+        // Data that can't be saved leaves the notification with its other icon
+        let (sender, mut receiver) = mpsc::channel(1);
+        load_image_data(4, time, vec![bad_data()], sender);
+        assert!(receiver.next().await.is_none());
+
+        // The next image data in the chain is saved when one can't be
+        let (sender, mut receiver) = mpsc::channel(1);
+        load_image_data(5, time, vec![bad_data(), image_data()], sender);
+        let Some(DbusMessage::IconLoaded { id, icon, .. }) = receiver.next().await else {
+            panic!("Icon not loaded");
+        };
+        assert_eq!(id, 5);
+        assert!(icon.is_file());
+        let _ = std::fs::remove_file(icon);
+    }
+
+    #[test]
+    fn test_split_inline_reply() {
+        let actions = vec![
+            ("default".to_string(), "Open".to_string()),
+            ("inline-reply".to_string(), "Reply".to_string()),
+            ("mark-read".to_string(), "Mark as read".to_string()),
+        ];
+        let (actions, reply) = split_inline_reply(actions);
+        assert_eq!(reply.as_deref(), Some("Reply"));
+        assert_eq!(
+            actions
+                .iter()
+                .map(|(key, _)| key.as_str())
+                .collect::<Vec<_>>(),
+            ["default", "mark-read"]
+        );
+
+        let (actions, reply) = split_inline_reply(vec![("ok".into(), "OK".into())]);
+        assert_eq!((actions.len(), reply), (1, None));
+    }
+
+    #[test]
+    fn test_vendor_urgency() {
+        let hints =
+            |urgency, x_kde_urgency_hint: Option<Value>, x_gnome_priority: Option<Value>| Hints {
+                urgency,
+                x_kde_urgency_hint: x_kde_urgency_hint.map(|value| value.try_into().unwrap()),
+                x_gnome_priority: x_gnome_priority.map(|value| value.try_into().unwrap()),
+                ..Default::default()
+            };
+
+        assert_eq!(hints(None, None, None).urgency(), Urgency::Normal);
+        assert_eq!(
+            hints(None, Some(Value::I32(90)), None).urgency(),
+            Urgency::Critical
+        );
+        assert_eq!(
+            hints(None, Some(Value::U8(0)), None).urgency(),
+            Urgency::Low
+        );
+        assert_eq!(
+            hints(None, None, Some(Value::from("urgent"))).urgency(),
+            Urgency::Critical
+        );
+        assert_eq!(
+            hints(None, None, Some(Value::from("high"))).urgency(),
+            Urgency::Normal
+        );
+        // Unrecognized vendor hints are skipped
+        assert_eq!(
+            hints(None, Some(Value::from("soon")), Some(Value::from("low"))).urgency(),
+            Urgency::Low
+        );
+        // The standard urgency takes precedence
+        assert_eq!(
+            hints(Some(0), Some(Value::I32(90)), None).urgency(),
+            Urgency::Low
+        );
+    }
+}
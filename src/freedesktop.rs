@@ -1,12 +1,19 @@
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use freedesktop_desktop_entry::{default_paths, get_languages_from_env, Iter};
-use image::{RgbImage, RgbaImage};
+use image::{ImageError, ImageReader, RgbImage, Rgba, RgbaImage};
 use log::{debug, trace, warn};
 use rand::distributions::Alphanumeric;
 use rand::Rng;
 
+use crate::config::config;
 use crate::dbus::ImageData;
+use crate::hooks;
+use crate::{paths, process};
 
 pub fn find_app_name(desktop_entry_name: &str) -> Option<String> {
     let locales = get_languages_from_env();
@@ -28,7 +35,43 @@ pub fn find_app_name(desktop_entry_name: &str) -> Option<String> {
     None
 }
 
+/// Gets the icon name (or path) from the desktop entry's `Icon=` field
+pub fn find_app_icon(desktop_entry_name: &str) -> Option<String> {
+    find_app_icon_in(default_paths(), desktop_entry_name)
+}
+
+fn find_app_icon_in(
+    paths: impl Iterator<Item = PathBuf>,
+    desktop_entry_name: &str,
+) -> Option<String> {
+    let locales = get_languages_from_env();
+    let mut entries = Iter::new(paths).entries(Some(&locales));
+
+    let desktop_entry_name = desktop_entry_name.to_lowercase();
+    if let Some(desktop_entry) =
+        entries.find(|desktop_entry| desktop_entry.appid.to_lowercase() == desktop_entry_name)
+    {
+        if let Some(icon) = desktop_entry.icon() {
+            return Some(icon.to_string());
+        } else {
+            debug!("No icon found for {}", desktop_entry_name);
+        }
+    } else {
+        debug!("No desktop entry found for {}", desktop_entry_name);
+    }
+
+    None
+}
+
+/// A new path in the image directory for an image to be saved to, creating the directory if it
+/// doesn't exist
 fn tmp_path() -> Option<PathBuf> {
+    let dir = paths::image_dir();
+    if let Err(err) = std::fs::create_dir_all(&dir) {
+        warn!("Unable to create {} with error {}", dir.display(), err);
+        return None;
+    }
+
     let mut tries = 0;
     while tries < 3 {
         tries += 1;
@@ -38,7 +81,7 @@ fn tmp_path() -> Option<PathBuf> {
             .take(8)
             .map(char::from)
             .collect();
-        let path = PathBuf::from(format!("/tmp/{}.png", filename));
+        let path = dir.join(format!("{}.png", filename));
 
         if path.try_exists().is_ok_and(|exists| !exists) {
             return Some(path);
@@ -49,27 +92,90 @@ fn tmp_path() -> Option<PathBuf> {
     None
 }
 
+/// Deletes an image saved from a notification, leaving paths outside the image directory alone
+pub fn remove_tmp_image(path: &Path) {
+    if !path.starts_with(paths::image_dir()) {
+        return;
+    }
+
+    trace!("Removing image {}", path.display());
+    if let Err(err) = std::fs::remove_file(path) {
+        warn!("Unable to remove {} with error {}", path.display(), err);
+    }
+}
+
+/// The pixels of image data without the padding at the end of each row, or None if the image is
+/// empty, isn't 8 bits per sample, or its data is too short for its dimensions
+fn packed_pixels(image_data: &ImageData) -> Option<Vec<u8>> {
+    if image_data.width <= 0 || image_data.height <= 0 {
+        debug!(
+            "Invalid image data dimensions {}x{}",
+            image_data.width, image_data.height
+        );
+        return None;
+    }
+    if image_data.bits_per_sample != 8 {
+        debug!(
+            "Unsupported image data with {} bits per sample",
+            image_data.bits_per_sample
+        );
+        return None;
+    }
+    let channels = if image_data.has_alpha { 4 } else { 3 };
+    let (width, height) = (image_data.width as usize, image_data.height as usize);
+    let row_len = width * channels;
+    let rowstride = usize::try_from(image_data.rowstride).unwrap_or(0);
+    if rowstride < row_len {
+        debug!(
+            "Image data rowstride {} is shorter than its {} byte rows",
+            image_data.rowstride, row_len
+        );
+        return None;
+    }
+    // The last row isn't padded
+    let expected_len = rowstride * (height - 1) + row_len;
+    if image_data.data.len() < expected_len {
+        debug!(
+            "Image data is {} bytes but {}x{} needs {}",
+            image_data.data.len(),
+            width,
+            height,
+            expected_len
+        );
+        return None;
+    }
+
+    Some(
+        image_data
+            .data
+            .chunks(rowstride)
+            .take(height)
+            .flat_map(|row| &row[..row_len])
+            .copied()
+            .collect(),
+    )
+}
+
 pub fn tmp_image_from_data(image_data: &ImageData) -> Option<PathBuf> {
-    // Generate a path in the /tmp directory
+    // Reject invalid image data before creating the image
+    let pixels = packed_pixels(image_data)?;
+
+    // Generate a path in the temporary directory
     let path = tmp_path()?;
 
     // Create and save the image
     let save_result = if image_data.has_alpha {
-        let Some(image) = RgbaImage::from_raw(
-            image_data.width as u32,
-            image_data.height as u32,
-            image_data.data.clone(),
-        ) else {
+        let Some(image) =
+            RgbaImage::from_raw(image_data.width as u32, image_data.height as u32, pixels)
+        else {
             warn!("Failed to create RGBA image");
             return None;
         };
         image.save(&path)
     } else {
-        let Some(image) = RgbImage::from_raw(
-            image_data.width as u32,
-            image_data.height as u32,
-            image_data.data.clone(),
-        ) else {
+        let Some(image) =
+            RgbImage::from_raw(image_data.width as u32, image_data.height as u32, pixels)
+        else {
             warn!("Failed to create RGB image");
             return None;
         };
@@ -88,6 +194,178 @@ pub fn tmp_image_from_data(image_data: &ImageData) -> Option<PathBuf> {
     Some(path)
 }
 
+/// The most common vivid color of an image, or None if it can't be decoded, like an SVG, or is
+/// mostly transparent or gray
+pub fn dominant_color(path: &Path) -> Option<[u8; 3]> {
+    let image = ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(ImageError::from)
+        .and_then(|reader| reader.decode())
+        .inspect_err(|err| {
+            trace!(
+                "Unable to decode {} for its color with error {}",
+                path.display(),
+                err
+            )
+        })
+        .ok()?;
+
+    // A thumbnail has plenty of pixels to find the dominant color, and is much quicker to scan
+    dominant_color_of(&image.thumbnail(32, 32).into_rgba8())
+}
+
+fn dominant_color_of(image: &RgbaImage) -> Option<[u8; 3]> {
+    // Count the pixels of each color, quantized to 4 bits per channel, summing the exact colors to
+    // average them
+    let mut buckets: HashMap<[u8; 3], (u32, [u32; 3])> = HashMap::new();
+    for &Rgba([r, g, b, a]) in image.pixels() {
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        // Skip transparent, dark, and gray pixels, which make poor accents
+        if a < 128 || max < 48 || max - min < 48 {
+            continue;
+        }
+
+        let (count, sum) = buckets.entry([r >> 4, g >> 4, b >> 4]).or_default();
+        *count += 1;
+        sum[0] += r as u32;
+        sum[1] += g as u32;
+        sum[2] += b as u32;
+    }
+
+    let (count, sum) = buckets.into_values().max_by_key(|(count, _)| *count)?;
+    Some(sum.map(|channel| (channel / count) as u8))
+}
+
+/// Make the corners of an image transparent outside circles of the radius, in pixels, with the
+/// edges antialiased
+pub fn round_corners(image: &mut RgbaImage, radius: f32) {
+    let (width, height) = image.dimensions();
+    let radius = radius.min(width.min(height) as f32 / 2.0);
+    if radius <= 0.0 {
+        return;
+    }
+
+    for (x, y, Rgba(pixel)) in image.enumerate_pixels_mut() {
+        // The distance of the pixel's center past the center of its corner's circle, if it is in
+        // a corner
+        let past = |position: u32, length: u32| {
+            let center = position as f32 + 0.5;
+            (radius - center)
+                .max(center - (length as f32 - radius))
+                .max(0.0)
+        };
+        let distance = past(x, width).hypot(past(y, height));
+        let coverage = (radius - distance + 0.5).clamp(0.0, 1.0);
+        pixel[3] = (pixel[3] as f32 * coverage).round() as u8;
+    }
+}
+
+/// Whether an image source is a remote http(s) URL rather than a local path
+pub fn is_remote_image(src: &Path) -> bool {
+    src.to_str().is_some_and(|src| {
+        let src = src.to_lowercase();
+        src.starts_with("http://") || src.starts_with("https://")
+    })
+}
+
+/// Whether an image source is an inline `data:` URI rather than a path
+pub fn is_data_uri(src: &Path) -> bool {
+    src.to_str()
+        .and_then(|src| src.get(..5))
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("data:"))
+}
+
+/// Decodes a base64 `data:` URI image and saves it as a PNG in the temporary directory
+pub fn tmp_image_from_data_uri(uri: &str) -> Option<PathBuf> {
+    /// Maximum size of the encoded data in bytes
+    const MAX_SIZE: usize = 10_000_000;
+
+    let Some((metadata, data)) = uri.get(5..).and_then(|uri| uri.split_once(',')) else {
+        warn!("Invalid data URI");
+        return None;
+    };
+    if !metadata
+        .split(';')
+        .any(|parameter| parameter.eq_ignore_ascii_case("base64"))
+    {
+        warn!(
+            "Unsupported data URI {}, only base64 is supported",
+            metadata
+        );
+        return None;
+    }
+    if data.len() > MAX_SIZE {
+        warn!("Data URI image is {} bytes, over {}", data.len(), MAX_SIZE);
+        return None;
+    }
+
+    // Line breaks are allowed in the base64, but not by the decoder
+    let data: String = data.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    let bytes = match STANDARD.decode(data) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            warn!("Failed to decode data URI with error {}", err);
+            return None;
+        }
+    };
+
+    // Re-encode the image as a PNG, which also makes sure it is actually an image
+    let path = tmp_path()?;
+    if let Err(err) = image::load_from_memory(&bytes).and_then(|image| image.save(&path)) {
+        warn!("Failed to save data URI image with error {}", err);
+        return None;
+    }
+
+    Some(path)
+}
+
+/// Downloads a remote image and saves it as a PNG in the temporary directory. The download is
+/// limited in size and time, since the URL comes from an arbitrary notification.
+pub fn download_image(url: &str) -> Option<PathBuf> {
+    /// Maximum download size in bytes
+    const MAX_SIZE: u32 = 10_000_000;
+    /// Maximum download time in seconds
+    const TIMEOUT: u32 = 10;
+
+    let path = tmp_path()?;
+    let download_path = path.with_extension("download");
+
+    debug!("Downloading {} to {}", url, download_path.display());
+    let status = Command::new("curl")
+        .args(["--silent", "--fail", "--location", "--proto", "=http,https"])
+        .args(["--max-filesize", &MAX_SIZE.to_string()])
+        .args(["--max-time", &TIMEOUT.to_string()])
+        .arg("--output")
+        .arg(&download_path)
+        .arg(url)
+        .status();
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => {
+            warn!("Failed to download {} with {}", url, status);
+            return None;
+        }
+        Err(err) => {
+            warn!("Failed to download {} with error {}", url, err);
+            return None;
+        }
+    }
+
+    // Re-encode the image as a PNG, which also makes sure it is actually an image
+    let image = ImageReader::open(&download_path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(ImageError::from)
+        .and_then(|reader| reader.decode());
+    let _ = std::fs::remove_file(&download_path);
+    if let Err(err) = image.and_then(|image| image.save(&path)) {
+        warn!("Failed to save image from {} with error {}", url, err);
+        return None;
+    }
+
+    Some(path)
+}
+
 /// Gets a path for an icon by first checking if the passed icon is a path that
 /// exists, and if not, searches for a matching freedesktop icon.
 pub fn find_icon_path(icon_name_or_path: &str) -> Option<PathBuf> {
@@ -113,3 +391,330 @@ pub fn find_icon_path(icon_name_or_path: &str) -> Option<PathBuf> {
             .with_theme(THEME)
             .find())
 }
+
+/// Gets a path for the default icon, like [`find_icon_path`], but falling back to the bundled
+/// icon if it can't be found
+pub fn find_default_icon(icon_name_or_path: &str) -> Option<PathBuf> {
+    find_icon_path(icon_name_or_path).or_else(|| {
+        warn!("Unable to find default icon {icon_name_or_path}, using the bundled one");
+        bundled_icon()
+    })
+}
+
+/// Writes the icon bundled into the binary to the temporary directory, for when the icon theme
+/// doesn't have a default icon
+fn bundled_icon() -> Option<PathBuf> {
+    const ICON: &[u8] = include_bytes!("../assets/default-icon.png");
+
+    let path = paths::runtime_dir().join(format!("{}-default-icon.png", env!("CARGO_PKG_NAME")));
+    if let Err(err) = std::fs::write(&path, ICON) {
+        warn!(
+            "Unable to write the bundled icon to {} with error {}",
+            path.display(),
+            err
+        );
+        return None;
+    }
+
+    Some(path)
+}
+
+/// Freedesktop Sound Theme name
+const SOUND_THEME: &str = "freedesktop";
+/// Sound file extensions, in order of preference
+const SOUND_EXTENSIONS: [&str; 3] = ["oga", "ogg", "wav"];
+
+/// The directories to search for sounds in, the user's data dir first
+fn sound_dirs() -> Vec<PathBuf> {
+    let data_dirs = std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or("/usr/local/share:/usr/share".into());
+
+    dirs::data_dir()
+        .into_iter()
+        .chain(std::env::split_paths(&data_dirs))
+        .map(|dir| dir.join("sounds"))
+        .collect()
+}
+
+/// Gets a path for a sound by searching for a matching freedesktop sound
+/// theme sound, analogous to [`find_icon_path`].
+pub fn find_sound_path(sound_name: &str) -> Option<PathBuf> {
+    find_sound_path_in(&sound_dirs(), sound_name)
+}
+
+fn find_sound_path_in(dirs: &[PathBuf], sound_name: &str) -> Option<PathBuf> {
+    // Sound names fall back by removing dash-separated suffixes, so
+    // "message-new-instant" then "message-new" then "message"
+    let mut name = sound_name;
+    loop {
+        trace!("Looking for sound {name}");
+        for dir in dirs {
+            for subdir in [dir.join(SOUND_THEME).join("stereo"), dir.clone()] {
+                for extension in SOUND_EXTENSIONS {
+                    let path = subdir.join(name).with_extension(extension);
+                    if path.exists() {
+                        return Some(path);
+                    }
+                }
+            }
+        }
+
+        match name.rsplit_once('-') {
+            Some((prefix, _)) => name = prefix,
+            None => break,
+        }
+    }
+
+    debug!("No sound found for {}", sound_name);
+    None
+}
+
+/// Plays a sound file in the background with the configured player
+pub fn play_sound(path: &Path) {
+    let file = path.to_string_lossy();
+    let Some(mut command) = hooks::command(&config().sound_player, &[("file", &file)]) else {
+        warn!("Empty sound player, not playing {}", path.display());
+        return;
+    };
+
+    debug!("Playing sound {} with {:?}", path.display(), command);
+    process::spawn(format!("play {}", path.display()), &mut command);
+}
+
+/// Whether a link is safe to hand to `xdg-open`, which only http(s) links are
+fn is_openable_link(link: &str) -> bool {
+    let lowercase = link.to_lowercase();
+    let Some(rest) = lowercase
+        .strip_prefix("https://")
+        .or_else(|| lowercase.strip_prefix("http://"))
+    else {
+        return false;
+    };
+
+    !rest.is_empty() && !link.chars().any(|c| c.is_whitespace() || c.is_control())
+}
+
+/// Opens an http(s) link with `xdg-open` in the background, refusing other schemes
+pub fn open_link(link: &str) {
+    if !is_openable_link(link) {
+        warn!("Refusing to open link {:?}", link);
+        return;
+    }
+
+    process::spawn(
+        format!("open {}", link),
+        Command::new("xdg-open")
+            .arg(link)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null()),
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bundled_icon() {
+        let path = bundled_icon().expect("Unable to write the bundled icon");
+        let icon = image::open(&path).expect("Bundled icon isn't an image");
+        assert_eq!((icon.width(), icon.height()), (48, 48));
+    }
+
+    fn touch(path: &Path) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, []).unwrap();
+    }
+
+    #[test]
+    fn test_find_sound_path() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        let user = root.join("user");
+        let system = root.join("system");
+        let dirs = [user.clone(), system.clone()];
+
+        touch(&system.join("freedesktop/stereo/message-new-instant.oga"));
+        touch(&system.join("freedesktop/stereo/bell.wav"));
+        touch(&user.join("freedesktop/stereo/bell.oga"));
+        touch(&user.join("freedesktop/stereo/message.oga"));
+        touch(&system.join("complete.ogg"));
+
+        // An exact match anywhere beats a fallback name in the user dir
+        assert_eq!(
+            find_sound_path_in(&dirs, "message-new-instant"),
+            Some(system.join("freedesktop/stereo/message-new-instant.oga"))
+        );
+        // The user dir beats the system dir
+        assert_eq!(
+            find_sound_path_in(&dirs, "bell"),
+            Some(user.join("freedesktop/stereo/bell.oga"))
+        );
+        // Dash-separated suffixes are removed until a match is found
+        assert_eq!(
+            find_sound_path_in(&dirs, "message-attachment"),
+            Some(user.join("freedesktop/stereo/message.oga"))
+        );
+        // Unthemed sounds in the sound dir itself are found last
+        assert_eq!(
+            find_sound_path_in(&dirs, "complete"),
+            Some(system.join("complete.ogg"))
+        );
+        assert_eq!(find_sound_path_in(&dirs, "missing"), None);
+    }
+
+    #[test]
+    fn test_is_openable_link() {
+        assert!(is_openable_link("https://example.com"));
+        assert!(is_openable_link("HTTP://example.com/path?query=1"));
+        assert!(!is_openable_link("file:///etc/passwd"));
+        assert!(!is_openable_link("javascript:alert(1)"));
+        assert!(!is_openable_link("example.com"));
+        assert!(!is_openable_link("https://"));
+        assert!(!is_openable_link("https://example.com/\nrm -rf"));
+        assert!(!is_openable_link("https://example.com/ --help"));
+    }
+
+    #[test]
+    fn test_is_remote_image() {
+        assert!(is_remote_image(Path::new("https://example.com/image.png")));
+        assert!(is_remote_image(Path::new("HTTP://example.com/image.png")));
+        assert!(!is_remote_image(Path::new("/path/to/image.png")));
+        assert!(!is_remote_image(Path::new("file:///path/to/image.png")));
+    }
+
+    #[test]
+    fn test_tmp_image_from_data_uri() {
+        let uri = "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR4\
+            nGP4z8DwHwAFAAH/iZk9HQAAAABJRU5ErkJggg==";
+        assert!(is_data_uri(Path::new(uri)));
+        let path = tmp_image_from_data_uri(uri).unwrap();
+        let image = image::open(&path).unwrap().into_rgba8();
+        assert_eq!(image.dimensions(), (1, 1));
+        assert_eq!(image.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+        remove_tmp_image(&path);
+        assert!(!path.exists());
+        // Paths outside the image directory are left alone
+        let bundled = bundled_icon().expect("Unable to write the bundled icon");
+        remove_tmp_image(&bundled);
+        assert!(bundled.exists());
+
+        assert!(!is_data_uri(Path::new("/path/to/data:image")));
+        assert_eq!(
+            tmp_image_from_data_uri("data:image/png;base64,not base64!"),
+            None
+        );
+        assert_eq!(
+            tmp_image_from_data_uri("data:image/png;base64,aGVsbG8="),
+            None
+        );
+        assert_eq!(tmp_image_from_data_uri("data:image/svg+xml,<svg/>"), None);
+    }
+
+    fn image_data(width: i32, height: i32, len: usize) -> ImageData {
+        ImageData {
+            width,
+            height,
+            rowstride: width * 4,
+            has_alpha: true,
+            bits_per_sample: 8,
+            channels: 4,
+            data: vec![0; len],
+        }
+    }
+
+    #[test]
+    fn test_tmp_image_from_invalid_data() {
+        assert_eq!(tmp_image_from_data(&image_data(0, 2, 0)), None);
+        assert_eq!(tmp_image_from_data(&image_data(2, -1, 8)), None);
+        assert_eq!(tmp_image_from_data(&image_data(2, 2, 15)), None);
+        assert_eq!(tmp_image_from_data(&image_data(2, 2, 0)), None);
+
+        let mut sixteen_bit = image_data(2, 2, 16);
+        sixteen_bit.bits_per_sample = 16;
+        assert_eq!(tmp_image_from_data(&sixteen_bit), None);
+        let mut overlapping_rows = image_data(2, 2, 16);
+        overlapping_rows.rowstride = 4;
+        assert_eq!(tmp_image_from_data(&overlapping_rows), None);
+    }
+
+    #[test]
+    fn test_padded_rowstride() {
+        // RGB rows padded to 4 bytes, with the last row unpadded
+        let image_data = ImageData {
+            width: 3,
+            height: 2,
+            rowstride: 12,
+            has_alpha: false,
+            bits_per_sample: 8,
+            channels: 3,
+            data: [&[1; 9][..], &[0xff; 3], &[0; 9]].concat(),
+        };
+        let mut pixels = vec![1; 9];
+        pixels.extend([0; 9]);
+        assert_eq!(packed_pixels(&image_data), Some(pixels));
+
+        let path = tmp_image_from_data(&image_data).expect("Unable to save the image");
+        let image = ImageReader::open(&path)
+            .unwrap()
+            .decode()
+            .unwrap()
+            .to_rgb8();
+        assert_eq!(image.get_pixel(2, 0).0, [1; 3]);
+        assert_eq!(image.get_pixel(0, 1).0, [0; 3]);
+        remove_tmp_image(&path);
+
+        // Only the last row can be missing its padding
+        let truncated = ImageData {
+            data: image_data.data[..20].to_vec(),
+            ..image_data
+        };
+        assert_eq!(packed_pixels(&truncated), None);
+    }
+
+    #[test]
+    fn test_find_app_icon() {
+        let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/applications");
+        assert_eq!(
+            find_app_icon_in(std::iter::once(fixtures.clone()), "org.example.Fixture"),
+            Some("org.example.Fixture-icon".to_string())
+        );
+        assert_eq!(
+            find_app_icon_in(std::iter::once(fixtures), "org.example.Missing"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_dominant_color() {
+        let mut image = RgbaImage::from_pixel(4, 4, Rgba([30, 60, 200, 255]));
+        // Gray and transparent pixels are ignored even when they outnumber the accent
+        for x in 0..4 {
+            for y in 0..3 {
+                image.put_pixel(x, y, Rgba([128, 128, 128, 255]));
+            }
+        }
+        image.put_pixel(0, 3, Rgba([255, 0, 0, 0]));
+        assert_eq!(dominant_color_of(&image), Some([30, 60, 200]));
+
+        let gray = RgbaImage::from_pixel(4, 4, Rgba([200, 200, 200, 255]));
+        assert_eq!(dominant_color_of(&gray), None);
+    }
+
+    #[test]
+    fn test_round_corners() {
+        let mut image = RgbaImage::from_pixel(10, 10, Rgba([255, 255, 255, 255]));
+        round_corners(&mut image, 4.0);
+
+        let alpha = |x, y| image.get_pixel(x, y).0[3];
+        assert_eq!([alpha(0, 0), alpha(9, 0), alpha(0, 9), alpha(9, 9)], [0; 4]);
+        assert_eq!(
+            [alpha(5, 5), alpha(0, 5), alpha(5, 0), alpha(9, 5)],
+            [255; 4]
+        );
+        // The edge of the curve is partly transparent
+        assert!((1..255).contains(&alpha(1, 0)));
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use iced::futures::channel::mpsc;
+use iced::futures::Stream;
+use log::debug;
+use wayland_client::backend::ObjectId;
+use wayland_client::globals::GlobalListContents;
+use wayland_client::protocol::wl_registry::{self, WlRegistry};
+use wayland_client::{event_created_child, Connection, Dispatch, Proxy, QueueHandle};
+use wayland_protocols_wlr::foreign_toplevel::v1::client::zwlr_foreign_toplevel_handle_v1::{
+    self, ZwlrForeignToplevelHandleV1,
+};
+use wayland_protocols_wlr::foreign_toplevel::v1::client::zwlr_foreign_toplevel_manager_v1::{
+    self, ZwlrForeignToplevelManagerV1,
+};
+
+use crate::wayland;
+
+/// Whether any window is fullscreen, sent each time that changes. Compositors without the
+/// wlr-foreign-toplevel-management protocol send nothing, so no window is ever fullscreen
+pub fn fullscreen() -> impl Stream<Item = bool> {
+    wayland::watch(watch)
+}
+
+fn watch(sender: mpsc::UnboundedSender<bool>) {
+    let Some((globals, queue)) = wayland::connect::<Toplevels>("fullscreen windows") else {
+        return;
+    };
+    let handle = queue.handle();
+    if let Err(err) = globals.bind::<ZwlrForeignToplevelManagerV1, _, _>(&handle, 1..=3, ()) {
+        debug!("Not watching for fullscreen windows, the compositor lacks the protocol: {err}");
+        return;
+    }
+
+    let mut toplevels = Toplevels {
+        sender,
+        pending: HashMap::new(),
+        fullscreen: HashMap::new(),
+        reported: false,
+    };
+    wayland::dispatch(queue, &mut toplevels, "fullscreen windows", |toplevels| {
+        toplevels.sender.is_closed()
+    });
+}
+
+/// The toplevel windows the compositor has told about
+struct Toplevels {
+    sender: mpsc::UnboundedSender<bool>,
+    /// The toplevels' fullscreen states that take effect at their next done event
+    pending: HashMap<ObjectId, bool>,
+    /// Whether each toplevel is fullscreen
+    fullscreen: HashMap<ObjectId, bool>,
+    /// Whether a window being fullscreen was last sent
+    reported: bool,
+}
+
+impl Toplevels {
+    /// Send whether any window is fullscreen, if that changed
+    fn report(&mut self) {
+        let fullscreen = self.fullscreen.values().any(|&fullscreen| fullscreen);
+        if fullscreen != self.reported {
+            debug!("A window being fullscreen changed to {fullscreen}");
+            self.reported = fullscreen;
+            let _ = self.sender.unbounded_send(fullscreen);
+        }
+    }
+}
+
+/// Whether a toplevel's state, an array of native endian state values, includes fullscreen
+fn is_fullscreen(state: &[u8]) -> bool {
+    state
+        .chunks_exact(4)
+        .filter_map(|value| value.try_into().ok())
+        .map(u32::from_ne_bytes)
+        .any(|value| value == zwlr_foreign_toplevel_handle_v1::State::Fullscreen as u32)
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for Toplevels {
+    fn event(
+        _: &mut Self,
+        _: &WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for Toplevels {
+    fn event(
+        _: &mut Self,
+        _: &ZwlrForeignToplevelManagerV1,
+        event: zwlr_foreign_toplevel_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let zwlr_foreign_toplevel_manager_v1::Event::Finished = event {
+            debug!("The compositor stopped sending toplevels");
+        }
+    }
+
+    event_created_child!(Toplevels, ZwlrForeignToplevelManagerV1, [
+        zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (ZwlrForeignToplevelHandleV1, ())
+    ]);
+}
+
+impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for Toplevels {
+    fn event(
+        toplevels: &mut Self,
+        toplevel: &ZwlrForeignToplevelHandleV1,
+        event: zwlr_foreign_toplevel_handle_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_foreign_toplevel_handle_v1::Event::State { state } => {
+                toplevels
+                    .pending
+                    .insert(toplevel.id(), is_fullscreen(&state));
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::Done => {
+                if let Some(fullscreen) = toplevels.pending.remove(&toplevel.id()) {
+                    toplevels.fullscreen.insert(toplevel.id(), fullscreen);
+                    toplevels.report();
+                }
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::Closed => {
+                toplevels.pending.remove(&toplevel.id());
+                toplevels.fullscreen.remove(&toplevel.id());
+                toplevels.report();
+                toplevel.destroy();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_fullscreen() {
+        let state = |values: &[u32]| -> Vec<u8> {
+            values
+                .iter()
+                .flat_map(|value| value.to_ne_bytes())
+                .collect()
+        };
+        assert!(!is_fullscreen(&state(&[])));
+        // Maximized and activated
+        assert!(!is_fullscreen(&state(&[0, 2])));
+        assert!(is_fullscreen(&state(&[2, 3])));
+    }
+}
@@ -0,0 +1,154 @@
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::notification::Urgency;
+use crate::paths;
+use crate::rotating_file::RotatingFile;
+
+/// Maximum size in bytes of the history file before it is rotated
+const MAX_SIZE: u64 = 1024 * 1024;
+
+/// A notification as saved to the history
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct HistoryEntry {
+    /// When the notification was sent, in RFC 3339
+    pub time: String,
+    /// The app name as sent, which decides whether the body has markup
+    pub app_name: String,
+    pub desktop_entry: Option<String>,
+    /// The display name for the application
+    pub name: String,
+    pub icon: PathBuf,
+    pub summary: String,
+    /// The body as sent, with its markup
+    pub body: String,
+    pub urgency: Urgency,
+}
+
+impl HistoryEntry {
+    /// When the notification was sent, or now if the time can't be read
+    pub fn time(&self) -> DateTime<Local> {
+        DateTime::parse_from_rfc3339(&self.time)
+            .map(|time| time.with_timezone(&Local))
+            .unwrap_or_else(|err| {
+                warn!("Invalid history time {} with error {}", self.time, err);
+                Local::now()
+            })
+    }
+}
+
+/// The notifications shown, saved as lines of JSON to the state directory, and rotated like the log
+pub struct History {
+    file: RotatingFile,
+}
+
+impl History {
+    pub fn open() -> Option<Self> {
+        let path = path();
+        RotatingFile::open(path.clone(), MAX_SIZE)
+            .inspect_err(|err| {
+                warn!(
+                    "Unable to open history {} with error {}",
+                    path.display(),
+                    err
+                )
+            })
+            .ok()
+            .map(|file| Self { file })
+    }
+
+    pub fn record(&mut self, entry: &HistoryEntry) {
+        let line = match serde_json::to_string(entry) {
+            Ok(line) => line,
+            Err(err) => {
+                warn!("Unable to serialize history entry with error {}", err);
+                return;
+            }
+        };
+        if let Err(err) = writeln!(self.file, "{}", line) {
+            warn!("Unable to write to history with error {}", err);
+        }
+    }
+}
+
+/// The path of the history file
+pub fn path() -> PathBuf {
+    paths::state_file("history")
+}
+
+/// The last `count` notifications in the history, oldest first
+pub fn recent(path: &Path, count: usize) -> Vec<HistoryEntry> {
+    // The older notifications are in the file rotated out
+    let mut old_path = path.to_path_buf().into_os_string();
+    old_path.push(".old");
+    let contents: String = [Path::new(&old_path), path]
+        .into_iter()
+        .filter_map(|path| match std::fs::read_to_string(path) {
+            Ok(contents) => Some(contents),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => {
+                warn!(
+                    "Unable to read history {} with error {}",
+                    path.display(),
+                    err
+                );
+                None
+            }
+        })
+        .collect();
+
+    parse_recent(&contents, count)
+}
+
+fn parse_recent(contents: &str, count: usize) -> Vec<HistoryEntry> {
+    let mut entries: Vec<HistoryEntry> = contents
+        .lines()
+        .rev()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            serde_json::from_str(line)
+                .inspect_err(|err| warn!("Skipping invalid history entry with error {}", err))
+                .ok()
+        })
+        .take(count)
+        .collect();
+    entries.reverse();
+    entries
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(summary: &str) -> HistoryEntry {
+        HistoryEntry {
+            time: "2024-05-01T12:00:00+00:00".into(),
+            app_name: "app".into(),
+            desktop_entry: None,
+            name: "App".into(),
+            icon: PathBuf::new(),
+            summary: summary.into(),
+            body: "<b>body</b>".into(),
+            urgency: Urgency::Normal,
+        }
+    }
+
+    #[test]
+    fn test_parse_recent() {
+        let contents = ["first", "second", "third"]
+            .map(|summary| serde_json::to_string(&entry(summary)).unwrap())
+            .join("\n");
+        let contents = format!("{contents}\nnot json\n\n");
+
+        assert_eq!(
+            parse_recent(&contents, 2),
+            vec![entry("second"), entry("third")]
+        );
+        assert_eq!(parse_recent(&contents, 10).len(), 3);
+        assert_eq!(parse_recent("", 10), vec![]);
+    }
+}
@@ -0,0 +1,155 @@
+use std::process::{Command, Stdio};
+
+use log::{debug, warn};
+
+use crate::config::config;
+use crate::notification::Notification;
+use crate::process;
+
+/// A notification event that can run a command
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Hook {
+    /// The notification arrived, including replacing another
+    Notify,
+    /// The user dismissed the notification
+    Dismiss,
+    /// The user invoked the action with this key
+    Action(String),
+}
+
+impl Hook {
+    /// The configured command for this event, if any
+    fn template(&self) -> Option<&'static str> {
+        match self {
+            Hook::Notify => config().on_notify.as_deref(),
+            Hook::Dismiss => config().on_dismiss.as_deref(),
+            Hook::Action(_) => config().on_action.as_deref(),
+        }
+    }
+}
+
+/// Replace the placeholders in a word with their values, and `%%` with `%`. Unknown placeholders
+/// are left as they are, and placeholders within the values aren't replaced
+fn substitute(word: &str, values: &[(&str, &str)]) -> String {
+    let mut result = String::with_capacity(word.len());
+    let mut rest = word;
+    while let Some(start) = rest.find('%') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        if let Some(after) = after.strip_prefix('%') {
+            result.push('%');
+            rest = after;
+            continue;
+        }
+
+        match values.iter().find(|(name, _)| after.starts_with(name)) {
+            Some((name, value)) => {
+                result.push_str(value);
+                rest = &after[name.len()..];
+            }
+            None => {
+                result.push('%');
+                rest = after;
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// The command for a template, split into words on whitespace with the placeholders replaced in
+/// each word. The values become part of a single argument however they are written, since no
+/// shell is involved
+pub fn command(template: &str, values: &[(&str, &str)]) -> Option<Command> {
+    let mut words = template
+        .split_whitespace()
+        .map(|word| substitute(word, values));
+    let mut command = Command::new(words.next()?);
+    command
+        .args(words)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    Some(command)
+}
+
+/// Runs the command configured for an event in the background, if there is one
+pub fn run(hook: Hook, notification: &Notification) {
+    let Some(template) = hook.template() else {
+        return;
+    };
+
+    let id = notification.id.to_string();
+    let action = match &hook {
+        Hook::Action(key) => key.as_str(),
+        _ => "",
+    };
+    let values = [
+        ("id", id.as_str()),
+        ("app", notification.name.as_str()),
+        ("summary", notification.summary.as_str()),
+        ("action", action),
+    ];
+    let Some(mut command) = command(template, &values) else {
+        warn!("Empty command for {:?}", hook);
+        return;
+    };
+
+    debug!(
+        "Running {:?} for {:?} of notification {}",
+        command, hook, id
+    );
+    process::spawn(format!("run hook {:?}", command), &mut command);
+}
+
+#[cfg(test)]
+mod test {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    #[test]
+    fn test_substitute() {
+        let values = [("id", "7"), ("app", "Mail"), ("action", "%id")];
+        assert_eq!(substitute("%id", &values), "7");
+        assert_eq!(substitute("--app=%app!", &values), "--app=Mail!");
+        assert_eq!(substitute("%%id %unknown %", &values), "%id %unknown %");
+        assert_eq!(substitute("%action", &values), "%id");
+    }
+
+    #[test]
+    fn test_command() {
+        let dir = tempfile::tempdir().expect("Unable to create the test directory");
+        let dir = dir.path();
+        let script = dir.join("hook.sh");
+        let output = dir.join("args");
+        std::fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\nprintf '%s\\n' \"$@\" > '{}'\n",
+                output.display()
+            ),
+        )
+        .expect("Unable to write the test script");
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))
+            .expect("Unable to make the test script executable");
+
+        let summary = "Hi; $(touch pwned) `id` \"there\"";
+        let values = [("id", "3"), ("app", "Mail"), ("summary", summary)];
+        let template = format!("{} %id  --from=%app %summary", script.display());
+        let status = command(&template, &values)
+            .expect("No command")
+            .current_dir(dir)
+            .status()
+            .expect("Unable to run the test script");
+        assert!(status.success());
+
+        let args = std::fs::read_to_string(&output).expect("The script didn't write its arguments");
+        assert_eq!(
+            args.lines().collect::<Vec<_>>(),
+            vec!["3", "--from=Mail", summary]
+        );
+        assert!(!dir.join("pwned").exists());
+        assert!(command("  ", &values).is_none());
+    }
+}
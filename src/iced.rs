@@ -1,18 +1,317 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-use chrono::Local;
-use iced::alignment::Horizontal;
-use iced::widget::{button, column, container, mouse_area, row, text, Column, Row};
-use iced::{window, Background, Border, Element, Length, Subscription, Task, Theme};
-use iced_layershell::reexport::{Anchor, NewLayerShellSettings};
+use chrono::{DateTime, Local, TimeDelta};
+use iced::alignment::{Horizontal, Vertical};
+use iced::mouse::ScrollDelta;
+use iced::widget::scrollable::RelativeOffset;
+use iced::widget::text::{Shaping, Wrapping};
+use iced::widget::{
+    button, column, container, mouse_area, row, scrollable, stack, text, text_input, Column, Row,
+    Space,
+};
+use iced::{window, Background, Border, ContentFit, Element, Length, Subscription, Task, Theme};
+use iced_layershell::reexport::{
+    Anchor, KeyboardInteractivity, Layer, NewLayerShellSettings, OutputOption,
+};
 use iced_layershell::settings::{LayerShellSettings, Settings, StartMode};
 use iced_layershell::{daemon, to_layer_message};
-use log::{debug, trace, warn};
+use log::{debug, error, trace, warn};
 
+use crate::appearance;
+use crate::clock::Clock;
+use crate::config::{
+    self, config, ActionAlign, ActionWidth, ColorScheme, EmptySummary, ImageAlt,
+    LayerShellFallback, Order, Output, Position, ThemeName, TimeBarPosition, UrgencyStyleConfig,
+};
 use crate::dbus::{self, DbusMessage, NotificationClosedReason, NotificationSignaller};
+use crate::freedesktop::{
+    find_default_icon, find_icon_path, is_data_uri, is_remote_image, open_link, play_sound,
+    remove_tmp_image, round_corners,
+};
+use crate::fullscreen;
+use crate::hooks::{self, Hook};
+use crate::idle;
 use crate::markup::{BodyElement, RichTextSpan};
-use crate::measuring_container::MeasuringContainer;
-use crate::notification::{notification_time, Notification, Urgency};
+use crate::measuring_container::{logical_pixels, MeasuringContainer};
+use crate::notification::{
+    ellipsize, find_code, notification_time, select_visible, sort_by_urgency, LayoutKind,
+    Notification, Urgency, DEFAULT_ACTION, INLINE_REPLY,
+};
+use crate::outputs;
+use crate::sliding_container::SlidingContainer;
+
+fn action_button<'a>(
+    label: String,
+    message: Message,
+    selected: bool,
+    width: Length,
+) -> Element<'a, Message> {
+    button(
+        text(label)
+            .size(FONT_SIZE)
+            .wrapping(Wrapping::None)
+            .align_x(Horizontal::Center),
+    )
+    .on_press(message)
+    .padding(SMALL)
+    // The button selected with the keyboard looks hovered
+    .style(move |theme: &Theme, status| match status {
+        _ if selected => button::Style {
+            background: Some(Background::Color(theme.palette().text)),
+            text_color: theme.palette().background,
+            border: iced::border::color(theme.palette().text).width(1.0),
+            ..Default::default()
+        },
+        button::Status::Active | button::Status::Disabled => button::Style {
+            background: None,
+            text_color: theme.palette().text,
+            border: iced::border::color(theme.palette().text).width(1.0),
+            ..Default::default()
+        },
+        button::Status::Hovered | button::Status::Pressed => button::Style {
+            background: Some(Background::Color(theme.palette().text)),
+            text_color: theme.palette().background,
+            border: iced::border::color(theme.palette().text).width(1.0),
+            ..Default::default()
+        },
+    })
+    .width(width)
+    .into()
+}
+
+fn close_button<'a>(message: Message) -> Element<'a, Message> {
+    button(text("✕").size(FONT_SIZE).center())
+        .on_press(message)
+        .width(FONT_SIZE)
+        .padding(0)
+        // Borderless until hovered
+        .style(|theme, status| match status {
+            button::Status::Hovered | button::Status::Pressed => button::secondary(theme, status),
+            _ => button::text(theme, status),
+        })
+        .into()
+}
+
+/// The border color of critical notifications
+fn critical_color(theme: &Theme, style: &UrgencyStyleConfig) -> iced::Color {
+    style
+        .critical_border_color
+        .map_or(theme.palette().danger, |[r, g, b]| {
+            iced::Color::from_rgb8(r, g, b)
+        })
+}
+
+/// The style of a notification by its urgency, bordered with its accent color unless critical,
+/// and faded by `alpha`
+fn notification_style(
+    theme: &Theme,
+    urgency: Urgency,
+    accent: Option<iced::Color>,
+    alpha: f32,
+    style: &UrgencyStyleConfig,
+) -> container::Style {
+    let palette = theme.palette();
+    let (border_color, border_width) = match urgency {
+        Urgency::Critical => (critical_color(theme, style), style.critical_border_width),
+        _ => (accent.unwrap_or(palette.text), style.border_width),
+    };
+    let background = style
+        .background(urgency)
+        .map_or(palette.background, |[r, g, b]| {
+            iced::Color::from_rgb8(r, g, b)
+        });
+    let background = match urgency {
+        Urgency::Critical => mix(background, border_color, style.critical_tint),
+        _ => background,
+    };
+    let text_color = match urgency {
+        Urgency::Low => palette.text.scale_alpha(style.low_text_opacity),
+        _ => palette.text,
+    };
+
+    let border = Border::default()
+        .width(border_width)
+        .color(border_color.scale_alpha(alpha));
+    container::bordered_box(theme)
+        .border(border)
+        .background(background.scale_alpha(alpha))
+        .color(text_color.scale_alpha(alpha))
+}
+
+/// How far through an animation started at `start` is by `now`, from 0 to 1, easing out
+fn animation_progress(start: Instant, now: Instant) -> f32 {
+    let elapsed = now.saturating_duration_since(start).as_secs_f32();
+    let linear = (elapsed / ANIMATION_DURATION.as_secs_f32()).min(1.0);
+    1.0 - (1.0 - linear).powi(2)
+}
+
+/// How far from the edge to place each of a stack of windows of these heights, past the ones
+/// before it
+fn stack_offsets(heights: &[u32]) -> Vec<i32> {
+    heights
+        .iter()
+        .scan(0, |offset, &height| {
+            let this = *offset;
+            *offset += height as i32 + SMALL as i32;
+            Some(this)
+        })
+        .collect()
+}
+
+/// A window's maximum height, lowered to the configured cap, unless it is 0 for no cap
+fn capped_height(height: u32, cap: u32) -> u32 {
+    match cap {
+        0 => height,
+        cap => height.min(cap),
+    }
+}
+
+/// A rough guess at the height of a notification's card, from how many lines its text wraps to
+fn estimated_card_height(notification: &Notification, kind: LayoutKind) -> f32 {
+    let config = config();
+    let line = FONT_SIZE * config.line_height;
+    if kind == LayoutKind::Compact {
+        return COMPACT_ICON_SIZE.max(line) + 2.0 * SMALL;
+    }
+
+    let wrapped = |text: &str, width: f32| {
+        let chars_per_line = (width / (FONT_SIZE * AVERAGE_CHAR_WIDTH)).max(1.0) as usize;
+        text.chars().count().div_ceil(chars_per_line).max(1)
+    };
+    // The app name and summary beside the icon
+    let header_width = WIDTH - 2.0 * BIG - config.icon_size - 2.0 * SMALL;
+    let name_lines =
+        usize::from(notification.shows_name(&notification.summary, config.show_app_name));
+    let header_lines = name_lines + wrapped(&notification.summary, header_width);
+    let header = config.icon_size.max(line * header_lines as f32);
+    // The body, truncated to the maximum lines with a row to show more
+    let body = notification.body.as_ref().map_or(0.0, |body| {
+        let max_lines = match config.max_body_lines {
+            0 => body.len(),
+            max_lines => max_lines,
+        };
+        let lines: usize = notification
+            .plain_text()
+            .lines()
+            .take(max_lines)
+            .map(|text| wrapped(text, WIDTH - 2.0 * BIG))
+            .sum();
+        let show_more = usize::from(body.len() > max_lines);
+        config.header_spacing + line * (lines + show_more) as f32
+    });
+    // Rows of action buttons
+    let actions = notification.button_actions().count();
+    let actions = match config.max_actions {
+        0 => actions,
+        max_actions => actions.min(max_actions),
+    };
+    let actions = actions.div_ceil(ACTIONS_PER_ROW) as f32 * (line + 3.0 * SMALL);
+
+    2.0 * BIG + header + body + actions
+}
+
+/// Mix `amount`, from 0 to 1, of the second color into the first
+fn mix(a: iced::Color, b: iced::Color, amount: f32) -> iced::Color {
+    let amount = amount.clamp(0.0, 1.0);
+    iced::Color {
+        r: a.r + (b.r - a.r) * amount,
+        g: a.g + (b.g - a.g) * amount,
+        b: a.b + (b.b - a.b) * amount,
+        a: a.a + (b.a - a.a) * amount,
+    }
+}
+
+impl From<config::IconFit> for ContentFit {
+    fn from(icon_fit: config::IconFit) -> Self {
+        match icon_fit {
+            config::IconFit::Contain => ContentFit::Contain,
+            config::IconFit::Cover => ContentFit::Cover,
+            config::IconFit::Stretch => ContentFit::Fill,
+        }
+    }
+}
+
+impl From<config::KeyboardInteractivity> for KeyboardInteractivity {
+    fn from(interactivity: config::KeyboardInteractivity) -> Self {
+        match interactivity {
+            config::KeyboardInteractivity::None => KeyboardInteractivity::None,
+            config::KeyboardInteractivity::OnDemand => KeyboardInteractivity::OnDemand,
+            config::KeyboardInteractivity::Exclusive => KeyboardInteractivity::Exclusive,
+        }
+    }
+}
+
+impl From<ActionAlign> for Horizontal {
+    fn from(align: ActionAlign) -> Self {
+        match align {
+            ActionAlign::Left => Horizontal::Left,
+            ActionAlign::Center => Horizontal::Center,
+            ActionAlign::Right => Horizontal::Right,
+        }
+    }
+}
+
+impl From<ThemeName> for Theme {
+    fn from(theme: ThemeName) -> Self {
+        match theme {
+            // Gruvbox with its lighter foreground for the text
+            ThemeName::GruvboxDark => Theme::custom(
+                "Gruvbox Dark".to_string(),
+                iced::theme::Palette {
+                    text: iced::color!(0xebdbb2),
+                    ..iced::theme::Palette::GRUVBOX_DARK
+                },
+            ),
+            ThemeName::GruvboxLight => Theme::GruvboxLight,
+            ThemeName::SolarizedDark => Theme::SolarizedDark,
+            ThemeName::SolarizedLight => Theme::SolarizedLight,
+            ThemeName::CatppuccinMocha => Theme::CatppuccinMocha,
+            ThemeName::CatppuccinLatte => Theme::CatppuccinLatte,
+            ThemeName::TokyoNight => Theme::TokyoNight,
+            ThemeName::TokyoNightLight => Theme::TokyoNightLight,
+            ThemeName::Nord => Theme::Nord,
+            ThemeName::Dracula => Theme::Dracula,
+            ThemeName::Dark => Theme::Dark,
+            ThemeName::Light => Theme::Light,
+        }
+    }
+}
+
+impl From<config::Layer> for Layer {
+    fn from(layer: config::Layer) -> Self {
+        match layer {
+            config::Layer::Background => Layer::Background,
+            config::Layer::Bottom => Layer::Bottom,
+            config::Layer::Top => Layer::Top,
+            config::Layer::Overlay => Layer::Overlay,
+        }
+    }
+}
+
+impl From<Position> for Anchor {
+    fn from(position: Position) -> Self {
+        match position {
+            Position::TopRight => Anchor::Top | Anchor::Right,
+            Position::TopLeft => Anchor::Top | Anchor::Left,
+            Position::BottomRight => Anchor::Bottom | Anchor::Right,
+            Position::BottomLeft => Anchor::Bottom | Anchor::Left,
+        }
+    }
+}
+
+/// The margins (top, right, bottom, left) placing the window `vertical` from the top or bottom
+/// edge it is anchored to and `horizontal` from the side
+fn margins(position: Position, vertical: i32, horizontal: i32) -> (i32, i32, i32, i32) {
+    match position {
+        Position::TopRight => (vertical, horizontal, 0, 0),
+        Position::TopLeft => (vertical, 0, 0, horizontal),
+        Position::BottomRight => (0, horizontal, vertical, 0),
+        Position::BottomLeft => (0, 0, vertical, horizontal),
+    }
+}
 
 pub fn run() -> Result<(), iced_layershell::Error> {
     daemon(State::default, State::namespace, State::update, State::view)
@@ -24,12 +323,34 @@ pub fn run() -> Result<(), iced_layershell::Error> {
                 start_mode: StartMode::Background,
                 ..Default::default()
             },
+            // Text falls back per character to any loaded font with the glyph
+            fonts: fallback_fonts(),
             default_font: iced::Font::with_name("JetBrains Mono"),
             ..Default::default()
         })
         .run()
 }
 
+/// Read the configured fallback fonts, skipping any that can't be read
+fn fallback_fonts() -> Vec<Cow<'static, [u8]>> {
+    config()
+        .fallback_fonts
+        .iter()
+        .filter_map(|path| {
+            std::fs::read(path)
+                .inspect_err(|err| {
+                    warn!(
+                        "Unable to read fallback font {} with error {}",
+                        path.display(),
+                        err
+                    )
+                })
+                .ok()
+        })
+        .map(Cow::Owned)
+        .collect()
+}
+
 struct State {
     /// Map of all current notifications
     notifications: HashMap<u32, Notification>,
@@ -37,8 +358,147 @@ struct State {
     alerts: Vec<u32>,
     /// DBUS signaller
     signaller: SignallerState,
-    /// The id of the window, if it exists
-    window_id: Option<iced::window::Id>,
+    /// The windows showing the notifications, one per output with `--all-monitors`
+    windows: HashMap<window::Id, NotificationWindow>,
+    /// The windows removed, which are closing until the compositor says they have closed
+    closing: HashSet<window::Id>,
+    /// The connected outputs, followed from the compositor with `--all-monitors` or `--output`
+    outputs: Vec<String>,
+    /// Notifications (by id) whose truncated bodies have been expanded
+    expanded: HashSet<u32>,
+    /// The notification shown in full detail while the others are dimmed, if any
+    focused: Option<u32>,
+    /// The notification or action button selected with the keyboard, if any
+    selected: Option<Selection>,
+    /// Raster images displayed by notifications, decoded off the UI thread
+    images: HashMap<PathBuf, ImageState>,
+    /// Apps whose grouped notifications have been expanded
+    expanded_groups: HashSet<String>,
+    /// Notifications (by id) whose actions past the maximum have been expanded
+    expanded_actions: HashSet<u32>,
+    /// Notifications (by id) whose list of actions has been opened
+    open_actions: HashSet<u32>,
+    /// Replies (by notification id) being written
+    replies: HashMap<u32, String>,
+    /// The icon shown in place of icons that can't be read, if the icon theme has one
+    default_icon: Option<PathBuf>,
+    /// Whether to show the notifications past the maximum visible
+    show_all: bool,
+    /// Notifications (by id) the pointer is over, and since when, which don't expire until it leaves
+    hovered: HashMap<u32, DateTime<Local>>,
+    /// Whether new notifications are held rather than displayed
+    paused: bool,
+    /// Whether a window is fullscreen, which holds new notifications like being paused
+    fullscreen: bool,
+    /// When the session went idle or locked, if it is, which stops notifications expiring
+    idle_since: Option<DateTime<Local>>,
+    /// The system's color scheme, when followed and it has a preference
+    system_color_scheme: Option<ColorScheme>,
+    /// Notifications (by id) held while paused or a window is fullscreen, in the order they arrived
+    queued: Vec<u32>,
+    /// When the timer for the next notification to expire goes off, if one is set
+    expiry_timer: Option<DateTime<Local>>,
+    /// Notifications (by id) fading in, and since when
+    appearing: HashMap<u32, Instant>,
+    /// Notifications (by id) fading out, since when, and why they are closing once they have
+    removing: HashMap<u32, (Instant, NotificationClosedReason)>,
+    /// The heights of the notifications' cards, which collapse to nothing as they fade out
+    card_heights: HashMap<u32, f32>,
+    /// The notifications the user dismissed lately and when, the last most recent, to bring back
+    recently_dismissed: Vec<(Instant, Notification)>,
+    /// When the scroll wheel last dismissed or brought back a notification
+    last_wheel: Option<Instant>,
+    /// The keyboard modifiers held, when the windows have keyboard focus
+    modifiers: iced::keyboard::Modifiers,
+    /// Where the current time comes from
+    clock: Clock,
+    /// The notification being dragged, if one is
+    drag: Option<Drag>,
+    /// Notifications (by id) let go of after being dragged, since when, and how far they were
+    /// dragged, which slide back unless they are being dismissed
+    released: HashMap<u32, (Instant, f32)>,
+}
+
+/// A layer shell window showing the notifications
+struct NotificationWindow {
+    /// The output the window is on, or `None` for the active output
+    output: Option<String>,
+    /// The notification the window shows, if each has its own window, or `None` for all of them
+    notification: Option<u32>,
+    /// The layer the window is on
+    layer: Layer,
+    /// The margins from the edge and the side of the screen the window is anchored to
+    margin: (i32, i32),
+    /// How much farther from the edge the window is, to stack it past the windows nearer the edge
+    stack_offset: i32,
+    /// Whether the window has opened, which it may never do if the layer shell is unsupported
+    opened: bool,
+    /// The maximum height of the window, which scrolls past it, from the height of its output
+    /// once known
+    max_height: u32,
+    /// The height of the window's contents, in whole logical pixels
+    content_height: u32,
+    /// The scale factor of the window's output, once known
+    scale_factor: f32,
+    /// The size last requested for the window
+    requested_size: (u32, u32),
+    /// Whether a resize is queued to run after the other messages in the current burst
+    resize_queued: bool,
+    /// How many resizes have been skipped since the window was last resized, for the logs
+    suppressed_resizes: u32,
+    /// The notifications (by id) displayed when the window was created
+    anchored: HashSet<u32>,
+}
+
+impl NotificationWindow {
+    /// The size of the window for its contents, clamped to its maximum height
+    fn size(&self) -> (u32, u32) {
+        (WIDTH as u32, self.content_height.clamp(1, self.max_height))
+    }
+
+    /// The size to resize the window to, unless it was already requested
+    fn resize(&mut self) -> Option<(u32, u32)> {
+        let size = self.size();
+        (size != self.requested_size).then(|| {
+            self.requested_size = size;
+            size
+        })
+    }
+}
+
+/// Something that can be selected with the keyboard
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Selection {
+    Notification(u32),
+    /// An action button of a notification, by its position
+    Action(u32, usize),
+}
+
+impl Selection {
+    /// The id of the notification the selection is in
+    fn id(self) -> u32 {
+        match self {
+            Selection::Notification(id) | Selection::Action(id, _) => id,
+        }
+    }
+}
+
+/// A notification being dragged to the side to dismiss it
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Drag {
+    id: u32,
+    /// Where the pointer was when it first moved, once it has
+    start: Option<f32>,
+    /// How far the notification has been dragged, negative for to the left
+    offset: f32,
+}
+
+/// The state of an image being decoded in the background
+#[derive(Clone, Debug)]
+enum ImageState {
+    Loading,
+    Loaded(iced::widget::image::Handle),
+    Failed,
 }
 
 enum SignallerState {
@@ -50,18 +510,106 @@ enum SignallerState {
 #[derive(Clone, Debug)]
 enum Message {
     ActionInvoked(u32, String),
-    ContainerResized(u32),
+    ActivateSelected,
+    CardMeasured(u32, f32),
+    CardReleased(u32),
+    ColorSchemeChanged(Option<ColorScheme>),
+    ContainerResized(window::Id, u32),
+    CopyBody(u32),
+    CopyCode(u32),
     Dbus(DbusMessage),
+    DismissAll,
+    DismissOldest,
+    DismissSelected,
+    DragEnded,
+    DragMoved(f32),
+    DragStarted(u32),
+    ExpandActions(u32),
+    ExpandBody(u32),
+    Expired(DateTime<Local>),
+    Fullscreen(bool),
+    GroupDismissed(Vec<u32>),
+    /// How long ago the session went idle, or `None` once it isn't
+    Idle(Option<Duration>),
+    ImageDecoded(PathBuf, Option<iced::widget::image::Handle>),
+    LayerShellTimeout(window::Id),
+    ModifiersChanged(iced::keyboard::Modifiers),
+    MonitorSized(window::Id, Option<iced::Size>),
+    MoveSelection(bool),
+    OpenLink(String),
+    OutputsChanged(Vec<String>),
+    PointerEntered(u32),
+    PointerExited(u32),
+    ReplyEdited(u32, String),
+    ReplySubmitted(u32),
+    Rescaled(window::Id, f32),
+    Resize(window::Id),
+    RestoreLastDismissed,
+    Resume,
+    ShowAll(bool),
     Tick,
+    ToggleActions(u32),
+    ToggleFocus(u32),
+    ToggleGroup(String),
+    Unfocus,
     UserDismissed(u32),
+    Wheel(f32),
     WindowClosed(window::Id),
+    WindowOpened(window::Id),
 }
 
 const FONT_SIZE: f32 = 20.0;
 const WIDTH: f32 = 500.0;
-const ICON_SIZE: f32 = 80.0;
+/// The maximum height of the window when the screen size is unknown, unless `--max-height` is
+/// lower
+const MAX_HEIGHT: u32 = 2000;
+/// How long the window has to open before the layer shell is assumed to have failed
+const LAYER_SHELL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+/// How long after a notification's expiry to remove it, so it has certainly expired by then
+const EXPIRY_SLACK: std::time::Duration = std::time::Duration::from_millis(5);
+/// Margins from the top or bottom and the side of the screen, when not a percent of its size
+const MARGIN_VERTICAL: i32 = 60;
+const MARGIN_HORIZONTAL: i32 = 20;
 const SMALL: f32 = 10.0;
 const BIG: f32 = 20.0;
+const BODY_IMAGE_MAX_WIDTH: f32 = WIDTH - 2.0 * BIG;
+const BODY_IMAGE_MAX_HEIGHT: f32 = 300.0;
+/// How much of the text color to mix into the background of monospace spans, to make inline code
+/// stand out
+const CODE_TINT: f32 = 0.1;
+/// Height of the bar showing an expiring notification's remaining time
+const TIME_BAR_HEIGHT: f32 = 3.0;
+/// Most action buttons to put in one row
+const ACTIONS_PER_ROW: usize = 3;
+/// Width of a row of action buttons, inside the notification's padding
+const ACTIONS_WIDTH: f32 = WIDTH - 2.0 * BIG;
+/// Width of a character relative to the font size, for the monospace font
+const CHAR_WIDTH: f32 = 0.6;
+/// Width of an average character relative to the font size, for guessing how text wraps
+const AVERAGE_CHAR_WIDTH: f32 = 0.5;
+/// Corner radius of icons, matching the corners of the notifications
+const ICON_RADIUS: f32 = 5.0;
+/// Size of the icon of compact notifications
+const COMPACT_ICON_SIZE: f32 = 24.0;
+/// Opacity of notifications dimmed while another is focused
+const DIMMED_ALPHA: f32 = 0.5;
+/// How long notifications take to fade in and out
+const ANIMATION_DURATION: std::time::Duration = std::time::Duration::from_millis(150);
+/// How often to redraw while notifications are fading in or out
+const ANIMATION_FRAME: std::time::Duration = std::time::Duration::from_millis(16);
+/// How far across its width a notification has to be dragged to dismiss it
+const DRAG_DISMISS: f32 = 0.4;
+/// How far a notification can move while it is pressed and still count as clicked
+const DRAG_SLOP: f32 = 10.0;
+/// How long after dismissing a notification it can be brought back with the scroll wheel
+const RESTORE_GRACE: std::time::Duration = std::time::Duration::from_secs(60);
+/// The most dismissed notifications kept to bring back
+const MAX_RECENTLY_DISMISSED: usize = 10;
+/// How long after the scroll wheel dismisses or brings back a notification before it can again,
+/// so a touchpad's stream of small scrolls only counts once
+const WHEEL_COOLDOWN: std::time::Duration = std::time::Duration::from_millis(250);
+/// Border width of the notification selected with the keyboard
+const SELECTED_BORDER_WIDTH: f32 = 3.0;
 
 impl Default for State {
     fn default() -> Self {
@@ -69,107 +617,652 @@ impl Default for State {
             notifications: HashMap::new(),
             alerts: Vec::new(),
             signaller: SignallerState::Unitialized,
-            window_id: None,
+            windows: HashMap::new(),
+            closing: HashSet::new(),
+            outputs: Vec::new(),
+            expanded: HashSet::new(),
+            focused: None,
+            selected: None,
+            images: HashMap::new(),
+            expanded_groups: HashSet::new(),
+            expanded_actions: HashSet::new(),
+            open_actions: HashSet::new(),
+            replies: HashMap::new(),
+            default_icon: find_default_icon(&config().default_icon),
+            show_all: false,
+            hovered: HashMap::new(),
+            paused: false,
+            fullscreen: false,
+            idle_since: None,
+            system_color_scheme: None,
+            queued: Vec::new(),
+            expiry_timer: None,
+            appearing: HashMap::new(),
+            removing: HashMap::new(),
+            card_heights: HashMap::new(),
+            recently_dismissed: Vec::new(),
+            clock: Clock::default(),
+            drag: None,
+            released: HashMap::new(),
+            last_wheel: None,
+            modifiers: iced::keyboard::Modifiers::default(),
         }
     }
 }
 
 impl State {
     fn view_notification(&self, notification: &Notification) -> Element<Message> {
-        let image: Element<Message> = if notification
-            .icon
-            .extension()
-            .is_some_and(|extension| extension == "svg")
-        {
-            iced::widget::svg(notification.icon.clone())
-                .width(Length::Fill)
-                .height(Length::Fill)
-                .into()
-        } else {
-            iced::widget::image(notification.icon.clone())
-                .width(Length::Fill)
-                .height(Length::Fill)
-                .into()
-        };
-        let icon = container(image).width(ICON_SIZE).height(ICON_SIZE);
+        // The focused notification is always shown in full
+        let kind = notification.layout_kind(config().compact, &config().compact_apps);
+        if kind == LayoutKind::Compact && self.focused != Some(notification.id) {
+            return self.view_compact(notification);
+        }
+
+        let icon = self.view_icon(notification, config().icon_size);
+        let (summary, body) =
+            notification.summary_and_body(config().empty_summary == EmptySummary::Promote);
+        let show_name = notification.shows_name(&summary, config().show_app_name);
 
         let header = container(row![
-            container(column![
-                text(notification.name.clone())
-                    .size(FONT_SIZE)
-                    .style(text::secondary),
-                text(notification.summary.clone()).size(FONT_SIZE)
-            ])
+            container(
+                // Leave out the app name when it only repeats the summary
+                Column::new()
+                    .push_maybe(show_name.then(|| {
+                        text(notification.name.clone())
+                            .size(FONT_SIZE)
+                            .shaping(Shaping::Advanced)
+                            .wrapping(Wrapping::WordOrGlyph)
+                            .style(text::secondary)
+                    }))
+                    // Leave out an empty summary, rather than a blank line
+                    .push_maybe((!summary.trim().is_empty()).then(|| {
+                        let colored = notification.urgency == Urgency::Critical
+                            && config().urgency_style.critical_summary_color;
+                        text(summary)
+                            .size(FONT_SIZE)
+                            .shaping(Shaping::Advanced)
+                            .wrapping(Wrapping::WordOrGlyph)
+                            .style(move |theme: &Theme| text::Style {
+                                color: colored
+                                    .then(|| critical_color(theme, &config().urgency_style)),
+                            })
+                    }))
+            )
             .padding([0, 10])
             .width(Length::Fill),
-            container(text(notification_time(&notification.time)).size(FONT_SIZE))
+            // Count merged duplicates, like ×3
+            container(
+                Row::new()
+                    .push_maybe(
+                        notification
+                            .historical
+                            .then(|| { text("history").size(FONT_SIZE).style(text::secondary) })
+                    )
+                    .push_maybe((!notification.merged.is_empty()).then(|| {
+                        text(format!("×{}", notification.merged.len() + 1))
+                            .size(FONT_SIZE)
+                            .style(text::secondary)
+                    }))
+                    .push(
+                        text(notification_time(
+                            &notification.time,
+                            self.clock.now(),
+                            &config().time_format,
+                        ))
+                        .size(FONT_SIZE)
+                    )
+                    .spacing(SMALL)
+            ),
+            self.view_close(notification)
         ]);
 
-        let body = notification
-            .body
-            .clone()
-            .map(|body| self.body_markup(&body));
+        let body = (!body.is_empty()).then(|| {
+            let max_lines = config().max_body_lines;
+            if max_lines == 0
+                || body.len() <= max_lines
+                || self.expanded.contains(&notification.id)
+                || self.focused == Some(notification.id)
+            {
+                return self.body_markup(body);
+            }
 
-        let actions: Option<Row<Message>> = notification.actions.as_ref().map(|actions| {
-            row(actions
-                .iter()
-                .cloned()
-                .map(|(key, label)| -> Element<Message> {
-                    button(text(label).size(FONT_SIZE).align_x(Horizontal::Center))
-                        .on_press(Message::ActionInvoked(notification.id, key.clone()))
-                        .padding(SMALL)
-                        .style(|theme: &Theme, status| match status {
-                            button::Status::Active | button::Status::Disabled => button::Style {
-                                background: None,
-                                text_color: theme.palette().text,
-                                border: iced::border::color(theme.palette().text).width(1.0),
-                                ..Default::default()
-                            },
-                            button::Status::Hovered | button::Status::Pressed => button::Style {
-                                background: Some(Background::Color(theme.palette().text)),
-                                text_color: theme.palette().background,
-                                border: iced::border::color(theme.palette().text).width(1.0),
-                                ..Default::default()
-                            },
-                        })
-                        .width(Length::Fill)
-                        .into()
-                }))
-            .spacing(SMALL)
+            // Truncate long bodies, with a control to show the rest
+            let show_more = button(text("show more").size(FONT_SIZE).style(text::secondary))
+                .on_press(Message::ExpandBody(notification.id))
+                .padding(0)
+                .style(button::text);
+            column![
+                self.body_markup(&body[..max_lines]),
+                row![
+                    text("…").size(FONT_SIZE),
+                    Space::with_width(Length::Fill),
+                    show_more
+                ]
+            ]
+            .into()
         });
 
-        let content = column![row![icon, header].width(Length::Fill)]
-            .push_maybe(body)
+        let actions = self.view_actions(notification);
+        let reply = self.view_reply(notification);
+        let time_bar = self.view_time_bar(notification);
+        let (top_bar, bottom_bar) = match config().time_bar_position {
+            TimeBarPosition::Top => (time_bar, None),
+            TimeBarPosition::Bottom => (None, time_bar),
+        };
+
+        let content = Column::new()
+            .push_maybe(top_bar)
+            .push(
+                column![Row::new().push_maybe(icon).push(header).width(Length::Fill)]
+                    .push_maybe(body)
+                    .spacing(config().header_spacing),
+            )
             .push_maybe(actions)
+            .push_maybe(reply)
+            .push_maybe(bottom_bar)
+            .spacing(SMALL);
+
+        self.view_card(notification, content.into(), BIG)
+    }
+
+    /// A low key notification as one row of a small icon, the app name, and the summary
+    fn view_compact(&self, notification: &Notification) -> Element<Message> {
+        let promote = config().empty_summary == EmptySummary::Promote;
+        let (summary, _) = notification.summary_and_body(promote);
+        let show_name = notification.shows_name(&summary, config().show_app_name);
+        let content = Row::new()
+            .push_maybe(self.view_icon(notification, COMPACT_ICON_SIZE))
+            .push_maybe(show_name.then(|| {
+                text(notification.name.clone())
+                    .size(FONT_SIZE)
+                    .shaping(Shaping::Advanced)
+                    .wrapping(Wrapping::None)
+                    .style(text::secondary)
+            }))
+            .push(
+                container(
+                    text(summary)
+                        .size(FONT_SIZE)
+                        .shaping(Shaping::Advanced)
+                        .wrapping(Wrapping::None),
+                )
+                .width(Length::Fill)
+                .clip(true),
+            )
+            .push(self.view_close(notification))
+            .align_y(Vertical::Center)
             .spacing(SMALL);
 
+        self.view_card(notification, content.into(), SMALL)
+    }
+
+    /// The card around a notification's content, styled by its urgency and dimmed while another
+    /// notification is focused
+    fn view_card<'a>(
+        &'a self,
+        notification: &Notification,
+        content: Element<'a, Message>,
+        padding: f32,
+    ) -> Element<'a, Message> {
         let urgency = notification.urgency;
-        mouse_area(
+        let accent = notification.border_accent();
+        // Dim the other notifications while one is focused, and fade them in and out
+        let dimmed = self.focused.is_some_and(|id| id != notification.id);
+        let now = Instant::now();
+        let visibility = self.visibility(notification.id, now);
+        let dim = if dimmed { DIMMED_ALPHA } else { 1.0 };
+        // Fade a notification out the farther it is dragged
+        let offset = self.drag_offset(notification.id, now);
+        let drag_fade = 1.0 - (offset.abs() / WIDTH).min(1.0);
+        let alpha = dim * drag_fade * visibility.unwrap_or(1.0);
+        let selected = self.selected == Some(Selection::Notification(notification.id));
+        let area = mouse_area(
             container(content)
                 .style(move |theme| {
-                    let border_color = if urgency == Urgency::Critical {
-                        theme.palette().danger
+                    let style =
+                        notification_style(theme, urgency, accent, alpha, &config().urgency_style);
+                    // Ring the notification selected with the keyboard
+                    if selected {
+                        let border = style
+                            .border
+                            .color(theme.palette().primary)
+                            .width(SELECTED_BORDER_WIDTH);
+                        style.border(border)
                     } else {
-                        theme.palette().text
-                    };
-                    let border = Border::default().width(2).color(border_color);
-                    container::bordered_box(theme)
-                        .border(border)
-                        .background(theme.palette().background)
+                        style
+                    }
                 })
-                .padding(BIG)
+                .padding(padding)
                 .width(WIDTH),
         )
+        .on_double_click(Message::ToggleFocus(notification.id))
         .on_right_release(Message::UserDismissed(notification.id))
-        .into()
+        .on_enter(Message::PointerEntered(notification.id))
+        .on_exit(Message::PointerExited(notification.id));
+
+        // Clicking away from the focused notification unfocuses it, and otherwise pressing one
+        // starts dragging it and clicking it invokes its default action, if it has one
+        let card = if dimmed {
+            area.on_press(Message::Unfocus)
+        } else {
+            let area = if config().no_drag_dismiss {
+                area
+            } else {
+                area.on_press(Message::DragStarted(notification.id))
+            };
+            if notification.has_default_action() {
+                area.on_release(Message::CardReleased(notification.id))
+            } else {
+                area
+            }
+        };
+        let card = if config().no_drag_dismiss {
+            card.into()
+        } else {
+            SlidingContainer::new(card, offset).into()
+        };
+        self.view_animated(notification.id, card, visibility)
+    }
+
+    /// How far a notification is dragged to the side, following the pointer while dragged and
+    /// then sliding back, or staying put while it is dismissed
+    fn drag_offset(&self, id: u32, now: Instant) -> f32 {
+        match (self.drag, self.released.get(&id)) {
+            (Some(drag), _) if drag.id == id => drag.offset,
+            (_, Some((_, offset))) if self.removing.contains_key(&id) => *offset,
+            (_, Some((start, offset))) => offset * (1.0 - animation_progress(*start, now)),
+            _ => 0.0,
+        }
+    }
+
+    /// Let go of the dragged notification, dismissing it if it was dragged far enough to the side
+    /// and otherwise sliding it back. Returns whether it was dragged, rather than just clicked
+    fn end_drag(&mut self) -> bool {
+        let Some(drag) = self.drag.take() else {
+            return false;
+        };
+        if drag.offset.abs() >= DRAG_DISMISS * WIDTH {
+            debug!("Notification {} dragged away", drag.id);
+            self.dismiss(drag.id);
+        }
+        if drag.offset != 0.0 && !config().reduce_motion {
+            self.released.insert(drag.id, (Instant::now(), drag.offset));
+        }
+        drag.offset.abs() >= DRAG_SLOP
+    }
+
+    /// A card that grows in below the one above it as it appears and collapses as it is removed,
+    /// so the cards below slide to their new positions rather than jumping
+    fn view_animated<'a>(
+        &self,
+        id: u32,
+        card: Element<'a, Message>,
+        visibility: Option<f32>,
+    ) -> Element<'a, Message> {
+        if config().reduce_motion {
+            return card;
+        }
+
+        // Measure the card to know how tall it is while collapsed
+        let card =
+            MeasuringContainer::new(card, move |size| Message::CardMeasured(id, size.height))
+                .max_width(WIDTH);
+        let height = match visibility {
+            Some(visibility) => {
+                Length::Fixed(self.card_heights.get(&id).map_or(0.0, |h| h * visibility))
+            }
+            None => Length::Shrink,
+        };
+        container(card).height(height).clip(true).into()
+    }
+
+    /// How visible a notification fading in or out is, from 0 to 1, or `None` if it isn't
+    fn visibility(&self, id: u32, now: Instant) -> Option<f32> {
+        match (self.appearing.get(&id), self.removing.get(&id)) {
+            (_, Some((start, _))) => Some(1.0 - animation_progress(*start, now)),
+            (Some(start), None) => Some(animation_progress(*start, now)),
+            (None, None) => None,
+        }
+    }
+
+    /// Remove the notifications that have finished fading out, and stop animating the ones that
+    /// have finished fading in
+    fn finish_animations(&mut self) {
+        let now = Instant::now();
+        self.appearing
+            .retain(|_, start| now.duration_since(*start) < ANIMATION_DURATION);
+        self.released.retain(|id, (start, _)| {
+            self.removing.contains_key(id) || now.duration_since(*start) < ANIMATION_DURATION
+        });
+        let finished: Vec<(u32, NotificationClosedReason)> = self
+            .removing
+            .iter()
+            .filter(|(_, (start, _))| now.duration_since(*start) >= ANIMATION_DURATION)
+            .map(|(&id, (_, reason))| (id, reason.clone()))
+            .collect();
+        finished.into_iter().for_each(|(id, reason)| {
+            self.removing.remove(&id);
+            self.finish_close(id, reason);
+        });
+    }
+
+    /// Consecutive notifications from the same app under a header, showing the most recent in full
+    /// and the older ones as one line summaries until expanded
+    fn view_group(&self, group: &[&Notification]) -> Element<Message> {
+        // Most recent first, whichever order the notifications are shown in
+        let mut group = group.to_vec();
+        group.sort_by_key(|notification| std::cmp::Reverse(notification.time));
+        let latest = group[0];
+        let expanded = self.expanded_groups.contains(&latest.name);
+        let ids = group.iter().map(|notification| notification.id).collect();
+
+        let toggle = button(
+            text(if expanded { "collapse" } else { "show all" })
+                .size(FONT_SIZE)
+                .style(text::secondary),
+        )
+        .on_press(Message::ToggleGroup(latest.name.clone()))
+        .padding(0)
+        .style(button::text);
+        let header = container(
+            Row::new()
+                .push_maybe(self.view_icon(latest, FONT_SIZE))
+                .push(
+                    text(format!("{} ({})", latest.name, group.len()))
+                        .size(FONT_SIZE)
+                        .shaping(Shaping::Advanced)
+                        .width(Length::Fill),
+                )
+                .push(toggle)
+                .push(close_button(Message::GroupDismissed(ids)))
+                .spacing(SMALL),
+        )
+        .style(container::bordered_box)
+        .padding([SMALL, BIG])
+        .width(WIDTH);
+
+        let members = if expanded {
+            Column::from_iter(
+                group
+                    .iter()
+                    .map(|notification| self.view_notification(notification)),
+            )
+        } else {
+            let promote = config().empty_summary == EmptySummary::Promote;
+            let older = group[1..].iter().map(|notification| {
+                mouse_area(
+                    container(
+                        text(notification.summary_and_body(promote).0)
+                            .size(FONT_SIZE)
+                            .shaping(Shaping::Advanced)
+                            .wrapping(Wrapping::None)
+                            .style(text::secondary),
+                    )
+                    .padding([0, BIG])
+                    .width(WIDTH)
+                    .clip(true),
+                )
+                .on_press(Message::ToggleGroup(latest.name.clone()))
+                .into()
+            });
+            column![self.view_notification(latest)].extend(older)
+        };
+
+        column![header, members.spacing(SMALL)]
+            .spacing(SMALL)
+            .into()
+    }
+
+    fn view_close(&self, notification: &Notification) -> Element<Message> {
+        // Keep the button's space while it is hidden so the header doesn't reflow on hover
+        if config().close_on_hover && !self.hovered.contains_key(&notification.id) {
+            return Space::with_width(FONT_SIZE).into();
+        }
+
+        close_button(Message::UserDismissed(notification.id))
+    }
+
+    fn view_time_bar(&self, notification: &Notification) -> Option<Element<Message>> {
+        if config().no_time_bar {
+            return None;
+        }
+
+        // Hold the bar still while the notification isn't expiring, like while hovered
+        let paused = [self.hovered.get(&notification.id), self.idle_since.as_ref()]
+            .into_iter()
+            .flatten()
+            .min();
+        let now = paused.copied().unwrap_or_else(|| self.clock.now());
+
+        // Drain the bar from full to empty by splitting the width between it and a space
+        let left = (notification.time_left(now)? * 1000.0).round() as u16;
+        Some(
+            row![
+                container(Space::with_height(TIME_BAR_HEIGHT))
+                    .width(Length::FillPortion(left))
+                    .style(|theme: &Theme| {
+                        container::Style::default().background(theme.palette().primary)
+                    }),
+                Space::new(Length::FillPortion(1000 - left), TIME_BAR_HEIGHT)
+            ]
+            .into(),
+        )
+    }
+
+    /// The labels and messages of the action buttons shown for a notification, with the ones past
+    /// the maximum collapsed behind a button
+    fn action_buttons(&self, notification: &Notification) -> Vec<(String, Message)> {
+        let config = config();
+        let mut buttons: Vec<(String, Message)> = notification
+            .button_actions()
+            .map(|(key, label)| {
+                (
+                    label.clone(),
+                    Message::ActionInvoked(notification.id, key.clone()),
+                )
+            })
+            .collect();
+
+        if config.copy_code && find_code(&notification.plain_text()).is_some() {
+            buttons.push(("Copy code".into(), Message::CopyCode(notification.id)));
+        }
+        if config.copy_button {
+            buttons.push(("Copy".into(), Message::CopyBody(notification.id)));
+        }
+
+        // Put many actions in a list behind a button that opens it
+        let dropdown = config.actions_dropdown;
+        if dropdown > 0 && buttons.len() > dropdown {
+            let open = self.open_actions.contains(&notification.id);
+            if !open {
+                buttons.clear();
+            }
+            let label = if open { "Actions ▴" } else { "Actions ▾" };
+            buttons.insert(0, (label.into(), Message::ToggleActions(notification.id)));
+            return buttons;
+        }
+
+        // Collapse the actions past the maximum behind a button, which takes the last slot
+        let max = config.max_actions;
+        let overflow =
+            max > 0 && buttons.len() > max && !self.expanded_actions.contains(&notification.id);
+        if overflow {
+            buttons.truncate(max - 1);
+            buttons.push(("⋯".into(), Message::ExpandActions(notification.id)));
+        }
+
+        buttons
+    }
+
+    fn view_actions(&self, notification: &Notification) -> Option<Column<Message>> {
+        let buttons = self.action_buttons(notification);
+        if buttons.is_empty() {
+            return None;
+        }
+
+        // A list of actions has one per row
+        let list = matches!(buttons.first(), Some((_, Message::ToggleActions(_))));
+        let per_row = if list {
+            1
+        } else {
+            buttons.len().min(ACTIONS_PER_ROW)
+        };
+
+        // Shorten labels to fit their buttons, in the monospace font
+        let width = (ACTIONS_WIDTH - SMALL * (per_row - 1) as f32) / per_row as f32;
+        let max_chars = ((width - 2.0 * SMALL) / (FONT_SIZE * CHAR_WIDTH)).floor() as usize;
+
+        // Buttons as wide as their labels line up on the configured side, even just one
+        let (width, align) = match config().action_width {
+            ActionWidth::Fill => (Length::Fill, Horizontal::Left),
+            ActionWidth::Shrink => (Length::Shrink, config().action_align.into()),
+        };
+
+        let mut buttons = buttons
+            .into_iter()
+            .enumerate()
+            .map(|(i, (label, message))| {
+                let selected = self.selected == Some(Selection::Action(notification.id, i));
+                action_button(
+                    ellipsize(&label, max_chars).into(),
+                    message,
+                    selected,
+                    width,
+                )
+            })
+            .peekable();
+        let mut rows = Column::new().spacing(SMALL);
+        while buttons.peek().is_some() {
+            let buttons = row(buttons.by_ref().take(per_row)).spacing(SMALL);
+            rows = rows.push(container(buttons).width(Length::Fill).align_x(align));
+        }
+        Some(rows)
+    }
+
+    /// A field to reply in, for apps that take inline replies
+    fn view_reply(&self, notification: &Notification) -> Option<Element<Message>> {
+        let placeholder = notification.reply.as_deref()?;
+        let id = notification.id;
+        let draft = self.replies.get(&id).map_or("", String::as_str);
+
+        let input = text_input(placeholder, draft)
+            .on_input(move |text| Message::ReplyEdited(id, text))
+            .on_submit(Message::ReplySubmitted(id))
+            .size(FONT_SIZE)
+            .padding(SMALL);
+        let send = button(text("Send").size(FONT_SIZE))
+            .on_press_maybe((!draft.trim().is_empty()).then_some(Message::ReplySubmitted(id)))
+            .padding(SMALL);
+
+        Some(row![input, send].spacing(SMALL).into())
+    }
+
+    fn view_icon(&self, notification: &Notification, size: f32) -> Option<Element<Message>> {
+        let config = config();
+        if config.no_icon {
+            return None;
+        }
+
+        // Fall back to the default icon when the icon can't be read, and then to a glyph
+        let image = self
+            .view_icon_image(&notification.icon)
+            .or_else(|| {
+                let default_icon = self.default_icon.as_ref()?;
+                self.view_icon_image(default_icon)
+            })
+            .unwrap_or_else(|| self.view_fallback_glyph(notification.urgency, size));
+
+        // Mark critical notifications with a dot in the corner of the icon
+        let badge = (notification.urgency == Urgency::Critical
+            && config.urgency_style.critical_badge)
+            .then(|| {
+                container(
+                    container(Space::new(size / 4.0, size / 4.0)).style(|theme: &Theme| {
+                        container::Style::default()
+                            .background(critical_color(theme, &config().urgency_style))
+                            .border(Border::default().rounded(size))
+                    }),
+                )
+                .align_right(Length::Fill)
+                .align_top(Length::Fill)
+            });
+
+        Some(
+            container(stack![image].push_maybe(badge))
+                .width(size)
+                .height(size)
+                .clip(true)
+                .into(),
+        )
+    }
+
+    /// The icon at the path, scaled to fit, or None if it can't be read
+    fn view_icon_image(&self, icon: &Path) -> Option<Element<Message>> {
+        let content_fit = config().icon_fit.into();
+        if matches!(self.images.get(icon), Some(ImageState::Failed)) {
+            return None;
+        }
+        if is_svg(icon) {
+            return Some(
+                iced::widget::svg(icon)
+                    .content_fit(content_fit)
+                    .width(Length::Fill)
+                    .height(Length::Fill)
+                    .into(),
+            );
+        }
+
+        match self.images.get(icon) {
+            Some(ImageState::Loaded(handle)) => Some(
+                iced::widget::image(handle.clone())
+                    .content_fit(content_fit)
+                    .width(Length::Fill)
+                    .height(Length::Fill)
+                    .into(),
+            ),
+            Some(ImageState::Failed) => None,
+            // Keep the icon's space while it is decoding so the card doesn't shift
+            Some(ImageState::Loading) | None => Some(Space::new(Length::Fill, Length::Fill).into()),
+        }
+    }
+
+    /// A glyph colored by urgency, for when there is no icon or it can't be read
+    fn view_fallback_glyph(&self, urgency: Urgency, size: f32) -> Element<Message> {
+        let glyph = text(config().fallback_glyph.as_str())
+            .size(size * 0.6)
+            .shaping(Shaping::Advanced)
+            .style(move |theme: &Theme| {
+                let palette = theme.palette();
+                let color = match urgency {
+                    Urgency::Low => palette.text.scale_alpha(DIMMED_ALPHA),
+                    Urgency::Normal => palette.primary,
+                    Urgency::Critical => palette.danger,
+                };
+                text::Style { color: Some(color) }
+            });
+
+        container(glyph).center(Length::Fill).into()
     }
 
     fn body_markup(&self, body: &[BodyElement]) -> Element<Message> {
+        let config = config();
+        let line_height = text::LineHeight::Relative(config.line_height);
+        let palette = self.current_theme().palette();
+        let code_background = mix(palette.background, palette.text, CODE_TINT);
+
         Column::from_iter(body.iter().map(|element| {
             match element {
+                // Blank lines between paragraphs
+                BodyElement::RichText(spans) if spans.is_empty() => {
+                    Space::with_height(line_height.to_absolute(FONT_SIZE.into())).into()
+                }
                 BodyElement::RichText(spans) => text::Rich::from_iter(spans.iter().map(
-                    |RichTextSpan { style, text }| -> iced::advanced::text::Span<'_, ()> {
-                        let mut font = iced::Font::with_name("JetBrains Mono");
+                    |RichTextSpan { style, text, link }| -> iced::advanced::text::Span<'_, String> {
+                        let mut font = if style.monospace {
+                            iced::Font::MONOSPACE
+                        } else {
+                            iced::Font::with_name("JetBrains Mono")
+                        };
                         if style.bold {
                             font.weight = iced::font::Weight::Bold;
                         }
@@ -178,63 +1271,908 @@ impl State {
                         }
                         iced::widget::span(text.clone())
                             .size(FONT_SIZE)
+                            .line_height(line_height)
                             .font(font)
                             .underline(style.underline)
+                            .strikethrough(style.strikethrough)
+                            .color_maybe(
+                                style
+                                    .foreground
+                                    .map(|[r, g, b]| iced::Color::from_rgb8(r, g, b)),
+                            )
+                            .background_maybe(
+                                style
+                                    .background
+                                    .map(|[r, g, b]| iced::Color::from_rgb8(r, g, b))
+                                    .or(style.monospace.then_some(code_background)),
+                            )
+                            .link_maybe(link.clone())
                     },
                 ))
+                // Break long unbroken text like URLs anywhere rather than overflowing the card
+                .wrapping(Wrapping::WordOrGlyph)
+                .on_link_click(Message::OpenLink)
                 .into(),
-                // Tooltip doesn't work?
-                BodyElement::Image { src, alt } => iced::widget::tooltip(
-                    iced::widget::image(src),
-                    text(alt.clone()),
-                    iced::widget::tooltip::Position::Top,
-                )
-                .into(),
+                BodyElement::Image { src, alt } => self.body_image(src, alt),
             }
         }))
+        .spacing(config.paragraph_spacing)
         .into()
     }
 
+    fn body_image(&self, src: &Path, alt: &str) -> Element<Message> {
+        // An image that is still decoding, failed to decode, or is a remote image that hasn't been
+        // downloaded, shows its alt text instead of an empty box of unknown size
+        let image: Element<Message> = if is_svg(src) {
+            iced::widget::svg(src)
+                .content_fit(ContentFit::ScaleDown)
+                .width(Length::Shrink)
+                .height(Length::Shrink)
+                .into()
+        } else if let Some(ImageState::Loaded(handle)) = self.images.get(src) {
+            iced::widget::image(handle.clone())
+                .content_fit(ContentFit::ScaleDown)
+                .width(Length::Shrink)
+                .height(Length::Shrink)
+                .into()
+        } else {
+            return text(alt.to_string())
+                .size(FONT_SIZE)
+                .style(text::secondary)
+                .into();
+        };
+
+        let image = container(image)
+            .max_width(BODY_IMAGE_MAX_WIDTH)
+            .max_height(BODY_IMAGE_MAX_HEIGHT);
+
+        if alt.is_empty() {
+            return image.into();
+        }
+
+        match config().image_alt {
+            ImageAlt::Tooltip => iced::widget::tooltip(
+                image,
+                container(text(alt.to_string()).size(FONT_SIZE))
+                    .padding(SMALL)
+                    .style(container::bordered_box),
+                iced::widget::tooltip::Position::Top,
+            )
+            .into(),
+            ImageAlt::Caption => column![
+                image,
+                text(alt.to_string()).size(FONT_SIZE).style(text::secondary)
+            ]
+            .into(),
+        }
+    }
+
     fn remove_expired(&mut self) {
+        if self.idle_since.is_some() {
+            return;
+        }
+        let now = self.clock.now();
+        let max_age = config().max_age;
         let expired: Vec<u32> = self
             .alerts
             .iter()
             .filter_map(|id| self.notifications.get(id))
-            .filter(|notification| {
-                notification
-                    .expire_time
-                    .is_some_and(|expire_time| Local::now() > expire_time)
-            })
+            .filter(|notification| !self.hovered.contains_key(&notification.id))
+            .filter(|notification| !self.removing.contains_key(&notification.id))
+            .filter(|notification| notification.is_expired(now, max_age))
             .map(|notification| notification.id)
             .collect();
 
         expired.into_iter().for_each(|id| {
             debug!("Notification {id} expired");
-            if let SignallerState::Initialized(signaller) = &mut self.signaller {
-                signaller.close_notification(id, NotificationClosedReason::Expired);
-            } else {
-                warn!("Signaller not initialized");
-            }
-            self.remove_notification(id);
+            self.close(id, NotificationClosedReason::Expired);
         });
     }
 
-    fn remove_notification(&mut self, id: u32) {
-        debug!("Removing notification {}", id);
-
-        // Remove the notification from alerts
-        if let Some((index, _)) = self
+    /// Set a timer for when the next displayed notification expires, unless one is already set for
+    /// then or sooner. A timer that goes off early, like for a notification hovered since, is
+    /// simply set again
+    fn schedule_expiry(&mut self) -> Option<Task<Message>> {
+        if self.idle_since.is_some() {
+            return None;
+        }
+        let max_age = config().max_age;
+        let next = self
             .alerts
             .iter()
-            .enumerate()
-            .find(|(_, &alert_id)| alert_id == id)
-        {
-            self.alerts.remove(index);
+            .filter_map(|id| self.notifications.get(id))
+            .filter(|notification| !self.hovered.contains_key(&notification.id))
+            .filter(|notification| !self.removing.contains_key(&notification.id))
+            .filter_map(|notification| notification.expiry(max_age))
+            .min()?;
+        if self.expiry_timer.is_some_and(|timer| timer <= next) {
+            return None;
         }
 
+        trace!("Setting expiry timer for {}", next);
+        self.expiry_timer = Some(next);
+        let delay = (next - self.clock.now()).to_std().unwrap_or_default() + EXPIRY_SLACK;
+        Some(Task::perform(tokio::time::sleep(delay), move |_| {
+            Message::Expired(next)
+        }))
+    }
+
+    fn add_notification(&mut self, notification: Notification) -> Task<Message> {
+        // Insert the notification, but only add to alerts if it didn't exist before, in order to
+        // replace the notification in place, if this is a replacement
+        let id = notification.id;
+        let replaced = self.notifications.insert(id, notification);
+        let new = replaced.is_none();
+        if let Some(replaced) = replaced {
+            self.remove_saved_images(&replaced);
+        } else {
+            self.alerts.push(id);
+            if !config().reduce_motion {
+                self.appearing.insert(id, Instant::now());
+            }
+        }
+        // A replacement for a notification fading out stays
+        self.removing.remove(&id);
+        self.prune_images();
+        let load_images = self.load_images(id);
+        let create_window = self.create_windows();
+
+        // Scroll back to the top for a new notification, but leave a replacement where it is
+        let scroll = if new {
+            scrollable::snap_to(notifications_scrollable_id(), RelativeOffset::START)
+        } else {
+            Task::none()
+        };
+
+        Task::batch([load_images, create_window, scroll])
+    }
+
+    /// Hold a notification until notifications are resumed, showing how many are held
+    fn hold_notification(&mut self, notification: Notification) -> Task<Message> {
+        // A held notification being replaced keeps its place in the queue
+        let id = notification.id;
+        match self.notifications.insert(id, notification) {
+            Some(replaced) => self.remove_saved_images(&replaced),
+            None => self.queued.push(id),
+        }
+        self.prune_images();
+        let load_images = self.load_images(id);
+        if !self.shows_held() {
+            return load_images;
+        }
+
+        Task::batch([load_images, self.create_windows()])
+    }
+
+    /// Whether to hold a notification rather than display it, which is while paused, or while a
+    /// window is fullscreen unless the notification is critical
+    fn holds(&self, notification: &Notification) -> bool {
+        self.paused
+            || (self.fullscreen
+                && (notification.urgency != Urgency::Critical
+                    || config().hold_critical_when_fullscreen))
+    }
+
+    /// Whether to show how many notifications are held, which isn't over a fullscreen window
+    fn shows_held(&self) -> bool {
+        !self.queued.is_empty() && !self.fullscreen
+    }
+
+    /// Note how long ago the session went idle, if it is, pushing back when the displayed
+    /// notifications expire by how long it was once it isn't. Those arriving meanwhile only count
+    /// the time since
+    fn set_idle(&mut self, idle: Option<Duration>) {
+        debug!("Setting idle to {idle:?}");
+        let now = self.clock.now();
+        match (self.idle_since, idle) {
+            (None, Some(ago)) => {
+                self.idle_since = Some(now - TimeDelta::from_std(ago).unwrap_or_default())
+            }
+            (Some(since), None) => {
+                self.idle_since = None;
+                for id in &self.alerts {
+                    // Hovered notifications are pushed back when the pointer leaves
+                    if self.hovered.contains_key(id) {
+                        continue;
+                    }
+                    if let Some(notification) = self.notifications.get_mut(id) {
+                        notification.delay_expiry(since.max(notification.time), now);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Pause or resume notifications, displaying the held ones in the order they arrived when
+    /// resumed
+    fn set_paused(&mut self, paused: bool) -> Task<Message> {
+        debug!("Setting paused to {paused}");
+        self.paused = paused;
+        self.release_held()
+    }
+
+    /// Note whether a window is fullscreen, displaying the held notifications once none is
+    fn set_fullscreen(&mut self, fullscreen: bool) -> Task<Message> {
+        debug!("Setting fullscreen to {fullscreen}");
+        self.fullscreen = fullscreen;
+        self.release_held()
+    }
+
+    /// Display the held notifications that are no longer held, in the order they arrived
+    fn release_held(&mut self) -> Task<Message> {
+        let now = self.clock.now();
+        let mut tasks = Vec::new();
+        for id in std::mem::take(&mut self.queued) {
+            if self
+                .notifications
+                .get(&id)
+                .is_some_and(|notification| self.holds(notification))
+            {
+                self.queued.push(id);
+                continue;
+            }
+            let Some(mut notification) = self.notifications.remove(&id) else {
+                continue;
+            };
+            // Count the time a notification is displayed from now, not from when it arrived
+            notification.delay_expiry(notification.time, now);
+            if let Some(notification) = self.merge_duplicate(notification) {
+                tasks.push(self.add_notification(notification));
+            }
+        }
+        Task::batch(tasks)
+    }
+
+    /// The outputs to show windows on, with `None` for the one the compositor picks, which the
+    /// configured output falls back to while it isn't connected
+    fn desired_outputs(&self) -> Vec<Option<String>> {
+        if config().all_monitors && !self.outputs.is_empty() {
+            return self.outputs.iter().cloned().map(Some).collect();
+        }
+
+        match &config().output {
+            Output::Named(name) if self.outputs.contains(name) => vec![Some(name.clone())],
+            _ => vec![None],
+        }
+    }
+
+    /// The theme for the color scheme in use
+    fn current_theme(&self) -> Theme {
+        let scheme = self.system_color_scheme.unwrap_or(config().color_scheme);
+        match scheme {
+            ColorScheme::Dark => config().dark_theme.into(),
+            ColorScheme::Light => config().light_theme.into(),
+        }
+    }
+
+    /// Warn when the configured output isn't connected
+    fn check_output(&self) {
+        if let Output::Named(name) = &config().output {
+            if !self.outputs.contains(name) {
+                warn!(
+                    "Output {} isn't connected, showing notifications on the focused output \
+                    instead of it. The connected outputs are {:?}",
+                    name, self.outputs
+                );
+            }
+        }
+    }
+
+    /// Create the layer shells for the outputs that don't have one yet
+    fn create_windows(&mut self) -> Task<Message> {
+        let missing: Vec<(Option<String>, Option<u32>)> =
+            self.desired_windows()
+                .into_iter()
+                .filter(|(output, notification)| {
+                    !self.windows.values().any(|window| {
+                        &window.output == output && &window.notification == notification
+                    })
+                })
+                .collect();
+        if missing.is_empty() {
+            return Task::none();
+        }
+
+        // Create the windows about as tall as their contents, rather than as a speck in the corner
+        // that grows once they are measured
+        let missing: Vec<_> = missing
+            .into_iter()
+            .map(|(output, notification)| {
+                let height = match notification.and_then(|id| self.notifications.get(&id)) {
+                    Some(notification) => {
+                        let kind =
+                            notification.layout_kind(config().compact, &config().compact_apps);
+                        (estimated_card_height(notification, kind).ceil() as u32)
+                            .clamp(1, capped_height(MAX_HEIGHT, config().max_height))
+                    }
+                    None => self.estimated_height(),
+                };
+                let layer = self.window_layer(notification);
+                (output, notification, (WIDTH as u32, height), layer)
+            })
+            .collect();
+        Task::batch(
+            missing
+                .into_iter()
+                .map(|(output, notification, size, layer)| {
+                    debug!(
+                        "Creating layer shell on {}",
+                        output.as_deref().unwrap_or("the active output")
+                    );
+                    let id = window::Id::unique();
+                    let output_option = output
+                        .clone()
+                        .map_or(OutputOption::None, OutputOption::OutputName);
+                    let anchored = match notification {
+                        Some(notification) => HashSet::from([notification]),
+                        None => self.alerts.iter().copied().collect(),
+                    };
+                    self.windows.insert(
+                        id,
+                        NotificationWindow {
+                            output,
+                            notification,
+                            layer,
+                            margin: (MARGIN_VERTICAL, MARGIN_HORIZONTAL),
+                            stack_offset: 0,
+                            opened: false,
+                            max_height: capped_height(MAX_HEIGHT, config().max_height),
+                            content_height: 1,
+                            scale_factor: 1.0,
+                            requested_size: size,
+                            resize_queued: false,
+                            suppressed_resizes: 0,
+                            anchored,
+                        },
+                    );
+                    let timeout =
+                        Task::perform(tokio::time::sleep(LAYER_SHELL_TIMEOUT), move |_| {
+                            Message::LayerShellTimeout(id)
+                        });
+                    Task::done(Message::NewLayerShell {
+                        settings: NewLayerShellSettings {
+                            layer,
+                            anchor: config().position.into(),
+                            size: Some(size),
+                            margin: Some(margins(
+                                config().position,
+                                MARGIN_VERTICAL,
+                                MARGIN_HORIZONTAL,
+                            )),
+                            exclusive_zone: Some(config().exclusive_zone.size()),
+                            keyboard_interactivity: config().keyboard_interactivity.into(),
+                            output_option,
+                            ..Default::default()
+                        },
+                        id,
+                    })
+                    .chain(timeout)
+                }),
+        )
+    }
+
+    /// A guess at the height of the displayed notifications, before they can be measured
+    fn estimated_height(&self) -> u32 {
+        let (notifications, _) = self.displayed();
+        let cards: f32 = notifications
+            .iter()
+            .map(|notification| {
+                let kind = notification.layout_kind(config().compact, &config().compact_apps);
+                estimated_card_height(notification, kind)
+            })
+            .sum();
+        let spacing = SMALL * notifications.len().saturating_sub(1) as f32;
+        ((cards + spacing).ceil() as u32).clamp(1, capped_height(MAX_HEIGHT, config().max_height))
+    }
+
+    /// Resize the window to its contents after the other messages already sent, so a burst of
+    /// content changes only resizes it once, to the last size
+    fn queue_resize(&mut self, id: window::Id) -> Task<Message> {
+        match self.windows.get_mut(&id) {
+            Some(window) if window.resize_queued => {
+                window.suppressed_resizes += 1;
+                Task::none()
+            }
+            Some(window) => {
+                window.resize_queued = true;
+                Task::done(Message::Resize(id))
+            }
+            None => Task::none(),
+        }
+    }
+
+    /// Run the resize queued for a window, returning the size to resize it to unless it was already
+    /// requested
+    fn run_resize(&mut self, id: window::Id) -> Option<(u32, u32)> {
+        let window = self.windows.get_mut(&id)?;
+        window.resize_queued = false;
+        match window.resize() {
+            Some(size) => {
+                trace!(
+                    "Resizing window {:?} to {:?}, suppressed {} redundant resizes",
+                    id,
+                    size,
+                    window.suppressed_resizes
+                );
+                window.suppressed_resizes = 0;
+                Some(size)
+            }
+            None => {
+                window.suppressed_resizes += 1;
+                None
+            }
+        }
+    }
+
+    /// Recreate the windows on the output the compositor picks once none of the notifications they
+    /// were created for are left, so that a burst of notifications spanning a focus change follows
+    /// the focus rather than staying on the old output
+    fn reanchor(&mut self) -> Option<Task<Message>> {
+        if self.alerts.is_empty() {
+            return None;
+        }
+
+        let stale: Vec<window::Id> = self
+            .windows
+            .iter()
+            .filter(|(_, window)| {
+                window.output.is_none()
+                    && window.opened
+                    && !self.alerts.iter().any(|id| window.anchored.contains(id))
+            })
+            .map(|(&id, _)| id)
+            .collect();
+        if stale.is_empty() {
+            return None;
+        }
+
+        debug!("Recreating layer shell to follow the focused output");
+        let remove = Task::batch(stale.into_iter().map(|id| self.remove_window(id)));
+        Some(Task::batch([remove, self.create_windows()]))
+    }
+
+    /// Remove the windows on outputs that were unplugged, and create them on outputs that were
+    /// plugged in, while notifications are shown
+    fn outputs_changed(&mut self, outputs: Vec<String>) -> Task<Message> {
+        debug!("Connected outputs changed to {:?}", outputs);
+        self.outputs = outputs;
+        self.check_output();
+        if self.windows.is_empty() {
+            return Task::none();
+        }
+
+        self.sync_windows()
+    }
+
+    /// Remove the windows that are no longer wanted, like on outputs that were unplugged or for
+    /// notifications that are gone, and create the ones that are missing
+    fn sync_windows(&mut self) -> Task<Message> {
+        let desired = self.desired_windows();
+        let removed: Vec<window::Id> = self
+            .windows
+            .iter()
+            .filter(|(_, window)| !desired.contains(&(window.output.clone(), window.notification)))
+            .map(|(&id, _)| id)
+            .collect();
+        let remove = Task::batch(removed.into_iter().map(|id| self.remove_window(id)));
+
+        Task::batch([remove, self.create_windows()])
+    }
+
+    /// The windows to show, as the output each is on and the notification it shows if each has
+    /// its own window
+    fn desired_windows(&self) -> Vec<(Option<String>, Option<u32>)> {
+        let notifications: Vec<Option<u32>> = if config().window_per_notification {
+            self.stack().into_iter().map(Some).collect()
+        } else {
+            vec![None]
+        };
+        self.desired_outputs()
+            .into_iter()
+            .flat_map(|output| {
+                notifications
+                    .iter()
+                    .map(move |&notification| (output.clone(), notification))
+            })
+            .collect()
+    }
+
+    /// The displayed notifications (by id), in order from the edge the windows are anchored to
+    fn stack(&self) -> Vec<u32> {
+        let mut stack: Vec<u32> = self
+            .displayed()
+            .0
+            .iter()
+            .map(|notification| notification.id)
+            .collect();
+        if config().position.is_bottom() {
+            stack.reverse();
+        }
+        stack
+    }
+
+    /// Move each notification's window past the windows nearer the edge on its output
+    fn restack(&mut self) -> Task<Message> {
+        let stack = self.stack();
+        let mut windows: Vec<(window::Id, &NotificationWindow)> = self
+            .windows
+            .iter()
+            .filter(|(_, window)| window.notification.is_some())
+            .map(|(&id, window)| (id, window))
+            .collect();
+        windows.sort_by_key(|(_, window)| {
+            let place = stack.iter().position(|&id| Some(id) == window.notification);
+            (window.output.clone(), place)
+        });
+
+        let heights: Vec<u32> = windows
+            .iter()
+            .map(|(_, window)| window.requested_size.1)
+            .collect();
+        let offsets = stack_offsets(&heights);
+        let mut moved = Vec::new();
+        for ((id, window), offset) in windows.iter().zip(&offsets) {
+            // Start again from the edge on each output
+            let first = windows
+                .iter()
+                .position(|(_, other)| other.output == window.output)
+                .expect("Window is in the list");
+            let offset = offset - offsets[first];
+            if offset != window.stack_offset {
+                moved.push((*id, offset));
+            }
+        }
+
+        Task::batch(moved.into_iter().filter_map(|(id, offset)| {
+            let window = self.windows.get_mut(&id)?;
+            window.stack_offset = offset;
+            let (vertical, horizontal) = window.margin;
+            Some(Task::done(Message::MarginChange {
+                id,
+                margin: margins(config().position, vertical + offset, horizontal),
+            }))
+        }))
+    }
+
+    /// Merge an exact duplicate of a displayed notification into it, if enabled, or else give the
+    /// notification back to be added
+    fn merge_duplicate(&mut self, notification: Notification) -> Option<Notification> {
+        // Replacements always replace in place
+        if self.notifications.contains_key(&notification.id) {
+            return Some(notification);
+        }
+        // A merged duplicate being replaced becomes its own notification
+        self.notifications
+            .values_mut()
+            .for_each(|other| other.merged.retain(|&id| id != notification.id));
+
+        if !config().collapse_duplicates {
+            return Some(notification);
+        }
+        let original = self.alerts.iter().copied().find(|id| {
+            self.notifications
+                .get(id)
+                .is_some_and(|other| notification.is_duplicate_of(other))
+        });
+        match original.and_then(|id| self.notifications.get_mut(&id)) {
+            Some(original) => {
+                debug!(
+                    "Merging duplicate notification {} into {}",
+                    notification.id, original.id
+                );
+                original.merge(notification);
+                None
+            }
+            None => Some(notification),
+        }
+    }
+
+    /// Remove a notification and send the DBUS signal for it and the duplicates merged into it
+    fn close(&mut self, id: u32, reason: NotificationClosedReason) {
+        // Fade a displayed notification out first, and only close it once it has
+        if !config().reduce_motion && self.alerts.contains(&id) {
+            self.appearing.remove(&id);
+            self.removing
+                .entry(id)
+                .or_insert_with(|| (Instant::now(), reason));
+            return;
+        }
+        self.finish_close(id, reason);
+    }
+
+    fn finish_close(&mut self, id: u32, reason: NotificationClosedReason) {
+        // Notifications replayed from the history aren't the apps' to be told about
+        let historical = self
+            .notifications
+            .get(&id)
+            .is_some_and(|notification| notification.historical);
+        let merged = match self.notifications.get(&id) {
+            Some(notification) => notification.merged.clone(),
+            None => {
+                // Closing a merged duplicate only removes it from the count
+                self.notifications
+                    .values_mut()
+                    .for_each(|other| other.merged.retain(|&merged| merged != id));
+                Vec::new()
+            }
+        };
+
+        if matches!(reason, NotificationClosedReason::DismissedByUser) {
+            if let Some(notification) = self.notifications.get(&id) {
+                hooks::run(Hook::Dismiss, notification);
+                self.recently_dismissed
+                    .push((Instant::now(), notification.clone()));
+                let excess = self
+                    .recently_dismissed
+                    .len()
+                    .saturating_sub(MAX_RECENTLY_DISMISSED);
+                let forgotten: Vec<_> = self.recently_dismissed.drain(..excess).collect();
+                forgotten
+                    .iter()
+                    .for_each(|(_, notification)| self.remove_saved_images(notification));
+            }
+        }
+
+        self.remove_notification(id);
+        if historical {
+            return;
+        }
+        if let SignallerState::Initialized(signaller) = &mut self.signaller {
+            std::iter::once(id)
+                .chain(merged)
+                .for_each(|id| signaller.close_notification(id, reason.clone()));
+        } else {
+            warn!("Signaller not initialized");
+        }
+    }
+
+    /// The displayed notifications that aren't already closing, oldest first
+    fn dismissable(&self) -> impl DoubleEndedIterator<Item = u32> + '_ {
+        self.alerts
+            .iter()
+            .copied()
+            .filter(|id| !self.removing.contains_key(id))
+    }
+
+    /// Dismiss the oldest displayed notification that isn't already closing, returning its id
+    fn dismiss_oldest(&mut self) -> Option<u32> {
+        let oldest = self.dismissable().next()?;
+        self.dismiss(oldest);
+        Some(oldest)
+    }
+
+    /// Dismiss the newest displayed notification that isn't already closing, returning its id
+    fn dismiss_latest(&mut self) -> Option<u32> {
+        let latest = self.dismissable().next_back()?;
+        self.dismiss(latest);
+        Some(latest)
+    }
+
+    /// Dismiss every displayed notification, returning the ids of those that weren't already
+    /// closing
+    fn dismiss_all(&mut self) -> Vec<u32> {
+        debug!("Dismissing all notifications");
+        let dismissed = self.dismissable().collect();
+        self.alerts
+            .clone()
+            .into_iter()
+            .for_each(|id| self.dismiss(id));
+        dismissed
+    }
+
+    /// Bring back the notification dismissed last, if it was within the grace period, returning
+    /// its id. The app has been told it closed, so it comes back like one replayed from the
+    /// history, without its actions and until dismissed again
+    fn restore_last_dismissed(&mut self) -> Option<(u32, Task<Message>)> {
+        let now = Instant::now();
+        let (recent, forgotten): (Vec<_>, Vec<_>) = std::mem::take(&mut self.recently_dismissed)
+            .into_iter()
+            .partition(|(dismissed, _)| now.duration_since(*dismissed) < RESTORE_GRACE);
+        self.recently_dismissed = recent;
+        forgotten
+            .iter()
+            .for_each(|(_, notification)| self.remove_saved_images(notification));
+
+        let (_, mut notification) = self.recently_dismissed.pop()?;
+        let id = notification.id;
+        if self.notifications.contains_key(&id) {
+            warn!("Not restoring notification {}, its id is in use", id);
+            self.remove_saved_images(&notification);
+            return None;
+        }
+
+        debug!("Restoring notification {}", id);
+        notification.historical = true;
+        notification.expire_time = None;
+        notification.actions = None;
+        notification.reply = None;
+        notification.merged.clear();
+        Some((id, self.add_notification(notification)))
+    }
+
+    /// Log the displayed and held notifications, so they aren't lost when they can't be shown
+    fn log_notifications(&self) {
+        self.alerts
+            .iter()
+            .chain(&self.queued)
+            .filter_map(|id| self.notifications.get(id))
+            .for_each(|notification| {
+                warn!(
+                    "Unable to show notification {} from {}: {}\n{}",
+                    notification.id,
+                    notification.name,
+                    notification.summary,
+                    notification.plain_text()
+                )
+            });
+    }
+
+    /// Remove a notification the user dismissed and send the DBUS signal
+    fn dismiss(&mut self, id: u32) {
+        debug!("User dismissed notification {id}");
+        self.close(id, NotificationClosedReason::DismissedByUser);
+    }
+
+    fn remove_notification(&mut self, id: u32) {
+        debug!("Removing notification {}", id);
+
+        // Remove the notification from alerts
+        if let Some((index, _)) = self
+            .alerts
+            .iter()
+            .enumerate()
+            .find(|(_, &alert_id)| alert_id == id)
+        {
+            self.alerts.remove(index);
+        }
+        self.queued.retain(|&queued| queued != id);
+
         // Remove the notification data
-        self.notifications.remove(&id);
+        if let Some(notification) = self.notifications.remove(&id) {
+            if !self
+                .notifications
+                .values()
+                .any(|other| other.name == notification.name)
+            {
+                self.expanded_groups.remove(&notification.name);
+            }
+            self.remove_saved_images(&notification);
+        }
+        self.expanded.remove(&id);
+        self.expanded_actions.remove(&id);
+        self.open_actions.remove(&id);
+        self.replies.remove(&id);
+        self.hovered.remove(&id);
+        self.appearing.remove(&id);
+        self.removing.remove(&id);
+        self.card_heights.remove(&id);
+        self.released.remove(&id);
+        if self.drag.is_some_and(|drag| drag.id == id) {
+            self.drag = None;
+        }
+        if self.focused == Some(id) {
+            self.focused = None;
+        }
+        if self.selected.is_some_and(|selected| selected.id() == id) {
+            self.selected = None;
+        }
+        self.prune_images();
+    }
+
+    /// Start decoding the images of a notification that aren't already decoded or decoding
+    fn load_images(&mut self, id: u32) -> Task<Message> {
+        let Some(notification) = self.notifications.get(&id) else {
+            return Task::none();
+        };
+        // The default icon is decoded too, in case the icon can't be
+        let icons = [Some(&notification.icon), self.default_icon.as_ref()];
+        // Icons that can't be read are noted once here rather than checked on every view, which
+        // covers the SVG icons that aren't decoded
+        for icon in icons.into_iter().flatten() {
+            if !self.images.contains_key(icon) && !icon.is_file() {
+                debug!("Unable to read icon {}", icon.display());
+                self.images.insert(icon.clone(), ImageState::Failed);
+            }
+        }
+        let paths: Vec<(PathBuf, bool)> = image_paths(notification)
+            .chain(self.default_icon.iter().filter(|icon| !is_svg(icon)))
+            .filter(|path| !self.images.contains_key(*path))
+            .map(|path| (path.clone(), icons.contains(&Some(path))))
+            .collect();
+
+        Task::batch(paths.into_iter().map(|(path, icon)| {
+            self.images.insert(path.clone(), ImageState::Loading);
+            Task::perform(decode_image(path.clone(), icon), move |handle| {
+                Message::ImageDecoded(path.clone(), handle)
+            })
+        }))
     }
+
+    /// Delete the images saved for a notification that no other notification displays or could be
+    /// restored with
+    fn remove_saved_images(&self, notification: &Notification) {
+        let in_use: HashSet<&PathBuf> = self
+            .notifications
+            .values()
+            .chain(
+                self.recently_dismissed
+                    .iter()
+                    .map(|(_, dismissed)| dismissed),
+            )
+            .flat_map(image_paths)
+            .collect();
+        image_paths(notification)
+            .filter(|path| !in_use.contains(path))
+            .for_each(|path| remove_tmp_image(path));
+    }
+
+    /// Forget the images and icons no notification displays anymore, besides the default icon
+    fn prune_images(&mut self) {
+        let displayed: HashSet<&PathBuf> = self
+            .notifications
+            .values()
+            .flat_map(|notification| image_paths(notification).chain([&notification.icon]))
+            .chain(&self.default_icon)
+            .collect();
+        self.images.retain(|path, _| displayed.contains(path));
+    }
+}
+
+fn notifications_scrollable_id() -> scrollable::Id {
+    scrollable::Id::new("notifications")
+}
+
+/// The raster images a notification displays, which are decoded off the UI thread
+fn image_paths(notification: &Notification) -> impl Iterator<Item = &PathBuf> {
+    let body_images = notification
+        .body
+        .iter()
+        .flat_map(|body| body.iter())
+        .filter_map(|element| match element {
+            BodyElement::Image { src, .. } => Some(src),
+            _ => None,
+        });
+
+    // Inline and remote images are decoded once they are saved to files
+    std::iter::once(&notification.icon)
+        .chain(body_images)
+        .filter(|path| !is_svg(path) && !is_data_uri(path) && !is_remote_image(path))
+}
+
+fn is_svg(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "svg")
+}
+
+/// Decode an image on a blocking thread so large images don't stall the UI, rounding the corners
+/// of icons like the corners of the notifications
+async fn decode_image(path: PathBuf, icon: bool) -> Option<iced::widget::image::Handle> {
+    let image = tokio::task::spawn_blocking(move || {
+        let mut image = ::image::open(&path)
+            .inspect_err(|err| {
+                trace!(
+                    "Unable to decode image {} with error {}",
+                    path.display(),
+                    err
+                )
+            })
+            .ok()?
+            .into_rgba8();
+        if icon {
+            // Scale the radius from the icon's size to the image's, which fits inside it
+            let scale = image.width().max(image.height()) as f32 / config().icon_size;
+            round_corners(&mut image, ICON_RADIUS * scale);
+        }
+        Some(image)
+    })
+    .await
+    .ok()??;
+
+    Some(iced::widget::image::Handle::from_rgba(
+        image.width(),
+        image.height(),
+        image.into_raw(),
+    ))
 }
 
 impl State {
@@ -242,147 +2180,928 @@ impl State {
         String::from("Notifications")
     }
 
-    fn view(&self, _window: window::Id) -> Element<Message> {
+    /// The notifications displayed, in order, and how many more are held back past the maximum
+    fn displayed(&self) -> (Vec<&Notification>, usize) {
+        let mut notifications: Vec<&Notification> = self
+            .alerts
+            .iter()
+            .filter_map(|id| self.notifications.get(id))
+            .collect();
+
+        if config().order == Order::NewestFirst {
+            notifications.reverse();
+        }
+        if config().sort_by_urgency {
+            sort_by_urgency(&mut notifications);
+        }
+
+        // Hold back the notifications past the maximum, which show as they make room
+        let visible = select_visible(&notifications, config().max_visible);
+        let hidden = notifications.len() - visible.len();
+        let mut displayed = if self.show_all {
+            notifications
+        } else {
+            visible
+        };
+
+        // Stack upwards from a bottom edge
+        if config().position.is_bottom() {
+            displayed.reverse();
+        }
+        (displayed, hidden)
+    }
+
+    /// What can be selected with the keyboard, in the order Tab moves through it: each displayed
+    /// notification followed by its action buttons, skipping group members shown as one line
+    fn selections(&self) -> Vec<Selection> {
+        let (notifications, _) = self.displayed();
+        let groups: Vec<Vec<&Notification>> = if config().group {
+            notifications
+                .chunk_by(|a, b| a.name == b.name)
+                .map(|group| match group {
+                    [notification] => vec![*notification],
+                    group => {
+                        // Most recent first, like the group is shown
+                        let mut group = group.to_vec();
+                        group.sort_by_key(|notification| std::cmp::Reverse(notification.time));
+                        if !self.expanded_groups.contains(&group[0].name) {
+                            group.truncate(1);
+                        }
+                        group
+                    }
+                })
+                .collect()
+        } else {
+            vec![notifications]
+        };
+
+        groups
+            .into_iter()
+            .flatten()
+            .flat_map(|notification| {
+                let id = notification.id;
+                let kind = notification.layout_kind(config().compact, &config().compact_apps);
+                let actions = if kind == LayoutKind::Full || self.focused == Some(id) {
+                    self.action_buttons(notification).len()
+                } else {
+                    0
+                };
+                std::iter::once(Selection::Notification(id))
+                    .chain((0..actions).map(move |i| Selection::Action(id, i)))
+            })
+            .collect()
+    }
+
+    fn view(&self, window: window::Id) -> Element<Message> {
+        // A closing window shows nothing, rather than the notifications that arrived meanwhile
+        if !self.windows.contains_key(&window) {
+            return Space::new(Length::Shrink, Length::Shrink).into();
+        }
+
         // Create a column of notifications from the alerts
-        let notifications = Column::from_iter(
-            self.alerts
-                .iter()
-                .filter_map(|id| self.notifications.get(id))
-                .map(|notification| self.view_notification(notification)),
-        )
+        let (mut notifications, hidden) = self.displayed();
+        // A notification's own window shows just it, with the held count in the window nearest
+        // the edge and the control for those past the maximum in the farthest
+        let (nearest, farthest) = match self.windows[&window].notification {
+            Some(id) => {
+                let stack = self.stack();
+                notifications.retain(|notification| notification.id == id);
+                (stack.first() == Some(&id), stack.last() == Some(&id))
+            }
+            None => (true, true),
+        };
+        let overflow = (hidden > 0 && farthest).then(|| {
+            let (label, show_all) = if self.show_all {
+                (String::from("show less"), false)
+            } else {
+                (format!("+ {hidden} more"), true)
+            };
+            button(text(label).size(FONT_SIZE).style(text::secondary))
+                .on_press(Message::ShowAll(show_all))
+                .padding([SMALL, BIG])
+                .width(WIDTH)
+                .style(|theme, status| {
+                    button::text(theme, status).with_background(theme.palette().background)
+                })
+        });
+        let notifications = if config().group {
+            Column::from_iter(
+                notifications
+                    .chunk_by(|a, b| a.name == b.name)
+                    .map(|group| match group {
+                        [notification] => self.view_notification(notification),
+                        group => self.view_group(group),
+                    }),
+            )
+        } else {
+            Column::from_iter(
+                notifications
+                    .into_iter()
+                    .map(|notification| self.view_notification(notification)),
+            )
+        }
         .spacing(SMALL);
 
-        // Wrap the column in a measuring container to dynamically resize the layer shell
-        MeasuringContainer::new(notifications.into(), |size| {
-            let height = size.height.ceil() as u32;
-            Message::ContainerResized(height.clamp(1, 2000))
+        // Show how many notifications are held while paused, above the rest
+        let held = (self.shows_held() && nearest).then(|| {
+            container(
+                Row::new()
+                    .push(
+                        text(format!("Paused, {} waiting", self.queued.len()))
+                            .size(FONT_SIZE)
+                            .style(text::secondary)
+                            .width(Length::Fill),
+                    )
+                    .push(
+                        button(text("resume").size(FONT_SIZE))
+                            .on_press(Message::Resume)
+                            .padding(0)
+                            .style(button::text),
+                    )
+                    .spacing(SMALL),
+            )
+            .style(container::bordered_box)
+            .padding([SMALL, BIG])
+            .width(WIDTH)
+        });
+        // From the edge the window is anchored to, the held count, the notifications, and the
+        // control for those past the maximum
+        let bottom = config().position.is_bottom();
+        let items = [
+            held.map(Element::from),
+            Some(notifications.into()),
+            overflow.map(Element::from),
+        ];
+        let notifications = if bottom {
+            Column::from_iter(items.into_iter().rev().flatten())
+        } else {
+            Column::from_iter(items.into_iter().flatten())
+        }
+        .spacing(SMALL);
+
+        // The scroll wheel dismisses and brings back notifications, unless it is needed to scroll
+        // them, when it takes holding Ctrl
+        let scrolls = self.windows[&window].content_height > self.windows[&window].max_height;
+        let wheel = !config().no_wheel_dismiss && (!scrolls || self.modifiers.control());
+        let notifications: Element<Message> = if wheel {
+            mouse_area(notifications)
+                .on_scroll(|delta| match delta {
+                    ScrollDelta::Lines { y, .. } | ScrollDelta::Pixels { y, .. } => {
+                        Message::Wheel(y)
+                    }
+                })
+                .into()
+        } else {
+            notifications.into()
+        };
+
+        // Scroll the column when it is taller than the window can be, keeping the notifications
+        // nearest a bottom edge in view
+        let notifications = scrollable(notifications).id(notifications_scrollable_id());
+        let notifications = if bottom {
+            notifications.anchor_bottom()
+        } else {
+            notifications
+        };
+
+        // Wrap the column in a measuring container to dynamically resize the layer shell, rounding
+        // its height to the device pixels it covers so fractional scaling neither clips the last
+        // pixel row nor adds one
+        let scale_factor = self
+            .windows
+            .get(&window)
+            .map_or(1.0, |window| window.scale_factor);
+        let measured = MeasuringContainer::new(notifications.into(), move |size| {
+            Message::ContainerResized(window, logical_pixels(size.height, scale_factor))
         })
-        .into()
+        .max_width(WIDTH)
+        .scale_factor(scale_factor);
+
+        // Against a bottom edge, draw from the bottom of the window, so the notifications stay put
+        // while the window is resized to a change in them, rather than jumping once it has been
+        let content: Element<Message> = if bottom {
+            container(measured)
+                .height(Length::Fill)
+                .align_y(Vertical::Bottom)
+                .into()
+        } else {
+            measured.into()
+        };
+
+        // Middle clicking anywhere dismisses every notification
+        if config().no_middle_click_dismiss {
+            content
+        } else {
+            mouse_area(content)
+                .on_middle_release(Message::DismissAll)
+                .into()
+        }
     }
 
     fn update(&mut self, message: Message) -> Task<Message> {
-        // Prune expired notifications
+        // Prune expired notifications, and those done fading out
+        self.finish_animations();
         self.remove_expired();
 
         // Process messages
         let task = match message {
-            Message::ContainerResized(height) => {
+            Message::ActivateSelected => match self.selected {
+                // Show the notification in full, like double clicking it
+                Some(Selection::Notification(id)) => Task::done(Message::ToggleFocus(id)),
+                Some(Selection::Action(id, i)) => self
+                    .notifications
+                    .get(&id)
+                    .and_then(|notification| self.action_buttons(notification).into_iter().nth(i))
+                    .map_or_else(Task::none, |(_, message)| Task::done(message)),
+                None => Task::none(),
+            },
+            Message::CardMeasured(id, height) => {
+                self.card_heights.insert(id, height);
+                Task::none()
+            }
+            Message::ContainerResized(id, height) => {
                 trace!("Container resized: {:?}", height);
 
-                let size = (WIDTH as u32, height);
-                if let Some(id) = self.window_id {
-                    Task::done(Message::SizeChange { id, size })
+                if let Some(window) = self.windows.get_mut(&id) {
+                    window.content_height = height;
+                    self.queue_resize(id)
                 } else {
                     warn!("Container resized but window doesn't exist");
                     Task::none()
                 }
             }
-            Message::ActionInvoked(id, key) => {
-                debug!("Action invoked: {} {}", id, key);
-
-                match &mut self.signaller {
-                    SignallerState::Unitialized => {
-                        warn!("Signaller unitialized, unable to signal action")
+            Message::CopyBody(id) => {
+                debug!("Copying body of notification {id}");
+                match self.notifications.get(&id) {
+                    Some(notification) => iced::clipboard::write(notification.plain_text()),
+                    None => Task::none(),
+                }
+            }
+            Message::CopyCode(id) => {
+                debug!("Copying code from notification {id}");
+                let code = self.notifications.get(&id).and_then(|notification| {
+                    find_code(&notification.plain_text()).map(String::from)
+                });
+                match code {
+                    Some(code) => iced::clipboard::write(code),
+                    None => Task::none(),
+                }
+            }
+            Message::ActionInvoked(id, key) => {
+                debug!("Action invoked: {} {}", id, key);
+
+                match &mut self.signaller {
+                    SignallerState::Unitialized => {
+                        warn!("Signaller unitialized, unable to signal action")
+                    }
+                    SignallerState::Initialized(signaller) => {
+                        signaller.action_invoked(id, key.clone())
+                    }
+                }
+                if let Some(notification) = self.notifications.get(&id) {
+                    hooks::run(Hook::Action(key), notification);
+                }
+
+                let dismisses = self.notifications.get(&id).is_some_and(|notification| {
+                    config().action_dismiss.dismisses(notification.resident)
+                });
+                if dismisses {
+                    self.dismiss(id);
+                }
+
+                Task::none()
+            }
+            Message::Dbus(message) => match message {
+                DbusMessage::Initialized(signaller) => {
+                    debug!("NotificationSignaller initialized");
+
+                    self.signaller = SignallerState::Initialized(signaller);
+
+                    Task::none()
+                }
+                DbusMessage::Notify(notification)
+                    if self.holds(&notification) && !self.alerts.contains(&notification.id) =>
+                {
+                    debug!("Holding notification {}", notification.id);
+                    self.hold_notification(notification)
+                }
+                DbusMessage::Notify(notification) => {
+                    debug!("Received notification {}", notification.id);
+                    if let Some(sound) = &notification.sound {
+                        play_sound(sound);
+                    }
+
+                    match self.merge_duplicate(notification) {
+                        Some(notification) => self.add_notification(notification),
+                        None => Task::none(),
+                    }
+                }
+                DbusMessage::IconLoaded {
+                    id,
+                    time,
+                    icon,
+                    accent,
+                } => {
+                    // Only update the icon if the notification hasn't since been replaced
+                    match self.notifications.get_mut(&id) {
+                        Some(notification) if notification.time == time => {
+                            debug!("Loaded icon for notification {id}");
+                            notification.icon = icon;
+                            notification.accent = accent;
+                            self.prune_images();
+                            self.load_images(id)
+                        }
+                        _ => {
+                            remove_tmp_image(&icon);
+                            Task::none()
+                        }
+                    }
+                }
+                DbusMessage::BodyImagesLoaded { id, original, body } => {
+                    // Only update the body if the notification hasn't since been replaced
+                    match self.notifications.get_mut(&id) {
+                        Some(notification) if notification.body.as_ref() == Some(&original) => {
+                            debug!("Loaded body images for notification {id}");
+                            notification.body = Some(body);
+                            self.prune_images();
+                            self.load_images(id)
+                        }
+                        _ => {
+                            body.iter().for_each(|element| {
+                                if let BodyElement::Image { src, .. } = element {
+                                    remove_tmp_image(src);
+                                }
+                            });
+                            Task::none()
+                        }
+                    }
+                }
+                DbusMessage::CloseNotification(id) => {
+                    self.close(id, NotificationClosedReason::ClosedByCloseNotification);
+                    Task::none()
+                }
+                DbusMessage::SetPaused(paused) => self.set_paused(paused),
+                DbusMessage::TogglePaused => self.set_paused(!self.paused),
+                DbusMessage::DismissLatest(reply) => {
+                    reply.send(self.dismiss_latest());
+                    Task::none()
+                }
+                DbusMessage::DismissOldest(reply) => {
+                    reply.send(self.dismiss_oldest());
+                    Task::none()
+                }
+                DbusMessage::DismissAll(reply) => {
+                    reply.send(self.dismiss_all());
+                    Task::none()
+                }
+                DbusMessage::ShowLatestFromHistory(reply) => match self.restore_last_dismissed() {
+                    Some((id, task)) => {
+                        reply.send(Some(id));
+                        task
+                    }
+                    None => {
+                        reply.send(None);
+                        Task::none()
+                    }
+                },
+            },
+            Message::ImageDecoded(path, handle) => {
+                // The image may have been pruned while it was decoding
+                if let Some(state) = self.images.get_mut(&path) {
+                    *state = match handle {
+                        Some(handle) => ImageState::Loaded(handle),
+                        None => ImageState::Failed,
+                    };
+                }
+
+                Task::none()
+            }
+            Message::DismissAll => {
+                self.dismiss_all();
+                Task::none()
+            }
+            Message::DismissOldest => {
+                self.dismiss_oldest();
+                Task::none()
+            }
+            Message::RestoreLastDismissed => self
+                .restore_last_dismissed()
+                .map_or_else(Task::none, |(_, task)| task),
+            Message::Wheel(y) => {
+                let now = Instant::now();
+                let cooling = self
+                    .last_wheel
+                    .is_some_and(|last| now.duration_since(last) < WHEEL_COOLDOWN);
+                if cooling || y == 0.0 {
+                    Task::none()
+                } else {
+                    self.last_wheel = Some(now);
+                    // Scrolling down takes notifications away, and up brings them back
+                    let message = if y < 0.0 {
+                        Message::DismissOldest
+                    } else {
+                        Message::RestoreLastDismissed
+                    };
+                    Task::done(message)
+                }
+            }
+            Message::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers;
+                Task::none()
+            }
+            Message::DismissSelected => {
+                // Without a selection, Escape just unfocuses
+                match self.selected.take() {
+                    Some(selection) => self.dismiss(selection.id()),
+                    None => self.focused = None,
+                }
+                Task::none()
+            }
+            Message::GroupDismissed(ids) => {
+                ids.into_iter().for_each(|id| self.dismiss(id));
+                Task::none()
+            }
+            Message::MoveSelection(forward) => {
+                let selections = self.selections();
+                let current = self
+                    .selected
+                    .and_then(|selected| selections.iter().position(|&s| s == selected));
+                let next = match (current, forward) {
+                    (Some(i), true) => (i + 1) % selections.len(),
+                    (Some(i), false) => (i + selections.len() - 1) % selections.len(),
+                    (None, true) => 0,
+                    (None, false) => selections.len().saturating_sub(1),
+                };
+                self.selected = selections.get(next).copied();
+                trace!("Selected {:?}", self.selected);
+                Task::none()
+            }
+            Message::ExpandActions(id) => {
+                debug!("Expanding actions of notification {id}");
+                self.expanded_actions.insert(id);
+                Task::none()
+            }
+            Message::ExpandBody(id) => {
+                debug!("Expanding body of notification {id}");
+                self.expanded.insert(id);
+                Task::none()
+            }
+            Message::OpenLink(link) => {
+                debug!("Opening link {link}");
+                open_link(&link);
+                Task::none()
+            }
+            Message::PointerEntered(id) => {
+                self.hovered.insert(id, self.clock.now());
+                Task::none()
+            }
+            Message::DragStarted(id) => {
+                self.drag = Some(Drag {
+                    id,
+                    start: None,
+                    offset: 0.0,
+                });
+                self.released.remove(&id);
+                Task::none()
+            }
+            Message::DragMoved(x) => {
+                if let Some(drag) = &mut self.drag {
+                    drag.offset = x - *drag.start.get_or_insert(x);
+                }
+                Task::none()
+            }
+            Message::DragEnded => {
+                self.end_drag();
+                Task::none()
+            }
+            Message::CardReleased(id) => {
+                // Letting go of a dragged notification isn't clicking it
+                if self.end_drag() {
+                    Task::none()
+                } else {
+                    Task::done(Message::ActionInvoked(id, DEFAULT_ACTION.into()))
+                }
+            }
+            Message::PointerExited(id) => {
+                // Give back the time spent reading it, so it doesn't vanish as soon as the pointer
+                // leaves
+                if let Some(since) = self.hovered.remove(&id) {
+                    if let Some(notification) = self.notifications.get_mut(&id) {
+                        notification.delay_expiry(since, self.clock.now());
                     }
-                    SignallerState::Initialized(signaller) => signaller.action_invoked(id, key),
                 }
-
                 Task::none()
             }
-            Message::Dbus(message) => match message {
-                DbusMessage::Initialized(signaller) => {
-                    debug!("NotificationSignaller initialized");
-
-                    self.signaller = SignallerState::Initialized(signaller);
-
-                    Task::none()
-                }
-                DbusMessage::Notify(notification) => {
-                    debug!("Received notification {}", notification.id);
-
-                    // Insert the notification, but only add to alerts if it didn't exist before,
-                    // in order to replace the notification in place, if this is a replacement
-                    let id = notification.id;
-                    if self.notifications.insert(id, notification).is_none() {
-                        self.alerts.push(id);
+            Message::Resume => self.set_paused(false),
+            Message::Fullscreen(fullscreen) => self.set_fullscreen(fullscreen),
+            Message::ColorSchemeChanged(scheme) => {
+                self.system_color_scheme = scheme;
+                Task::none()
+            }
+            Message::Idle(idle) => {
+                self.set_idle(idle);
+                Task::none()
+            }
+            Message::ReplyEdited(id, text) => {
+                self.replies.insert(id, text);
+                Task::none()
+            }
+            Message::ReplySubmitted(id) => {
+                let reply = self.replies.remove(&id);
+                if let Some(text) = reply.filter(|text| !text.trim().is_empty()) {
+                    debug!("Replying to notification {id}");
+                    match &mut self.signaller {
+                        SignallerState::Unitialized => {
+                            warn!("Signaller unitialized, unable to signal reply")
+                        }
+                        SignallerState::Initialized(signaller) => {
+                            signaller.notification_replied(id, text)
+                        }
+                    }
+                    if let Some(notification) = self.notifications.get(&id) {
+                        hooks::run(Hook::Action(INLINE_REPLY.into()), notification);
                     }
 
-                    // Create the layer shell if it doesn't exist
-                    if self.window_id.is_none() {
-                        debug!("Creating layer shell");
-                        let id = window::Id::unique();
-                        self.window_id = Some(id);
-                        Task::done(Message::NewLayerShell {
-                            settings: NewLayerShellSettings {
-                                anchor: Anchor::Top | Anchor::Right,
-                                size: Some((1, 1)),
-                                margin: Some((60, 20, 0, 0)),
-                                ..Default::default()
-                            },
-                            id,
-                        })
-                    } else {
-                        Task::none()
+                    // Replying dismisses like invoking an action
+                    let dismisses = self.notifications.get(&id).is_some_and(|notification| {
+                        config().action_dismiss.dismisses(notification.resident)
+                    });
+                    if dismisses {
+                        self.dismiss(id);
                     }
                 }
-                DbusMessage::CloseNotification(id) => {
-                    // Remove the notification and send the DBUS signal
-                    self.remove_notification(id);
-                    if let SignallerState::Initialized(signaller) = &mut self.signaller {
-                        signaller.close_notification(
-                            id,
-                            NotificationClosedReason::ClosedByCloseNotification,
+                Task::none()
+            }
+            Message::ShowAll(show_all) => {
+                self.show_all = show_all;
+                Task::none()
+            }
+            Message::Expired(time) => {
+                // The expired notifications have been removed, so the next timer can be set
+                if self.expiry_timer == Some(time) {
+                    self.expiry_timer = None;
+                }
+                Task::none()
+            }
+            Message::Tick => Task::none(),
+            Message::ToggleActions(id) => {
+                debug!("Toggling actions of notification {id}");
+                if !self.open_actions.remove(&id) {
+                    self.open_actions.insert(id);
+                }
+                Task::none()
+            }
+            Message::ToggleFocus(id) => {
+                debug!("Toggling focus of notification {id}");
+                self.focused = if self.focused == Some(id) {
+                    None
+                } else {
+                    Some(id)
+                };
+                Task::none()
+            }
+            Message::ToggleGroup(name) => {
+                debug!("Toggling group of {name}");
+                if !self.expanded_groups.remove(&name) {
+                    self.expanded_groups.insert(name);
+                }
+                Task::none()
+            }
+            Message::Unfocus => {
+                self.focused = None;
+                Task::none()
+            }
+            Message::UserDismissed(id) => {
+                self.dismiss(id);
+                Task::none()
+            }
+            Message::WindowClosed(id) => self.window_closed(id),
+            Message::LayerShellTimeout(id) => {
+                let failed = self.windows.get(&id).is_some_and(|window| !window.opened);
+                // Windows on other outputs opening means this output is the problem
+                if failed && !self.windows.values().any(|window| window.opened) {
+                    // The compositor may not support the layer shell
+                    error!(
+                        "Notification window didn't open within {:?}",
+                        LAYER_SHELL_TIMEOUT
+                    );
+                    // Close the windows too, rather than only forgetting them, in case they open
+                    let ids: Vec<window::Id> = self.windows.keys().copied().collect();
+                    let remove = Task::batch(ids.into_iter().map(|id| self.remove_window(id)));
+                    match config().layer_shell_fallback {
+                        LayerShellFallback::Log => self.log_notifications(),
+                        LayerShellFallback::Exit => std::process::exit(1),
+                    }
+                    remove
+                } else {
+                    if failed {
+                        warn!(
+                            "Notification window {:?} didn't open within {:?}",
+                            id, LAYER_SHELL_TIMEOUT
                         );
-                    } else {
-                        warn!("Signaller not initialized");
                     }
-
                     Task::none()
                 }
+            }
+            Message::WindowOpened(id) => {
+                if let Some(window) = self.windows.get_mut(&id) {
+                    window.opened = true;
+                }
+                // Size the margins and maximum height to the screen the window opened on, and
+                // measure the contents in its pixels
+                Task::batch([
+                    window::monitor_size(id).map(move |size| Message::MonitorSized(id, size)),
+                    window::scale_factor(id).map(move |scale| Message::Rescaled(id, scale)),
+                ])
+            }
+            Message::Resize(id) => match self.run_resize(id) {
+                Some(size) => Task::done(Message::SizeChange { id, size }),
+                None => Task::none(),
             },
-            Message::Tick => Task::none(),
-            Message::UserDismissed(id) => {
-                debug!("User dismissed notification {id}");
-
-                // Remove the notification and send the DBUS signal
-                self.remove_notification(id);
-                if let SignallerState::Initialized(signaller) = &mut self.signaller {
-                    signaller.close_notification(id, NotificationClosedReason::DismissedByUser);
-                } else {
-                    warn!("Signaller not initialized");
+            Message::Rescaled(id, scale_factor) => {
+                // The measuring container publishes the new size if it covers different pixels
+                if let Some(window) = self.windows.get_mut(&id) {
+                    debug!("Window {:?} has scale factor {}", id, scale_factor);
+                    window.scale_factor = scale_factor;
                 }
-
                 Task::none()
             }
-            Message::WindowClosed(id) => {
-                self.remove_id(id);
+            Message::OutputsChanged(outputs) => {
+                if outputs != self.outputs {
+                    self.outputs_changed(outputs)
+                } else {
+                    Task::none()
+                }
+            }
+            Message::MonitorSized(_, None) => {
+                debug!("Unknown monitor size, keeping absolute margins and maximum height");
                 Task::none()
             }
+            Message::MonitorSized(id, Some(size)) => {
+                let percent = config().margin_percent;
+                let vertical = percent.map_or(MARGIN_VERTICAL, |percent| {
+                    (size.height * percent / 100.0).round() as i32
+                });
+                // Leave as much space past the window as before it, and shrink a window that grew
+                // past it before the size was known
+                let resize = match self.windows.get_mut(&id) {
+                    Some(window) => {
+                        let fits = (size.height as i32 - 2 * vertical).max(1) as u32;
+                        window.max_height = capped_height(fits, config().max_height);
+                        debug!("Setting maximum height to {}", window.max_height);
+                        self.queue_resize(id)
+                    }
+                    None => Task::none(),
+                };
+
+                let margin = match (percent, self.windows.get_mut(&id)) {
+                    (Some(percent), Some(window)) => {
+                        let horizontal = (size.width * percent / 100.0).round() as i32;
+                        debug!("Setting margins to {percent}% of {size:?}");
+                        window.margin = (vertical, horizontal);
+                        Task::done(Message::MarginChange {
+                            id,
+                            margin: margins(
+                                config().position,
+                                vertical + window.stack_offset,
+                                horizontal,
+                            ),
+                        })
+                    }
+                    _ => Task::none(),
+                };
+                Task::batch([resize, margin])
+            }
             _ => unreachable!(),
         };
 
-        // If there are no alerts to display, close the window
-        if self.alerts.is_empty() {
-            if let Some(id) = self.window_id {
-                debug!("Closing layer shell");
-                return Task::done(Message::RemoveWindow(id));
+        // Let the DBUS interface know what is being displayed
+        if let SignallerState::Initialized(signaller) = &self.signaller {
+            signaller.set_active(
+                self.alerts
+                    .iter()
+                    .filter_map(|id| self.notifications.get(id))
+                    .filter(|notification| !notification.historical),
+            );
+        }
+
+        // Remove the next notification to expire right when it does, rather than at the next tick
+        let task = match self.schedule_expiry() {
+            Some(timer) => Task::batch([task, timer]),
+            None => task,
+        };
+
+        // If there are no alerts to display, nor any held to show the count of, close the windows
+        if self.alerts.is_empty() && !self.shows_held() && !self.windows.is_empty() {
+            debug!("Closing layer shells");
+            // Exit now that the first notification is gone
+            if config().once {
+                if let SignallerState::Initialized(signaller) = &mut self.signaller {
+                    signaller.shutdown();
+                } else {
+                    std::process::exit(0);
+                }
             }
+            let ids: Vec<window::Id> = self.windows.keys().copied().collect();
+            let remove: Vec<Task<Message>> =
+                ids.into_iter().map(|id| self.remove_window(id)).collect();
+            return Task::batch(std::iter::once(task).chain(remove));
         }
 
-        task
+        if let Some(reanchor) = self.reanchor() {
+            return Task::batch([task, reanchor]);
+        }
+
+        // Give each notification its own window, stacked past the others
+        let task = if config().window_per_notification && !self.windows.is_empty() {
+            Task::batch([task, self.sync_windows(), self.restack()])
+        } else {
+            task
+        };
+
+        // Move the windows to a different layer if a critical notification arrived or left
+        let changes: Vec<(window::Id, Layer)> = self
+            .windows
+            .iter()
+            .map(|(&id, window)| (id, window.layer, self.window_layer(window.notification)))
+            .filter(|(_, layer, desired)| layer != desired)
+            .map(|(id, _, desired)| (id, desired))
+            .collect();
+        let changes: Vec<Task<Message>> = changes
+            .into_iter()
+            .map(|(id, layer)| {
+                debug!("Changing layer of window {:?} to {:?}", id, layer);
+                if let Some(window) = self.windows.get_mut(&id) {
+                    window.layer = layer;
+                }
+                Task::done(Message::LayerChange { id, layer })
+            })
+            .collect();
+
+        Task::batch(std::iter::once(task).chain(changes))
+    }
+
+    /// The layer the window should be on, escalated while a critical notification is displayed
+    fn window_layer(&self, notification: Option<u32>) -> Layer {
+        let shown: Vec<u32> = match notification {
+            Some(id) => vec![id],
+            None => self.alerts.clone(),
+        };
+        let has_critical = shown
+            .iter()
+            .filter_map(|id| self.notifications.get(id))
+            .any(|notification| notification.urgency == Urgency::Critical);
+        if has_critical {
+            config().critical_layer.into()
+        } else {
+            config().layer.into()
+        }
+    }
+
+    /// Remove a window, which is closing until the compositor says it has closed. Notifications
+    /// arriving meanwhile get a new window rather than the closing one
+    fn remove_window(&mut self, id: window::Id) -> Task<Message> {
+        self.windows.remove(&id);
+        self.closing.insert(id);
+        Task::done(Message::RemoveWindow(id))
     }
 
-    fn remove_id(&mut self, _id: window::Id) {
-        self.window_id = None;
+    /// Forget a window that has closed, and replace it if the compositor closed it while it still
+    /// had notifications to show, like when its output was unplugged
+    fn window_closed(&mut self, id: window::Id) -> Task<Message> {
+        let task = if self.closing.remove(&id) {
+            Task::none()
+        } else if self.windows.remove(&id).is_some() && !self.alerts.is_empty() {
+            debug!(
+                "Window {:?} closed with notifications left, replacing it",
+                id
+            );
+            self.create_windows()
+        } else {
+            Task::none()
+        };
+        if self.windows.is_empty() {
+            self.show_all = false;
+        }
+        task
     }
 
     fn subscription(&self) -> Subscription<Message> {
         let dbus = Subscription::run(dbus::dbus).map(Message::Dbus);
-        // Send a message every second to run update, to update times and remove expired
-        // notifications
-        let ticker = iced::time::every(iced::time::Duration::from_secs(1)).map(|_| Message::Tick);
+        // While notifications are displayed, send a message every tick interval to run update and
+        // update the times, or more often while a time bar is draining so it moves smoothly.
+        // Expiry has its own timer
+        let time_bar_shown = !config().no_time_bar
+            && self
+                .alerts
+                .iter()
+                .filter_map(|id| self.notifications.get(id))
+                .any(|notification| notification.expire_time.is_some());
+        let tick_interval = iced::time::Duration::from_millis(config().tick_interval.max(1));
+        let tick = if time_bar_shown {
+            tick_interval.min(iced::time::Duration::from_millis(100))
+        } else {
+            tick_interval
+        };
+        let ticker = if self.alerts.is_empty() {
+            Subscription::none()
+        } else {
+            iced::time::every(tick).map(|_| Message::Tick)
+        };
+        // Redraw smoothly while notifications are fading in or out or sliding back
+        let animation =
+            if self.appearing.is_empty() && self.removing.is_empty() && self.released.is_empty() {
+                Subscription::none()
+            } else {
+                iced::time::every(ANIMATION_FRAME).map(|_| Message::Tick)
+            };
         let window_closed = iced::window::close_events().map(Message::WindowClosed);
-        Subscription::batch([dbus, ticker, window_closed])
+        let window_opened = iced::window::open_events().map(Message::WindowOpened);
+        let rescaled = iced::event::listen_with(|event, _, id| match event {
+            iced::Event::Window(window::Event::Rescaled(scale)) => {
+                Some(Message::Rescaled(id, scale))
+            }
+            _ => None,
+        });
+        let modifiers = iced::event::listen_with(|event, _, _| match event {
+            iced::Event::Keyboard(iced::keyboard::Event::ModifiersChanged(modifiers)) => {
+                Some(Message::ModifiersChanged(modifiers))
+            }
+            _ => None,
+        });
+        // Navigate the notifications with the keyboard, when the surface has keyboard focus
+        let keys = iced::keyboard::on_key_press(|key, modifiers| {
+            use iced::keyboard::{key::Named, Key};
+            match key {
+                Key::Named(Named::Tab) => Some(Message::MoveSelection(!modifiers.shift())),
+                Key::Named(Named::Enter) => Some(Message::ActivateSelected),
+                Key::Named(Named::Escape) if modifiers.shift() => Some(Message::DismissAll),
+                Key::Named(Named::Escape) => Some(Message::DismissSelected),
+                _ => None,
+            }
+        });
+        // Follow the pointer or finger while a notification is dragged, until it is let go of or
+        // leaves the window
+        let drag = if self.drag.is_some() {
+            iced::event::listen_with(|event, _, _| {
+                use iced::{mouse, touch};
+                match event {
+                    iced::Event::Mouse(mouse::Event::CursorMoved { position })
+                    | iced::Event::Touch(touch::Event::FingerMoved { position, .. }) => {
+                        Some(Message::DragMoved(position.x))
+                    }
+                    iced::Event::Mouse(
+                        mouse::Event::ButtonReleased(mouse::Button::Left)
+                        | mouse::Event::CursorLeft,
+                    )
+                    | iced::Event::Touch(
+                        touch::Event::FingerLifted { .. } | touch::Event::FingerLost { .. },
+                    ) => Some(Message::DragEnded),
+                    _ => None,
+                }
+            })
+        } else {
+            Subscription::none()
+        };
+        // Follow outputs being plugged in or unplugged to mirror the notifications onto
+        let outputs = if config().all_monitors || config().output != Output::Focused {
+            Subscription::run(outputs::outputs).map(Message::OutputsChanged)
+        } else {
+            Subscription::none()
+        };
+        // Hold notifications while a window is fullscreen
+        let fullscreen = if config().pause_when_fullscreen {
+            Subscription::run(fullscreen::fullscreen).map(Message::Fullscreen)
+        } else {
+            Subscription::none()
+        };
+        // Stop expiring notifications while the session is idle
+        let idle = if config().pause_expiry_when_idle {
+            Subscription::run(idle::idle).map(Message::Idle)
+        } else {
+            Subscription::none()
+        };
+        // Switch themes with the system's color scheme
+        let color_scheme = if config().follow_color_scheme {
+            Subscription::run(appearance::color_scheme).map(Message::ColorSchemeChanged)
+        } else {
+            Subscription::none()
+        };
+        Subscription::batch([
+            dbus,
+            ticker,
+            animation,
+            window_closed,
+            window_opened,
+            rescaled,
+            modifiers,
+            keys,
+            drag,
+            outputs,
+            fullscreen,
+            idle,
+            color_scheme,
+        ])
     }
 
     fn style(&self, theme: &Theme) -> iced::theme::Style {
@@ -393,12 +3112,525 @@ impl State {
     }
 
     fn theme(&self, _: window::Id) -> Theme {
-        iced::Theme::custom(
-            "Gruvbox Dark".to_string(),
-            iced::theme::Palette {
-                text: iced::color!(0xebdbb2),
-                ..iced::theme::Palette::GRUVBOX_DARK
-            },
-        )
+        self.current_theme()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dbus::Reply;
+
+    #[test]
+    fn test_notification_style() {
+        let theme = Theme::GruvboxDark;
+        let palette = theme.palette();
+        let style = UrgencyStyleConfig {
+            critical_border_width: 4.0,
+            ..UrgencyStyleConfig::default()
+        };
+        let accent = iced::Color::from_rgb8(0x45, 0x85, 0x88);
+
+        let critical = notification_style(&theme, Urgency::Critical, Some(accent), 1.0, &style);
+        assert_eq!(critical.border.color, palette.danger);
+        assert_eq!(critical.border.width, 4.0);
+        assert_eq!(critical.text_color, Some(palette.text));
+
+        let normal = notification_style(&theme, Urgency::Normal, Some(accent), 1.0, &style);
+        assert_eq!(normal.border.color, accent);
+        assert_eq!(normal.border.width, 2.0);
+        assert_eq!(
+            normal.background,
+            Some(Background::Color(palette.background))
+        );
+
+        let low = notification_style(&theme, Urgency::Low, None, 1.0, &style);
+        assert_eq!(low.border.color, palette.text);
+        assert_eq!(low.text_color, Some(palette.text.scale_alpha(0.7)));
+
+        let tinted = UrgencyStyleConfig {
+            critical_border_color: Some([255, 0, 0]),
+            critical_tint: 1.0,
+            ..UrgencyStyleConfig::default()
+        };
+        let critical = notification_style(&theme, Urgency::Critical, None, 0.5, &tinted);
+        assert_eq!(
+            critical.background,
+            Some(Background::Color(iced::Color::from_rgba(
+                1.0, 0.0, 0.0, 0.5
+            )))
+        );
+    }
+
+    #[test]
+    fn test_notification_background() {
+        let theme = Theme::GruvboxDark;
+        let palette = theme.palette();
+
+        // By default every urgency has the theme's background
+        for urgency in [Urgency::Low, Urgency::Normal, Urgency::Critical] {
+            let style = notification_style(&theme, urgency, None, 1.0, &Default::default());
+            assert_eq!(
+                style.background,
+                Some(Background::Color(palette.background))
+            );
+        }
+
+        let washed = UrgencyStyleConfig {
+            critical_background: Some([0x3c, 0x1f, 0x1e]),
+            ..UrgencyStyleConfig::default()
+        };
+        let critical = notification_style(&theme, Urgency::Critical, None, 1.0, &washed);
+        assert_eq!(
+            critical.background,
+            Some(Background::Color(iced::Color::from_rgb8(0x3c, 0x1f, 0x1e)))
+        );
+        let normal = notification_style(&theme, Urgency::Normal, None, 1.0, &washed);
+        assert_eq!(
+            normal.background,
+            Some(Background::Color(palette.background))
+        );
+    }
+
+    #[test]
+    fn test_animation_progress() {
+        let start = Instant::now();
+        assert_eq!(animation_progress(start, start), 0.0);
+        assert_eq!(animation_progress(start + ANIMATION_DURATION, start), 0.0);
+        assert_eq!(animation_progress(start, start + ANIMATION_DURATION), 1.0);
+        assert_eq!(
+            animation_progress(start, start + 2 * ANIMATION_DURATION),
+            1.0
+        );
+
+        // Easing out, the first half covers more than half the way
+        let halfway = animation_progress(start, start + ANIMATION_DURATION / 2);
+        assert!(halfway > 0.5 && halfway < 1.0);
+    }
+
+    /// An opened window one pixel tall on an output of the default height
+    fn opened_window() -> NotificationWindow {
+        NotificationWindow {
+            output: None,
+            notification: None,
+            layer: Layer::Top,
+            margin: (MARGIN_VERTICAL, MARGIN_HORIZONTAL),
+            stack_offset: 0,
+            opened: true,
+            max_height: MAX_HEIGHT,
+            content_height: 1,
+            scale_factor: 1.0,
+            requested_size: (1, 1),
+            resize_queued: false,
+            suppressed_resizes: 0,
+            anchored: HashSet::new(),
+        }
+    }
+
+    #[test]
+    fn test_layer_shell_timeout() {
+        config::init_default();
+        let mut state = State::default();
+        let ids = [window::Id::unique(), window::Id::unique()];
+        for id in ids {
+            let window = NotificationWindow {
+                opened: false,
+                ..opened_window()
+            };
+            state.windows.insert(id, window);
+        }
+
+        // Windows that never opened are closed, not only forgotten
+        let _ = state.update(Message::LayerShellTimeout(ids[0]));
+        assert!(state.windows.is_empty());
+        assert!(ids.iter().all(|id| state.closing.contains(id)));
+    }
+
+    #[test]
+    fn test_window_size() {
+        let mut window = NotificationWindow {
+            content_height: 0,
+            ..opened_window()
+        };
+        assert_eq!(window.size(), (WIDTH as u32, 1));
+
+        window.content_height = 900;
+        assert_eq!(window.size(), (WIDTH as u32, 900));
+
+        // A smaller output shrinks the window, which scrolls the rest
+        window.max_height = 648;
+        assert_eq!(window.size(), (WIDTH as u32, 648));
+    }
+
+    #[test]
+    fn test_capped_height() {
+        assert_eq!(capped_height(MAX_HEIGHT, 0), MAX_HEIGHT);
+        assert_eq!(capped_height(MAX_HEIGHT, 600), 600);
+        // The screen still limits a cap taller than it
+        assert_eq!(capped_height(1040, 1200), 1040);
+    }
+
+    #[tokio::test]
+    async fn test_replacement_keeps_position() {
+        config::init_default();
+        let mut state = State::default();
+        for id in 1..=3 {
+            let notification = Notification::test(id, "Summary", None);
+            let _ = state.update(Message::Dbus(DbusMessage::Notify(notification)));
+        }
+        assert_eq!(state.alerts, vec![1, 2, 3]);
+
+        let mut replacement = Notification::test(2, "Summary", None);
+        replacement.summary = "Replaced".into();
+        let _ = state.update(Message::Dbus(DbusMessage::Notify(replacement)));
+        assert_eq!(state.alerts, vec![1, 2, 3]);
+        assert_eq!(state.notifications[&2].summary, "Replaced");
+    }
+
+    #[tokio::test]
+    async fn test_fullscreen_holds() {
+        config::init_default();
+        let mut state = State::default();
+        let _ = state.update(Message::Fullscreen(true));
+
+        // Critical notifications still display over a fullscreen window
+        let mut critical = Notification::test(2, "Summary", None);
+        critical.urgency = Urgency::Critical;
+        let notification = Notification::test(1, "Summary", None);
+        let _ = state.update(Message::Dbus(DbusMessage::Notify(notification)));
+        let _ = state.update(Message::Dbus(DbusMessage::Notify(critical)));
+        assert_eq!(state.queued, vec![1]);
+        assert_eq!(state.alerts, vec![2]);
+
+        // The rest display once no window is fullscreen
+        let _ = state.update(Message::Fullscreen(false));
+        assert!(state.queued.is_empty());
+        assert_eq!(state.alerts, vec![2, 1]);
+    }
+
+    #[test]
+    fn test_unreadable_icon() {
+        config::init_default();
+        let mut state = State::default();
+        let icon = PathBuf::from("/not/an/icon.svg");
+        let mut unreadable = Notification::test(1, "Summary", None);
+        unreadable.icon = icon.clone();
+        state.notifications.insert(1, unreadable);
+
+        // Noted once when loading, and kept while the notification displays it
+        let _ = state.load_images(1);
+        state.prune_images();
+        assert!(matches!(state.images.get(&icon), Some(ImageState::Failed)));
+        assert!(state.view_icon_image(&icon).is_none());
+    }
+
+    #[test]
+    fn test_remove_saved_images() {
+        config::init_default();
+        let dir = crate::paths::image_dir();
+        std::fs::create_dir_all(&dir).expect("Unable to create the image directory");
+        // Only images saved in the image directory are removed, so the icon is made there
+        let icon = tempfile::Builder::new()
+            .suffix(".png")
+            .tempfile_in(&dir)
+            .expect("Unable to write the icon");
+        let icon = icon.path().to_path_buf();
+
+        let mut state = State::default();
+        for id in 1..=2 {
+            let mut notification = Notification::test(id, "Summary", None);
+            notification.icon = icon.clone();
+            state.alerts.push(id);
+            state.notifications.insert(id, notification);
+        }
+
+        // The icon stays while another notification displays it
+        state.remove_notification(1);
+        assert!(icon.exists());
+        state.remove_notification(2);
+        assert!(!icon.exists());
+    }
+
+    #[test]
+    fn test_idle_delays_expiry() {
+        config::init_default();
+        let mut state = State::default();
+        let now = Local::now();
+        let hour = TimeDelta::hours(1);
+        // One arrived before the session went idle an hour ago, and one during
+        let mut before = Notification::test(1, "Summary", None);
+        before.time = now - hour * 2;
+        before.expire_time = Some(now - hour / 2);
+        let mut during = Notification::test(2, "Summary", None);
+        during.time = now - hour / 2;
+        during.expire_time = Some(now - hour / 4);
+        for notification in [before, during] {
+            state.alerts.push(notification.id);
+            state.notifications.insert(notification.id, notification);
+        }
+
+        state.idle_since = Some(now - hour);
+        state.remove_expired();
+        assert_eq!(state.alerts, vec![1, 2]);
+
+        state.set_idle(None);
+        state.remove_expired();
+        assert_eq!(state.alerts, vec![1, 2]);
+        let expire_time = |id| state.notifications[&id].expire_time.unwrap();
+        assert!(expire_time(1) - (now + hour / 2) < TimeDelta::seconds(1));
+        assert!(expire_time(2) - (now + hour / 4) < TimeDelta::seconds(1));
+    }
+
+    #[test]
+    fn test_idle_from_last_input() {
+        config::init_default();
+        let now = Local::now();
+        let mut state = State::default();
+        state.clock = Clock::fixed(now);
+        let mut notification = Notification::test(1, "Summary", None);
+        notification.time = now - TimeDelta::minutes(2);
+        notification.expire_time = Some(now + TimeDelta::seconds(10));
+        state.alerts.push(notification.id);
+        state.notifications.insert(notification.id, notification);
+
+        // The session went idle at the last input, a minute before it was said to be
+        state.set_idle(Some(Duration::from_secs(60)));
+        assert_eq!(state.idle_since, Some(now - TimeDelta::minutes(1)));
+
+        state.clock.advance(TimeDelta::minutes(1));
+        state.set_idle(None);
+        assert_eq!(
+            state.notifications[&1].expire_time,
+            Some(now + TimeDelta::seconds(10) + TimeDelta::minutes(2))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dismiss_and_restore() {
+        config::init_default();
+        let mut state = State::default();
+        for id in 1..=2 {
+            let notification = Notification::test(id, "Summary", None);
+            let _ = state.update(Message::Dbus(DbusMessage::Notify(notification)));
+        }
+
+        // The oldest is dismissed first, and once it is closing, the next oldest
+        let _ = state.update(Message::DismissOldest);
+        assert!(state.removing.contains_key(&1));
+        state.finish_close(1, NotificationClosedReason::DismissedByUser);
+        assert_eq!(state.alerts, vec![2]);
+
+        // It comes back, but only once
+        let _ = state.update(Message::RestoreLastDismissed);
+        assert_eq!(state.alerts, vec![2, 1]);
+        assert!(state.notifications[&1].historical);
+        let _ = state.update(Message::RestoreLastDismissed);
+        assert_eq!(state.alerts, vec![2, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_expiry_with_clock() {
+        config::init_default();
+        let mut state = State::default();
+        let start = Local::now();
+        state.clock = Clock::fixed(start);
+        let mut expiring = Notification::test(1, "Summary", None);
+        expiring.time = start;
+        expiring.expire_time = Some(start + TimeDelta::seconds(5));
+        let _ = state.update(Message::Dbus(DbusMessage::Notify(expiring)));
+
+        state.clock.advance(TimeDelta::seconds(4));
+        state.remove_expired();
+        assert!(!state.removing.contains_key(&1));
+
+        // Hovering holds it past its expiry, and leaving gives back the time hovered
+        let _ = state.update(Message::PointerEntered(1));
+        state.clock.advance(TimeDelta::seconds(10));
+        state.remove_expired();
+        assert!(!state.removing.contains_key(&1));
+        let _ = state.update(Message::PointerExited(1));
+        assert_eq!(
+            state.notifications[&1].expire_time,
+            Some(start + TimeDelta::seconds(15))
+        );
+
+        state.clock.advance(TimeDelta::seconds(2));
+        state.remove_expired();
+        assert!(state.removing.contains_key(&1));
+    }
+
+    #[tokio::test]
+    async fn test_drag_to_dismiss() {
+        config::init_default();
+        let mut state = State::default();
+        for id in 1..=2 {
+            let notification = Notification::test(id, "Summary", None);
+            let _ = state.update(Message::Dbus(DbusMessage::Notify(notification)));
+        }
+        let drag = |state: &mut State, id, positions: &[f32]| {
+            let _ = state.update(Message::DragStarted(id));
+            for &x in positions {
+                let _ = state.update(Message::DragMoved(x));
+            }
+        };
+
+        // Barely moving while pressed is still a click
+        drag(&mut state, 1, &[200.0, 204.0]);
+        assert!(!state.end_drag());
+
+        // A short drag slides back
+        drag(&mut state, 1, &[200.0, 300.0]);
+        assert_eq!(state.drag_offset(1, Instant::now()), 100.0);
+        let _ = state.update(Message::DragEnded);
+        assert!(!state.removing.contains_key(&1));
+        assert!(state.released.contains_key(&1));
+        let later = Instant::now() + ANIMATION_DURATION;
+        assert_eq!(state.drag_offset(1, later), 0.0);
+
+        // A long one dismisses it, and it stays where it was let go of as it fades out
+        drag(&mut state, 2, &[400.0, 150.0]);
+        assert!(state.end_drag());
+        assert!(state.removing.contains_key(&2));
+        assert_eq!(state.drag_offset(2, later), -250.0);
+    }
+
+    /// Send a DBUS message asking for an answer, and take the answer
+    fn ask<T>(state: &mut State, message: fn(Reply<T>) -> DbusMessage) -> Option<T> {
+        let (reply, mut answer) = Reply::new();
+        let _ = state.update(Message::Dbus(message(reply)));
+        answer.try_recv().ok().flatten()
+    }
+
+    #[tokio::test]
+    async fn test_control_methods() {
+        config::init_default();
+        let mut state = State::default();
+        for id in 1..=3 {
+            let notification = Notification::test(id, "Summary", None);
+            let _ = state.update(Message::Dbus(DbusMessage::Notify(notification)));
+        }
+
+        assert_eq!(ask(&mut state, DbusMessage::DismissLatest), Some(Some(3)));
+        assert_eq!(ask(&mut state, DbusMessage::DismissOldest), Some(Some(1)));
+        // Those closing aren't dismissed again
+        assert_eq!(ask(&mut state, DbusMessage::DismissAll), Some(vec![2]));
+        assert_eq!(ask(&mut state, DbusMessage::DismissLatest), Some(None));
+
+        state.finish_close(2, NotificationClosedReason::DismissedByUser);
+        assert_eq!(
+            ask(&mut state, DbusMessage::ShowLatestFromHistory),
+            Some(Some(2))
+        );
+        assert!(state.alerts.contains(&2));
+    }
+
+    #[tokio::test]
+    async fn test_window_lifecycle() {
+        config::init_default();
+        let mut state = State::default();
+
+        // A notification opens a window
+        let notification = Notification::test(1, "Summary", None);
+        let _ = state.update(Message::Dbus(DbusMessage::Notify(notification)));
+        let first = *state.windows.keys().next().expect("No window created");
+
+        // Closing the last notification removes the window, which closes later
+        state.finish_close(1, NotificationClosedReason::Expired);
+        let _ = state.update(Message::Tick);
+        assert!(state.windows.is_empty());
+        assert!(state.closing.contains(&first));
+
+        // A notification arriving before the window has closed gets a new window
+        let notification = Notification::test(2, "Summary", None);
+        let _ = state.update(Message::Dbus(DbusMessage::Notify(notification)));
+        let second = *state
+            .windows
+            .keys()
+            .next()
+            .expect("No window for the new notification");
+        assert_ne!(first, second);
+
+        // The old window closing leaves the new one be
+        let _ = state.update(Message::WindowClosed(first));
+        assert!(state.closing.is_empty());
+        assert_eq!(state.windows.keys().collect::<Vec<_>>(), vec![&second]);
+
+        // The compositor closing the new window while it has notifications replaces it
+        let _ = state.update(Message::WindowClosed(second));
+        assert_eq!(state.windows.len(), 1);
+        assert!(!state.windows.contains_key(&second));
+    }
+
+    #[test]
+    fn test_estimated_card_height() {
+        config::init_default();
+        let short = Notification::test(1, "Summary", None);
+        let long = Notification {
+            body: Some(std::sync::Arc::new(crate::markup::markup(
+                "A long line of body text ".repeat(10),
+                1,
+            ))),
+            actions: Some(vec![("default".into(), "Open".into())]),
+            ..Notification::test(2, "Summary", None)
+        };
+
+        let compact = estimated_card_height(&short, LayoutKind::Compact);
+        let full = estimated_card_height(&short, LayoutKind::Full);
+        let longer = estimated_card_height(&long, LayoutKind::Full);
+        assert!(compact < full);
+        assert!(full < longer);
+        assert!(longer < MAX_HEIGHT as f32);
+    }
+
+    #[test]
+    fn test_stack_offsets() {
+        assert_eq!(stack_offsets(&[100, 50, 200]), vec![0, 110, 170]);
+        assert_eq!(stack_offsets(&[]), Vec::<i32>::new());
+    }
+
+    #[test]
+    fn test_margins() {
+        assert_eq!(margins(Position::TopRight, 60, 20), (60, 20, 0, 0));
+        assert_eq!(margins(Position::TopLeft, 60, 20), (60, 0, 0, 20));
+        assert_eq!(margins(Position::BottomRight, 60, 20), (0, 20, 60, 0));
+        assert_eq!(margins(Position::BottomLeft, 60, 20), (0, 0, 60, 20));
+    }
+
+    #[test]
+    fn test_window_resize() {
+        let mut window = opened_window();
+        assert_eq!(window.resize(), Some((WIDTH as u32, 1)));
+        assert_eq!(window.resize(), None);
+
+        // Only the last of several content heights is requested
+        window.content_height = 100;
+        window.content_height = 200;
+        assert_eq!(window.resize(), Some((WIDTH as u32, 200)));
+        assert_eq!(window.resize(), None);
+
+        // Content past the maximum height doesn't resize a window already at it
+        window.content_height = 5000;
+        assert_eq!(window.resize(), Some((WIDTH as u32, MAX_HEIGHT)));
+        window.content_height = 6000;
+        assert_eq!(window.resize(), None);
+
+        // A burst of content changes queues one resize, to the last height, counting the rest
+        config::init_default();
+        let mut state = State::default();
+        let id = window::Id::unique();
+        state.windows.insert(id, opened_window());
+        for height in [100, 150, 200] {
+            let _ = state.update(Message::ContainerResized(id, height));
+        }
+        assert!(state.windows[&id].resize_queued);
+        assert_eq!(state.windows[&id].suppressed_resizes, 2);
+        assert_eq!(state.run_resize(id), Some((WIDTH as u32, 200)));
+        assert!(!state.windows[&id].resize_queued);
+        assert_eq!(state.windows[&id].suppressed_resizes, 0);
+
+        // A resize queued for a size already requested changes nothing
+        let _ = state.update(Message::ContainerResized(id, 200));
+        assert_eq!(state.run_resize(id), None);
+        assert_eq!(state.windows[&id].suppressed_resizes, 1);
     }
 }
@@ -0,0 +1,225 @@
+use std::time::{Duration, Instant};
+
+use iced::futures::channel::mpsc;
+use iced::futures::{stream, SinkExt, Stream, StreamExt};
+use log::{debug, warn};
+use wayland_client::globals::GlobalListContents;
+use wayland_client::protocol::wl_registry::{self, WlRegistry};
+use wayland_client::protocol::wl_seat::{self, WlSeat};
+use wayland_client::{Connection, Dispatch, QueueHandle};
+use wayland_protocols::ext::idle_notify::v1::client::ext_idle_notification_v1::{
+    self, ExtIdleNotificationV1,
+};
+use wayland_protocols::ext::idle_notify::v1::client::ext_idle_notifier_v1::{
+    self, ExtIdleNotifierV1,
+};
+use zbus::proxy;
+
+use crate::config::config;
+use crate::wayland;
+
+#[proxy(
+    interface = "org.freedesktop.ScreenSaver",
+    default_service = "org.freedesktop.ScreenSaver",
+    default_path = "/org/freedesktop/ScreenSaver"
+)]
+trait ScreenSaver {
+    #[zbus(signal)]
+    fn active_changed(&self, active: bool) -> zbus::Result<()>;
+}
+
+/// What says whether the session is idle
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Source {
+    /// The screensaver, which is active while the screen is locked or blanked
+    ScreenSaver,
+    /// The compositor, after the idle timeout without input
+    Wayland,
+}
+
+/// When each source says the session went idle, if it is
+#[derive(Default)]
+struct Sources {
+    screensaver: Option<Instant>,
+    wayland: Option<Instant>,
+}
+
+impl Sources {
+    /// Note when a source says the session went idle, or that it isn't, returning the earliest
+    /// time any still idle says it went idle
+    fn update(&mut self, source: Source, since: Option<Instant>) -> Option<Instant> {
+        match source {
+            Source::ScreenSaver => self.screensaver = since,
+            Source::Wayland => self.wayland = since,
+        }
+        self.screensaver.into_iter().chain(self.wayland).min()
+    }
+}
+
+/// How long ago the session went idle or locked, or `None` once it isn't, sent each time that
+/// changes. The compositor only says the session is idle after the idle timeout, so it went idle
+/// at the last input, that long before. Sources missing from the session, like a compositor
+/// without ext-idle-notify, never say it is idle
+pub fn idle() -> impl Stream<Item = Option<Duration>> {
+    iced::stream::channel(10, async move |mut output| {
+        let (sender, receiver) = mpsc::unbounded();
+        tokio::task::spawn(watch_screensaver(sender));
+        let mut changes = std::pin::pin!(stream::select(receiver, wayland::watch(watch_wayland)));
+
+        let mut sources = Sources::default();
+        let mut reported = false;
+        while let Some((source, since)) = changes.next().await {
+            let since = sources.update(source, since);
+            if since.is_some() != reported {
+                debug!("Idle changed to {}", since.is_some());
+                reported = since.is_some();
+                let _ = output.send(since.map(|since| since.elapsed())).await;
+            }
+        }
+    })
+}
+
+/// The screensaver's changes between active and inactive, if there is one
+async fn screensaver_changes() -> zbus::Result<ActiveChangedStream> {
+    let connection = zbus::Connection::session().await?;
+    ScreenSaverProxy::new(&connection)
+        .await?
+        .receive_active_changed()
+        .await
+}
+
+async fn watch_screensaver(sender: mpsc::UnboundedSender<(Source, Option<Instant>)>) {
+    let mut changes = match screensaver_changes().await {
+        Ok(changes) => changes,
+        Err(err) => {
+            debug!("Not watching the screensaver with error {err}");
+            return;
+        }
+    };
+
+    while let Some(change) = changes.next().await {
+        match change.args() {
+            Ok(args) => {
+                let since = args.active().then(Instant::now);
+                let _ = sender.unbounded_send((Source::ScreenSaver, since));
+            }
+            Err(err) => warn!("Invalid screensaver ActiveChanged signal with error {err}"),
+        }
+    }
+}
+
+fn watch_wayland(sender: mpsc::UnboundedSender<(Source, Option<Instant>)>) {
+    let Some((globals, queue)) = wayland::connect::<IdleWatcher>("idle") else {
+        return;
+    };
+    let handle = queue.handle();
+    let notifier = match globals.bind::<ExtIdleNotifierV1, _, _>(&handle, 1..=1, ()) {
+        Ok(notifier) => notifier,
+        Err(err) => {
+            debug!("Not watching for idle, the compositor lacks the protocol: {err}");
+            return;
+        }
+    };
+    let seat = match globals.bind::<WlSeat, _, _>(&handle, 1..=1, ()) {
+        Ok(seat) => seat,
+        Err(err) => {
+            warn!("Unable to get the seat to watch for idle with error {err}");
+            return;
+        }
+    };
+    let timeout = Duration::from_secs(config().idle_timeout.max(1));
+    let milliseconds = timeout.as_millis().try_into().unwrap_or(u32::MAX);
+    let _notification = notifier.get_idle_notification(milliseconds, &seat, &handle, ());
+
+    let mut watcher = IdleWatcher { sender, timeout };
+    wayland::dispatch(queue, &mut watcher, "idle", |watcher| {
+        watcher.sender.is_closed()
+    });
+}
+
+struct IdleWatcher {
+    sender: mpsc::UnboundedSender<(Source, Option<Instant>)>,
+    /// The time without input before the compositor says the session is idle
+    timeout: Duration,
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for IdleWatcher {
+    fn event(
+        _: &mut Self,
+        _: &WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WlSeat, ()> for IdleWatcher {
+    fn event(
+        _: &mut Self,
+        _: &WlSeat,
+        _: wl_seat::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ExtIdleNotifierV1, ()> for IdleWatcher {
+    fn event(
+        _: &mut Self,
+        _: &ExtIdleNotifierV1,
+        _: ext_idle_notifier_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ExtIdleNotificationV1, ()> for IdleWatcher {
+    fn event(
+        watcher: &mut Self,
+        _: &ExtIdleNotificationV1,
+        event: ext_idle_notification_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let since = match event {
+            ext_idle_notification_v1::Event::Idled => Some(
+                Instant::now()
+                    .checked_sub(watcher.timeout)
+                    .unwrap_or_else(Instant::now),
+            ),
+            ext_idle_notification_v1::Event::Resumed => None,
+            _ => return,
+        };
+        let _ = watcher.sender.unbounded_send((Source::Wayland, since));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sources() {
+        let mut sources = Sources::default();
+        let last_input = Instant::now();
+        let locked = last_input + Duration::from_secs(60);
+        assert_eq!(
+            sources.update(Source::Wayland, Some(last_input)),
+            Some(last_input)
+        );
+        // Locking while idle, then input while still locked
+        assert_eq!(
+            sources.update(Source::ScreenSaver, Some(locked)),
+            Some(last_input)
+        );
+        assert_eq!(sources.update(Source::Wayland, None), Some(locked));
+        assert_eq!(sources.update(Source::ScreenSaver, None), None);
+    }
+}
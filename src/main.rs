@@ -1,14 +1,32 @@
+use std::io::Write;
+
 use chrono::Local;
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use derive_more::Debug;
 use log::{debug, info};
 
+use crate::rotating_file::RotatingFile;
+
+mod appearance;
+mod body;
+mod clock;
+mod config;
 mod dbus;
 mod freedesktop;
+mod fullscreen;
+mod history;
+mod hooks;
 mod iced;
+mod idle;
 mod markup;
 mod measuring_container;
 mod notification;
+mod outputs;
+mod paths;
+mod process;
+mod rotating_file;
+mod sliding_container;
+mod wayland;
 
 /// A notification server using Eww to display notifications
 #[derive(Parser, Debug)]
@@ -17,17 +35,25 @@ struct Args {
     /// Log level: can be Off, Error, Warn, Info, Debug, or Trace
     #[arg(long, default_value_t = log::LevelFilter::Debug)]
     log: log::LevelFilter,
+    /// Where to log to
+    #[arg(long, value_enum, default_value_t = LogTarget::Both)]
+    log_target: LogTarget,
+    /// Maximum size in MiB of the log file before it is rotated
+    #[arg(long, default_value_t = 10)]
+    log_max_size: u64,
+    #[command(flatten)]
+    config: config::Config,
 }
 
-fn setup_logger(log_level: log::LevelFilter) -> Result<(), fern::InitError> {
-    // Log to stderr and ~/.local/state/baelyks-notification-server.log
-    let log_path = dirs::home_dir()
-        .expect("Unable to get the home dir")
-        .join(".local/state/")
-        .join(env!("CARGO_PKG_NAME"))
-        .with_extension("log");
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+enum LogTarget {
+    Stderr,
+    File,
+    Both,
+}
 
-    fern::Dispatch::new()
+fn setup_logger(args: &Args) -> Result<(), fern::InitError> {
+    let mut dispatch = fern::Dispatch::new()
         .filter(|metadata| {
             metadata
                 .target()
@@ -41,9 +67,18 @@ fn setup_logger(log_level: log::LevelFilter) -> Result<(), fern::InitError> {
                 message
             ))
         })
-        .level(log_level)
-        .chain(std::io::stderr())
-        .chain(
+        .level(args.log);
+
+    if args.log_target != LogTarget::File {
+        dispatch = dispatch.chain(std::io::stderr());
+    }
+
+    if args.log_target != LogTarget::Stderr {
+        // Log to ~/.local/state/baelyks-notification-daemon.log, or in $XDG_STATE_HOME
+        let log_path = paths::state_file("log");
+        let log_file = RotatingFile::open(log_path, args.log_max_size * 1024 * 1024)?;
+
+        dispatch = dispatch.chain(
             fern::Dispatch::new()
                 .format(|out, message, _| {
                     out.finish(format_args!(
@@ -52,15 +87,17 @@ fn setup_logger(log_level: log::LevelFilter) -> Result<(), fern::InitError> {
                         message
                     ))
                 })
-                .chain(fern::log_file(&log_path)?),
-        )
-        .apply()?;
+                .chain(Box::new(log_file) as Box<dyn Write + Send>),
+        );
+    }
+
+    dispatch.apply()?;
 
     info!(
         "Starting {} v{} with log level: {}",
         env!("CARGO_PKG_NAME"),
         env!("CARGO_PKG_VERSION"),
-        log_level
+        args.log
     );
 
     Ok(())
@@ -69,9 +106,10 @@ fn setup_logger(log_level: log::LevelFilter) -> Result<(), fern::InitError> {
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
 
-    setup_logger(args.log)?;
+    setup_logger(&args)?;
 
     debug!("Command line arguments: {:#?}", args);
+    config::init(args.config);
     iced::run()?;
 
     Ok(())
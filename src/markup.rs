@@ -3,20 +3,31 @@ use std::path::PathBuf;
 use log::warn;
 use nom::{
     branch::{alt, permutation},
-    bytes::{tag, take_until, take_until1},
+    bytes::{tag, take_till1, take_until, take_until1},
     character::complete::multispace0,
     combinator::eof,
     error::ParseError,
-    multi::many_till,
-    sequence::{delimited, pair, separated_pair, terminated},
+    multi::{many0, many_till},
+    sequence::{delimited, separated_pair, terminated},
     IResult, Parser,
 };
 
+/// How deeply elements can be nested before the rest is shown as text, so adversarial bodies
+/// can't overflow the stack
+const MAX_DEPTH: usize = 32;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum MarkupTag {
     Bold(Vec<MarkupTag>),
     Italic(Vec<MarkupTag>),
     Underline(Vec<MarkupTag>),
+    Monospace(Vec<MarkupTag>),
+    Strikethrough(Vec<MarkupTag>),
+    Span {
+        foreground: Option<[u8; 3]>,
+        background: Option<[u8; 3]>,
+        children: Vec<MarkupTag>,
+    },
     Hyperlink {
         href: String,
         children: Vec<MarkupTag>,
@@ -50,7 +61,7 @@ fn parse_markup_image(input: &str) -> IResult<&str, MarkupTag> {
         permutation((parse_markup_tag_field("src"), parse_markup_tag_field("alt"))),
         tag("/>"),
     )
-    .parse(input)?;
+    .parse_complete(input)?;
     Ok((
         remainder,
         MarkupTag::Image {
@@ -60,14 +71,47 @@ fn parse_markup_image(input: &str) -> IResult<&str, MarkupTag> {
     ))
 }
 
-fn parse_markup_hyperlink(input: &str) -> IResult<&str, MarkupTag> {
-    let (remainder, (href, contents)) = pair(
-        delimited(tag("<a"), parse_markup_tag_field("href"), tag(">")),
-        terminated(take_until("</a>"), tag("</a>")),
-    )
-    .parse(input)?;
+/// Whether the rest of a tag after a name ends the name, so that `<b>` and `<b class="x">` are
+/// `b` tags, but `<br>` isn't
+fn ends_tag_name(rest: &str) -> bool {
+    rest.starts_with(|c: char| c == '>' || c.is_whitespace())
+}
+
+/// Take the contents of a `name` element up to its closing tag, skipping over nested elements with
+/// the same name, and consume the closing tag
+fn parse_element_contents<'a>(input: &'a str, name: &str) -> IResult<&'a str, &'a str> {
+    let mut depth = 0;
+    let mut offset = 0;
+    while let Some(start) = input[offset..].find('<').map(|i| offset + i) {
+        let rest = &input[start + 1..];
+        if let Some(rest) = rest
+            .strip_prefix('/')
+            .and_then(|rest| rest.strip_prefix(name))
+            .filter(|rest| ends_tag_name(rest))
+        {
+            if depth == 0 {
+                let (remainder, _) = terminated(take_until(">"), tag(">")).parse_complete(rest)?;
+                return Ok((remainder, &input[..start]));
+            }
+            depth -= 1;
+        } else if rest.strip_prefix(name).is_some_and(ends_tag_name) {
+            depth += 1;
+        }
+        offset = start + 1;
+    }
 
-    let (_, children) = parse_markup(contents)?;
+    Err(nom::Err::Error(nom::error::Error::new(
+        input,
+        nom::error::ErrorKind::TakeUntil,
+    )))
+}
+
+fn parse_markup_hyperlink(input: &str, depth: usize) -> IResult<&str, MarkupTag> {
+    let (remainder, href) =
+        delimited(tag("<a"), parse_markup_tag_field("href"), tag(">")).parse_complete(input)?;
+    let (remainder, contents) = parse_element_contents(remainder, "a")?;
+
+    let (_, children) = parse_markup_nested(contents, depth + 1)?;
 
     Ok((
         remainder,
@@ -78,87 +122,229 @@ fn parse_markup_hyperlink(input: &str) -> IResult<&str, MarkupTag> {
     ))
 }
 
-fn parse_markup_tag(input: &str) -> IResult<&str, MarkupTag> {
-    let (remainder, tag_name) = delimited(tag("<"), take_until1(">"), tag(">")).parse(input)?;
-    let end_tag = format!("</{}", tag_name);
-    let (remainder, contents) = take_until(end_tag.as_str()).parse(remainder)?;
-    let (remainder, _) = delimited(tag("</"), take_until(">"), tag(">")).parse(remainder)?;
+enum SpanAttribute<'a> {
+    Foreground(&'a str),
+    Background(&'a str),
+    Unknown,
+}
+
+fn parse_markup_span_attribute(input: &str) -> IResult<&str, SpanAttribute> {
+    alt((
+        alt((
+            parse_markup_tag_field("foreground"),
+            parse_markup_tag_field("fgcolor"),
+            parse_markup_tag_field("color"),
+        ))
+        .map(SpanAttribute::Foreground),
+        alt((
+            parse_markup_tag_field("background"),
+            parse_markup_tag_field("bgcolor"),
+        ))
+        .map(SpanAttribute::Background),
+        // Skip unsupported attributes, like the font weight
+        ws(separated_pair(
+            take_till1(|c: char| c == '=' || c == '>' || c.is_whitespace()),
+            ws(tag("=")),
+            delimited(tag("\""), take_until("\""), tag("\"")),
+        ))
+        .map(|_| SpanAttribute::Unknown),
+    ))
+    .parse_complete(input)
+}
 
-    let (_, children) = parse_markup(contents)?;
+fn parse_markup_span(input: &str, depth: usize) -> IResult<&str, MarkupTag> {
+    let (remainder, attributes) =
+        delimited(tag("<span"), many0(parse_markup_span_attribute), tag(">"))
+            .parse_complete(input)?;
+    let (remainder, contents) = parse_element_contents(remainder, "span")?;
 
-    let markup_tag = match tag_name.chars().next().unwrap() {
-        'b' => MarkupTag::Bold(children),
-        'u' => MarkupTag::Underline(children),
-        'i' => MarkupTag::Italic(children),
+    let (_, children) = parse_markup_nested(contents, depth + 1)?;
+
+    let mut foreground = None;
+    let mut background = None;
+    for attribute in attributes {
+        match attribute {
+            SpanAttribute::Foreground(color) => foreground = parse_color(color),
+            SpanAttribute::Background(color) => background = parse_color(color),
+            SpanAttribute::Unknown => {}
+        }
+    }
+
+    Ok((
+        remainder,
+        MarkupTag::Span {
+            foreground,
+            background,
+            children,
+        },
+    ))
+}
+
+/// Parse a `#rgb`, `#rrggbb`, or `#rrrrggggbbbb` hex color, or one of a few common color names
+pub fn parse_color(color: &str) -> Option<[u8; 3]> {
+    if let Some(hex) = color.strip_prefix('#') {
+        let digits = hex.len() / 3;
+        if hex.len() % 3 != 0 || !(1..=4).contains(&digits) || !hex.is_ascii() {
+            warn!("Invalid color {}", color);
+            return None;
+        }
+
+        let max = (1 << (4 * digits)) - 1;
+        let mut rgb = [0; 3];
+        for (i, channel) in rgb.iter_mut().enumerate() {
+            let value = u32::from_str_radix(&hex[i * digits..(i + 1) * digits], 16).ok()?;
+            *channel = (value * 255 / max) as u8;
+        }
+        return Some(rgb);
+    }
+
+    match color.to_lowercase().as_str() {
+        "black" => Some([0, 0, 0]),
+        "white" => Some([255, 255, 255]),
+        "gray" | "grey" => Some([128, 128, 128]),
+        "red" => Some([255, 0, 0]),
+        "orange" => Some([255, 165, 0]),
+        "yellow" => Some([255, 255, 0]),
+        "green" => Some([0, 128, 0]),
+        "blue" => Some([0, 0, 255]),
+        "purple" => Some([128, 0, 128]),
         _ => {
-            warn!("Unknown tag name {}", tag_name);
-            MarkupTag::Text(contents.into())
+            warn!("Unknown color {}", color);
+            None
         }
+    }
+}
+
+/// Parse a simple element, like `<b>`. An element that is never closed fails to parse, so that its
+/// open tag is kept as text and the markup after it is still parsed
+fn parse_markup_tag(input: &str, depth: usize) -> IResult<&str, MarkupTag> {
+    let (remainder, tag_name) =
+        delimited(tag("<"), take_until1(">"), tag(">")).parse_complete(input)?;
+    // Ignore attributes, like the language in `<code class="rust">`
+    let Some(tag_name) = tag_name.split_whitespace().next() else {
+        return Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Tag,
+        )));
+    };
+    let (remainder, contents) = parse_element_contents(remainder, tag_name)?;
+
+    let (_, children) = parse_markup_nested(contents, depth + 1)?;
+
+    let markup_tag = match tag_name {
+        "code" | "tt" => MarkupTag::Monospace(children),
+        "s" | "del" => MarkupTag::Strikethrough(children),
+        _ => match tag_name.chars().next() {
+            Some('b') => MarkupTag::Bold(children),
+            Some('u') => MarkupTag::Underline(children),
+            Some('i') => MarkupTag::Italic(children),
+            _ => {
+                warn!("Unknown tag name {}", tag_name);
+                MarkupTag::Text(contents.into())
+            }
+        },
     };
 
     Ok((remainder, markup_tag))
 }
 
 fn parse_markup_text(input: &str) -> IResult<&str, MarkupTag> {
-    let (rem, text) = take_until::<&str, &str, nom::error::Error<&str>>("<")
-        .parse(input)
-        .unwrap_or(("", input));
-    let text = MarkupTag::Text(text.to_string());
+    // A `<` that doesn't start a valid element, like a stray `<` or an unclosed tag, is just text
+    let start = usize::from(input.starts_with('<'));
+    let end = input[start..]
+        .find('<')
+        .map_or(input.len(), |end| start + end);
+    let text = MarkupTag::Text(input[..end].to_string());
 
-    Ok((rem, text))
+    Ok((&input[end..], text))
 }
 
 fn parse_markup(input: &str) -> IResult<&str, Vec<MarkupTag>> {
+    parse_markup_nested(input, 0)
+}
+
+fn parse_markup_nested(input: &str, depth: usize) -> IResult<&str, Vec<MarkupTag>> {
+    if depth > MAX_DEPTH {
+        warn!(
+            "Markup nested more than {} deep, showing the rest as text",
+            MAX_DEPTH
+        );
+        return Ok(("", vec![MarkupTag::Text(input.into())]));
+    }
+
     let (rem, (tags, _)) = many_till(
         alt((
             parse_markup_image,
-            parse_markup_hyperlink,
-            parse_markup_tag,
+            |input| parse_markup_hyperlink(input, depth),
+            |input| parse_markup_span(input, depth),
+            |input| parse_markup_tag(input, depth),
             parse_markup_text,
         )),
         eof,
     )
-    .parse(input)?;
-    assert!(rem.is_empty());
-    Ok((rem, tags))
+    .parse_complete(input)?;
+
+    // Join the text split up by stray `<`s back together
+    let mut merged: Vec<MarkupTag> = Vec::with_capacity(tags.len());
+    for markup_tag in tags {
+        match (merged.last_mut(), markup_tag) {
+            (Some(MarkupTag::Text(text)), MarkupTag::Text(next)) => text.push_str(&next),
+            (_, markup_tag) => merged.push(markup_tag),
+        }
+    }
+
+    Ok((rem, merged))
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
 pub struct RichTextSpanStyle {
     pub bold: bool,
     pub italic: bool,
     pub underline: bool,
+    pub monospace: bool,
+    pub strikethrough: bool,
+    pub foreground: Option<[u8; 3]>,
+    pub background: Option<[u8; 3]>,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct RichTextSpan {
     pub style: RichTextSpanStyle,
     pub text: String,
+    /// The href of the hyperlink this span is part of, if any
+    pub link: Option<String>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 enum UngroupedBodyElement {
     Span(RichTextSpan),
     Image { src: PathBuf, alt: String },
+    LineBreak,
 }
 
 fn flatten(tree: Vec<MarkupTag>) -> Vec<UngroupedBodyElement> {
-    flatten_traverser(
-        tree,
-        RichTextSpanStyle {
-            bold: false,
-            italic: false,
-            underline: false,
-        },
-    )
+    flatten_traverser(tree, RichTextSpanStyle::default())
 }
 
 fn flatten_traverser(tree: Vec<MarkupTag>, style: RichTextSpanStyle) -> Vec<UngroupedBodyElement> {
     tree.into_iter()
         .flat_map(|tag| match tag {
-            MarkupTag::Text(text) => {
-                vec![UngroupedBodyElement::Span(RichTextSpan { style, text })]
-            }
+            MarkupTag::Text(text) => text
+                .replace("\r\n", "\n")
+                .split('\n')
+                .enumerate()
+                .flat_map(|(i, line)| {
+                    let line_break = (i > 0).then_some(UngroupedBodyElement::LineBreak);
+                    let span = (!line.is_empty()).then(|| {
+                        UngroupedBodyElement::Span(RichTextSpan {
+                            style,
+                            text: line.to_string(),
+                            link: None,
+                        })
+                    });
+                    line_break.into_iter().chain(span)
+                })
+                .collect(),
             MarkupTag::Bold(children) => flatten_traverser(
                 children,
                 RichTextSpanStyle {
@@ -180,67 +366,114 @@ fn flatten_traverser(tree: Vec<MarkupTag>, style: RichTextSpanStyle) -> Vec<Ungr
                     ..style
                 },
             ),
-            MarkupTag::Hyperlink { href: _, children } => {
-                flatten_traverser(children, RichTextSpanStyle { ..style })
-            }
+            MarkupTag::Monospace(children) => flatten_traverser(
+                children,
+                RichTextSpanStyle {
+                    monospace: true,
+                    ..style
+                },
+            ),
+            MarkupTag::Strikethrough(children) => flatten_traverser(
+                children,
+                RichTextSpanStyle {
+                    strikethrough: true,
+                    ..style
+                },
+            ),
+            MarkupTag::Span {
+                foreground,
+                background,
+                children,
+            } => flatten_traverser(
+                children,
+                RichTextSpanStyle {
+                    foreground: foreground.or(style.foreground),
+                    background: background.or(style.background),
+                    ..style
+                },
+            ),
+            MarkupTag::Hyperlink { href, children } => flatten_traverser(children, style)
+                .into_iter()
+                .map(|element| match element {
+                    // Keep the innermost link when hyperlinks are nested
+                    UngroupedBodyElement::Span(span) => UngroupedBodyElement::Span(RichTextSpan {
+                        link: span.link.or_else(|| Some(href.clone())),
+                        ..span
+                    }),
+                    element => element,
+                })
+                .collect(),
             MarkupTag::Image { src, alt } => vec![UngroupedBodyElement::Image { src, alt }],
         })
         .collect()
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum BodyElement {
     RichText(Vec<RichTextSpan>),
     Image { src: PathBuf, alt: String },
 }
 
-fn group(elements: Vec<UngroupedBodyElement>) -> Vec<BodyElement> {
+/// Group the elements into lines of rich text and images, with at most `max_blank_lines`
+/// consecutive blank lines between lines, and no leading or trailing blank lines
+fn group(elements: Vec<UngroupedBodyElement>, max_blank_lines: usize) -> Vec<BodyElement> {
     let mut grouped = vec![];
-    let mut elements = elements.into_iter();
-    let mut group = vec![];
-
-    loop {
-        match elements.next() {
-            Some(UngroupedBodyElement::Span(span)) => group.push(span),
-            Some(UngroupedBodyElement::Image { src, alt }) => {
-                if !group.is_empty() {
-                    grouped.push(BodyElement::RichText(group));
-                }
-                group = vec![];
-                grouped.push(BodyElement::Image {
-                    src: src.clone(),
-                    alt: alt.clone(),
-                });
-            }
-            None => {
-                if !group.is_empty() {
-                    grouped.push(BodyElement::RichText(group));
+    let mut blank_lines = 0;
+
+    for line in elements.split(|element| *element == UngroupedBodyElement::LineBreak) {
+        if line.is_empty() {
+            blank_lines += 1;
+            continue;
+        }
+
+        if !grouped.is_empty() {
+            grouped.extend(
+                std::iter::repeat_with(|| BodyElement::RichText(vec![]))
+                    .take(blank_lines.min(max_blank_lines)),
+            );
+        }
+        blank_lines = 0;
+
+        let mut group = vec![];
+        for element in line {
+            match element {
+                UngroupedBodyElement::Span(span) => group.push(span.clone()),
+                UngroupedBodyElement::Image { src, alt } => {
+                    if !group.is_empty() {
+                        grouped.push(BodyElement::RichText(group));
+                    }
+                    group = vec![];
+                    grouped.push(BodyElement::Image {
+                        src: src.clone(),
+                        alt: alt.clone(),
+                    });
                 }
-                break;
+                UngroupedBodyElement::LineBreak => unreachable!(),
             }
         }
+        if !group.is_empty() {
+            grouped.push(BodyElement::RichText(group));
+        }
     }
 
     grouped
 }
 
-pub fn markup(text: String) -> Vec<BodyElement> {
+pub fn markup(text: String, max_blank_lines: usize) -> Vec<BodyElement> {
     match parse_markup(&text) {
-        Ok((_, parsed)) => group(flatten(parsed)),
+        Ok((_, parsed)) => group(flatten(parsed), max_blank_lines),
         Err(error) => {
             warn!("Error parsing body: {error}");
-            vec![BodyElement::RichText(vec![RichTextSpan {
-                style: RichTextSpanStyle {
-                    bold: false,
-                    italic: false,
-                    underline: false,
-                },
-                text,
-            }])]
+            plain(text, max_blank_lines)
         }
     }
 }
 
+/// Wrap text as unstyled lines, without parsing any markup
+pub fn plain(text: String, max_blank_lines: usize) -> Vec<BodyElement> {
+    group(flatten(vec![MarkupTag::Text(text)]), max_blank_lines)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -293,29 +526,32 @@ mod test {
                     style: RichTextSpanStyle {
                         bold: true,
                         italic: false,
-                        underline: false,
+                        ..Default::default()
                     },
                     text: "Some ".into(),
+                    link: None,
                 }),
                 UngroupedBodyElement::Span(RichTextSpan {
                     style: RichTextSpanStyle {
                         bold: true,
                         italic: true,
-                        underline: false,
+                        ..Default::default()
                     },
                     text: "bold and italic".into(),
+                    link: None,
                 }),
                 UngroupedBodyElement::Span(RichTextSpan {
                     style: RichTextSpanStyle {
                         bold: true,
                         italic: false,
-                        underline: false,
+                        ..Default::default()
                     },
                     text: " text".into(),
+                    link: None,
                 })
             ]
         );
-        let grouped = group(flattened);
+        let grouped = group(flattened, 1);
         assert_eq!(
             grouped,
             vec![BodyElement::RichText(vec![
@@ -323,30 +559,158 @@ mod test {
                     style: RichTextSpanStyle {
                         bold: true,
                         italic: false,
-                        underline: false,
+                        ..Default::default()
                     },
                     text: "Some ".into(),
+                    link: None,
                 },
                 RichTextSpan {
                     style: RichTextSpanStyle {
                         bold: true,
                         italic: true,
-                        underline: false,
+                        ..Default::default()
                     },
                     text: "bold and italic".into(),
+                    link: None,
                 },
                 RichTextSpan {
                     style: RichTextSpanStyle {
                         bold: true,
                         italic: false,
-                        underline: false,
+                        ..Default::default()
                     },
                     text: " text".into(),
+                    link: None,
                 }
             ])]
         );
     }
 
+    #[test]
+    fn test_nested_same_tag() {
+        let text = r#"<b>1<b>2</b>3</b><span color="red"><span>4</span></span>"#;
+        let (_, parsed) = parse_markup(&text).unwrap();
+        assert_eq!(
+            parsed,
+            vec![
+                MarkupTag::Bold(vec![
+                    MarkupTag::Text("1".into()),
+                    MarkupTag::Bold(vec![MarkupTag::Text("2".into())]),
+                    MarkupTag::Text("3".into()),
+                ]),
+                MarkupTag::Span {
+                    foreground: Some([255, 0, 0]),
+                    background: None,
+                    children: vec![MarkupTag::Span {
+                        foreground: None,
+                        background: None,
+                        children: vec![MarkupTag::Text("4".into())],
+                    }],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_malformed() {
+        for text in ["<>", "1 < 2", "<b>unclosed", "< >", "a <<b>c", "</b>"] {
+            let (_, parsed) = parse_markup(text).unwrap();
+            assert_eq!(parsed, vec![MarkupTag::Text(text.into())], "{text}");
+        }
+
+        let (_, parsed) = parse_markup("1 < 2 <b>bold</b>").unwrap();
+        assert_eq!(
+            parsed,
+            vec![
+                MarkupTag::Text("1 < 2 ".into()),
+                MarkupTag::Bold(vec![MarkupTag::Text("bold".into())]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unclosed_tag() {
+        let (_, parsed) = parse_markup("<b>bold").unwrap();
+        assert_eq!(parsed, vec![MarkupTag::Text("<b>bold".into())]);
+
+        let text = "ok <b>x</b> <i>y <u>z</u>";
+        let (_, parsed) = parse_markup(text).unwrap();
+        assert_eq!(
+            parsed,
+            vec![
+                MarkupTag::Text("ok ".into()),
+                MarkupTag::Bold(vec![MarkupTag::Text("x".into())]),
+                MarkupTag::Text(" <i>y ".into()),
+                MarkupTag::Underline(vec![MarkupTag::Text("z".into())]),
+            ]
+        );
+        let BodyElement::RichText(spans) = &markup(text.into(), 1)[0] else {
+            panic!("Expected rich text");
+        };
+        assert!(spans[1].style.bold);
+        assert_eq!(spans[2].text, " <i>y ");
+        assert!(!spans[2].style.italic);
+        assert!(spans[3].style.underline);
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        let depth = 10_000;
+        let text = format!("{}x{}", "<b>".repeat(depth), "</b>".repeat(depth));
+        assert!(!markup(text, 1).is_empty());
+    }
+
+    #[test]
+    fn test_fuzz() {
+        const FRAGMENTS: [&str; 22] = [
+            "<",
+            ">",
+            "/",
+            "\"",
+            "=",
+            " ",
+            "\n",
+            "x",
+            "é",
+            "b",
+            "<b>",
+            "</b>",
+            "<i>",
+            "</i>",
+            "<>",
+            "</>",
+            "<a href=\"",
+            "</a>",
+            "<img src=\"",
+            "/>",
+            "<span color=\"#f00\">",
+            "</span>",
+        ];
+
+        // A xorshift generator, so failures are reproducible
+        let mut seed: u64 = 0x2545f4914f6cdd1d;
+        let mut next = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        };
+
+        for _ in 0..2000 {
+            let length = next() % 24;
+            let text: String = (0..length)
+                .map(|_| FRAGMENTS[(next() % FRAGMENTS.len() as u64) as usize])
+                .collect();
+
+            let (remainder, _) =
+                parse_markup(&text).unwrap_or_else(|error| panic!("{text}: {error}"));
+            assert!(remainder.is_empty(), "{text}");
+            if text.contains('x') {
+                assert!(!markup(text.clone(), 1).is_empty(), "{text}");
+            }
+        }
+    }
+
     #[test]
     fn test_hyperlink() {
         let text = r#"<a href="example.com">Link text</a>"#;
@@ -388,7 +752,7 @@ mod test {
                 alt: "Alternative text".to_string(),
             }]
         );
-        let grouped = group(flattened);
+        let grouped = group(flattened, 1);
         assert_eq!(
             grouped,
             vec![BodyElement::Image {
@@ -397,4 +761,273 @@ mod test {
             }]
         );
     }
+
+    #[test]
+    fn test_monospace() {
+        let text = r#"<code>cargo</code> <tt>build</tt>"#;
+        let (_, parsed) = parse_markup(&text).unwrap();
+        assert_eq!(
+            parsed,
+            vec![
+                MarkupTag::Monospace(vec![MarkupTag::Text("cargo".into())]),
+                MarkupTag::Text(" ".into()),
+                MarkupTag::Monospace(vec![MarkupTag::Text("build".into())]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_monospace_nesting() {
+        let text = r#"<b><code>x</code></b><code><i>y</i></code>"#;
+        let (_, parsed) = parse_markup(&text).unwrap();
+        let flattened = flatten(parsed);
+        assert_eq!(
+            flattened,
+            vec![
+                UngroupedBodyElement::Span(RichTextSpan {
+                    style: RichTextSpanStyle {
+                        bold: true,
+                        monospace: true,
+                        ..Default::default()
+                    },
+                    text: "x".into(),
+                    link: None,
+                }),
+                UngroupedBodyElement::Span(RichTextSpan {
+                    style: RichTextSpanStyle {
+                        italic: true,
+                        monospace: true,
+                        ..Default::default()
+                    },
+                    text: "y".into(),
+                    link: None,
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_monospace_attributes() {
+        let text = r#"<code class="rust">let x;</code>"#;
+        let (_, parsed) = parse_markup(&text).unwrap();
+        assert_eq!(
+            parsed,
+            vec![MarkupTag::Monospace(vec![MarkupTag::Text("let x;".into())])]
+        );
+    }
+
+    #[test]
+    fn test_span_foreground() {
+        let text = r##"<span foreground="#ff0000">red</span>"##;
+        let (_, parsed) = parse_markup(&text).unwrap();
+        assert_eq!(
+            parsed,
+            vec![MarkupTag::Span {
+                foreground: Some([255, 0, 0]),
+                background: None,
+                children: vec![MarkupTag::Text("red".into())],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_span_foreground_background() {
+        let text = r##"<span background="#fff" weight="bold" foreground="blue"><b>x</b></span>"##;
+        let (_, parsed) = parse_markup(&text).unwrap();
+        assert_eq!(
+            flatten(parsed),
+            vec![UngroupedBodyElement::Span(RichTextSpan {
+                style: RichTextSpanStyle {
+                    bold: true,
+                    foreground: Some([0, 0, 255]),
+                    background: Some([255, 255, 255]),
+                    ..Default::default()
+                },
+                text: "x".into(),
+                link: None,
+            })]
+        );
+    }
+
+    #[test]
+    fn test_span_unknown_color() {
+        let text = r#"<span foreground="chartreuse" size="large">text</span>"#;
+        let (_, parsed) = parse_markup(&text).unwrap();
+        assert_eq!(
+            parsed,
+            vec![MarkupTag::Span {
+                foreground: None,
+                background: None,
+                children: vec![MarkupTag::Text("text".into())],
+            }]
+        );
+        assert_eq!(parse_color("#ffff00000000"), Some([255, 0, 0]));
+        assert_eq!(parse_color("#12345"), None);
+    }
+
+    #[test]
+    fn test_strikethrough() {
+        let text = r#"<s>Done</s><del>Deleted</del>"#;
+        let (_, parsed) = parse_markup(&text).unwrap();
+        assert_eq!(
+            parsed,
+            vec![
+                MarkupTag::Strikethrough(vec![MarkupTag::Text("Done".into())]),
+                MarkupTag::Strikethrough(vec![MarkupTag::Text("Deleted".into())]),
+            ]
+        )
+    }
+
+    #[test]
+    fn test_strikethrough_nesting() {
+        let text = r#"<b><s>x</s></b>"#;
+        let (_, parsed) = parse_markup(&text).unwrap();
+        assert_eq!(
+            parsed,
+            vec![MarkupTag::Bold(vec![MarkupTag::Strikethrough(vec![
+                MarkupTag::Text("x".into())
+            ])])]
+        );
+        let flattened = flatten(parsed);
+        assert_eq!(
+            flattened,
+            vec![UngroupedBodyElement::Span(RichTextSpan {
+                style: RichTextSpanStyle {
+                    bold: true,
+                    strikethrough: true,
+                    ..Default::default()
+                },
+                text: "x".into(),
+                link: None,
+            })]
+        );
+        let grouped = group(flattened, 1);
+        assert_eq!(
+            grouped,
+            vec![BodyElement::RichText(vec![RichTextSpan {
+                style: RichTextSpanStyle {
+                    bold: true,
+                    strikethrough: true,
+                    ..Default::default()
+                },
+                text: "x".into(),
+                link: None,
+            }])]
+        );
+    }
+
+    #[test]
+    fn test_hyperlink_flatten() {
+        let text = r#"See <a href="https://example.com">the <b>docs</b></a>"#;
+        let (_, parsed) = parse_markup(&text).unwrap();
+        let flattened = flatten(parsed);
+        assert_eq!(
+            flattened,
+            vec![
+                UngroupedBodyElement::Span(RichTextSpan {
+                    style: RichTextSpanStyle::default(),
+                    text: "See ".into(),
+                    link: None,
+                }),
+                UngroupedBodyElement::Span(RichTextSpan {
+                    style: RichTextSpanStyle::default(),
+                    text: "the ".into(),
+                    link: Some("https://example.com".into()),
+                }),
+                UngroupedBodyElement::Span(RichTextSpan {
+                    style: RichTextSpanStyle {
+                        bold: true,
+                        ..Default::default()
+                    },
+                    text: "docs".into(),
+                    link: Some("https://example.com".into()),
+                }),
+            ]
+        );
+    }
+
+    fn line(text: &str) -> BodyElement {
+        BodyElement::RichText(vec![RichTextSpan {
+            style: RichTextSpanStyle::default(),
+            text: text.into(),
+            link: None,
+        }])
+    }
+
+    #[test]
+    fn test_newlines() {
+        assert_eq!(
+            markup("line1\nline2".into(), 1),
+            vec![line("line1"), line("line2")]
+        );
+        assert_eq!(
+            markup("line1\r\nline2\r\n".into(), 1),
+            vec![line("line1"), line("line2")]
+        );
+        assert_eq!(
+            markup("\nline1\nline2\n\n\n".into(), 1),
+            vec![line("line1"), line("line2")]
+        );
+        assert_eq!(
+            plain("<b>line1</b>\nline2".into(), 1),
+            vec![line("<b>line1</b>"), line("line2")]
+        );
+    }
+
+    #[test]
+    fn test_blank_lines() {
+        let text = "para1\n\n\n\npara2";
+        assert_eq!(markup(text.into(), 0), vec![line("para1"), line("para2")]);
+        assert_eq!(
+            markup(text.into(), 2),
+            vec![
+                line("para1"),
+                BodyElement::RichText(vec![]),
+                BodyElement::RichText(vec![]),
+                line("para2")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_newline_in_markup() {
+        let text = "<b>bold\nstill bold</b>";
+        assert_eq!(
+            markup(text.into(), 1),
+            vec![
+                BodyElement::RichText(vec![RichTextSpan {
+                    style: RichTextSpanStyle {
+                        bold: true,
+                        ..Default::default()
+                    },
+                    text: "bold".into(),
+                    link: None,
+                }]),
+                BodyElement::RichText(vec![RichTextSpan {
+                    style: RichTextSpanStyle {
+                        bold: true,
+                        ..Default::default()
+                    },
+                    text: "still bold".into(),
+                    link: None,
+                }]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_long_token() {
+        // A long unbroken token stays a single span on a single line, leaving wrapping to the
+        // renderer
+        let token = "a".repeat(300);
+        assert_eq!(markup(token.clone(), 1), vec![line(&token)]);
+        let text = format!("<a href=\"https://example.com\">{token}</a>");
+        let grouped = markup(text, 1);
+        assert_eq!(grouped.len(), 1);
+        let BodyElement::RichText(spans) = &grouped[0] else {
+            panic!("Expected rich text");
+        };
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].text.chars().count(), 300);
+    }
 }
@@ -1,20 +1,59 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
 use iced::advanced::layout;
 use iced::advanced::mouse;
+use iced::advanced::overlay;
 use iced::advanced::renderer;
 use iced::advanced::widget;
 use iced::advanced::Layout;
 use iced::advanced::Shell;
-use iced::Rectangle;
 use iced::{Element, Length, Size};
+use iced::{Rectangle, Vector};
+
+/// Lengths within this fraction of a device pixel over a whole pixel are rounded down to it, so
+/// float error in the layout doesn't add a pixel
+const PIXEL_EPSILON: f32 = 0.01;
+
+/// The device pixels covering a logical length at the scale factor
+pub fn device_pixels(length: f32, scale_factor: f32) -> u32 {
+    (length * scale_factor - PIXEL_EPSILON).ceil().max(0.0) as u32
+}
+
+/// The whole logical length covering a logical length's device pixels at the scale factor, since
+/// the layer shell is sized in whole logical pixels
+pub fn logical_pixels(length: f32, scale_factor: f32) -> u32 {
+    let device = device_pixels(length, scale_factor) as f32;
+    (device / scale_factor - PIXEL_EPSILON).ceil().max(0.0) as u32
+}
+
+/// A hash of where everything in the layout is, which changes when anything moves or resizes
+fn layout_fingerprint(layout: Layout<'_>) -> u64 {
+    fn hash(layout: Layout<'_>, hasher: &mut DefaultHasher) {
+        let bounds = layout.bounds();
+        [bounds.x, bounds.y, bounds.width, bounds.height]
+            .map(f32::to_bits)
+            .hash(hasher);
+        layout.children().for_each(|child| hash(child, hasher));
+    }
+
+    let mut hasher = DefaultHasher::new();
+    hash(layout, &mut hasher);
+    hasher.finish()
+}
 
 #[derive(Default)]
 struct State {
-    size: Size,
+    /// The last measured size, in device pixels
+    device_size: (u32, u32),
+    /// The fingerprint of the contents' layout when last drawn
+    layout_fingerprint: u64,
 }
 
 pub struct MeasuringContainer<'a, Message, Theme, Renderer> {
     child: Element<'a, Message, Theme, Renderer>,
     on_resize: Box<dyn Fn(Size) -> Message + 'a>,
+    max_width: f32,
+    scale_factor: f32,
 }
 
 impl<'a, Message, Theme, Renderer> MeasuringContainer<'a, Message, Theme, Renderer>
@@ -29,8 +68,23 @@ where
         Self {
             child,
             on_resize: Box::new(on_resize),
+            max_width: f32::INFINITY,
+            scale_factor: 1.0,
         }
     }
+
+    /// Sets the scale factor of the window, so the size is only published when it changes by a
+    /// whole device pixel
+    pub fn scale_factor(mut self, scale_factor: f32) -> Self {
+        self.scale_factor = scale_factor;
+        self
+    }
+
+    /// Sets the maximum width of the contents, so the measured width never exceeds it
+    pub fn max_width(mut self, max_width: f32) -> Self {
+        self.max_width = max_width;
+        self
+    }
 }
 
 impl<'a, Message, Theme, Renderer> widget::Widget<Message, Theme, Renderer>
@@ -49,17 +103,40 @@ where
         shell: &mut Shell<'_, Message>,
         viewport: &Rectangle,
     ) {
-        // On redraws, measure the size of the widget, and if it's different from last time,
-        // publish a message with the new size
+        // On redraws, measure the size of the widget, and if it covers different device pixels
+        // than last time, publish a message with the new size
         if let iced::Event::Window(iced::window::Event::RedrawRequested(_)) = event {
-            let limits = layout::Limits::new(Size::ZERO, Size::INFINITY);
+            let limits = layout::Limits::new(Size::ZERO, Size::new(self.max_width, f32::INFINITY));
             let new_size = self.layout(tree, renderer, &limits).bounds().size();
+            let device_size = (
+                device_pixels(new_size.width, self.scale_factor),
+                device_pixels(new_size.height, self.scale_factor),
+            );
+            let fingerprint = layout_fingerprint(layout);
             let state = tree.state.downcast_mut::<State>();
 
-            if new_size != state.size {
-                state.size = new_size;
+            if device_size != state.device_size {
+                state.device_size = device_size;
                 shell.publish((self.on_resize)(new_size));
             }
+
+            // Contents only notice what is under the cursor when it moves, so when they move
+            // under a still cursor, like when a notification above it closes, move the cursor to
+            // where it already is for them to notice
+            let moved = fingerprint != state.layout_fingerprint;
+            state.layout_fingerprint = fingerprint;
+            if let Some(position) = cursor.position().filter(|_| moved) {
+                self.child.as_widget_mut().update(
+                    &mut tree.children[0],
+                    &iced::Event::Mouse(mouse::Event::CursorMoved { position }),
+                    layout.children().next().unwrap(),
+                    cursor,
+                    renderer,
+                    clipboard,
+                    shell,
+                    viewport,
+                );
+            }
         }
 
         // Let the contents capture the event
@@ -75,6 +152,57 @@ where
         );
     }
 
+    fn operate(
+        &self,
+        tree: &mut widget::Tree,
+        layout: Layout<'_>,
+        renderer: &Renderer,
+        operation: &mut dyn widget::Operation,
+    ) {
+        // Let operations, like scrolling, reach the contents
+        self.child.as_widget().operate(
+            &mut tree.children[0],
+            layout.children().next().unwrap(),
+            renderer,
+            operation,
+        );
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &widget::Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+        renderer: &Renderer,
+    ) -> mouse::Interaction {
+        self.child.as_widget().mouse_interaction(
+            &tree.children[0],
+            layout.children().next().unwrap(),
+            cursor,
+            viewport,
+            renderer,
+        )
+    }
+
+    fn overlay<'b>(
+        &'b mut self,
+        tree: &'b mut widget::Tree,
+        layout: Layout<'b>,
+        renderer: &Renderer,
+        viewport: &Rectangle,
+        translation: Vector,
+    ) -> Option<overlay::Element<'b, Message, Theme, Renderer>> {
+        // Forward the child's overlay, so that e.g. tooltips in the contents can be shown
+        self.child.as_widget_mut().overlay(
+            &mut tree.children[0],
+            layout.children().next().unwrap(),
+            renderer,
+            viewport,
+            translation,
+        )
+    }
+
     fn size(&self) -> Size<Length> {
         Size {
             width: Length::Shrink,
@@ -104,10 +232,11 @@ where
         renderer: &Renderer,
         limits: &layout::Limits,
     ) -> layout::Node {
+        let limits = limits.max_width(self.max_width);
         let contents = self
             .child
             .as_widget()
-            .layout(&mut tree.children[0], renderer, limits);
+            .layout(&mut tree.children[0], renderer, &limits);
 
         layout::Node::container(contents, 0.into())
     }
@@ -147,3 +276,49 @@ where
         Self::new(widget)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use iced::Point;
+
+    #[test]
+    fn test_pixels() {
+        // 433.333 logical pixels are 650 device pixels at 1.5, covered by 434 logical pixels
+        assert_eq!(device_pixels(433.333, 1.5), 650);
+        assert_eq!(device_pixels(433.3334, 1.5), 650);
+        assert_eq!(logical_pixels(433.333, 1.5), 434);
+        assert_eq!(logical_pixels(433.4, 1.25), 434);
+
+        // Float error doesn't add a pixel
+        assert_eq!(logical_pixels(434.0001, 1.0), 434);
+        assert_eq!(logical_pixels(433.9999, 1.0), 434);
+        assert_eq!(logical_pixels(0.0, 1.25), 0);
+    }
+
+    #[test]
+    fn test_layout_fingerprint() {
+        let card = |y: f32| layout::Node::new(Size::new(300.0, 80.0)).move_to(Point::new(0.0, y));
+        let stack =
+            |cards: Vec<layout::Node>| layout::Node::with_children(Size::new(300.0, 200.0), cards);
+
+        let before = stack(vec![card(0.0), card(90.0)]);
+        let same = stack(vec![card(0.0), card(90.0)]);
+        assert_eq!(
+            layout_fingerprint(Layout::new(&before)),
+            layout_fingerprint(Layout::new(&same))
+        );
+
+        // The first card closes and the second moves into its place, in a window the same size
+        let after = stack(vec![card(0.0)]);
+        assert_ne!(
+            layout_fingerprint(Layout::new(&before)),
+            layout_fingerprint(Layout::new(&after))
+        );
+        let shifted = stack(vec![card(10.0), card(90.0)]);
+        assert_ne!(
+            layout_fingerprint(Layout::new(&before)),
+            layout_fingerprint(Layout::new(&shifted))
+        );
+    }
+}
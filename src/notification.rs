@@ -1,7 +1,19 @@
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Local, TimeDelta};
+use iced::Color;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::path::PathBuf;
+use std::sync::Arc;
 
-use crate::markup::BodyElement;
+use crate::config::{CompactUrgency, ShowAppName, TimeFormatConfig};
+use crate::markup::{BodyElement, RichTextSpan};
+
+/// The key of the action invoked by clicking a notification
+pub const DEFAULT_ACTION: &str = "default";
+/// The key of the action replying from the notification, which is shown as a text field rather
+/// than a button
+pub const INLINE_REPLY: &str = "inline-reply";
 
 #[derive(Clone, Debug)]
 pub struct Notification {
@@ -13,8 +25,8 @@ pub struct Notification {
     pub name: String,
     /// The application provided summary
     pub summary: String,
-    /// The application provided body
-    pub body: Option<Vec<BodyElement>>,
+    /// The application provided body, shared so cloning a notification doesn't copy it
+    pub body: Option<Arc<Vec<BodyElement>>>,
     /// The time the notification was sent
     pub time: DateTime<Local>,
     /// The time the notification will expire
@@ -23,17 +35,290 @@ pub struct Notification {
     pub actions: Option<Vec<(String, String)>>,
     /// The DBUS supplied urgency, defaulting to Normal
     pub urgency: Urgency,
+    /// The dominant color of the icon, if accent colors are enabled
+    pub accent: Option<Color>,
+    /// The border color the app asked for, if any
+    pub border_color: Option<Color>,
+    /// Whether the app asked for the notification to stay after an action is invoked
+    pub resident: bool,
+    /// The placeholder text of the field to reply in, if the app takes inline replies
+    pub reply: Option<String>,
+    /// The ids of exact duplicates merged into this notification
+    pub merged: Vec<u32>,
+    /// Whether the notification is shown again from the history, rather than sent by an app
+    pub historical: bool,
+    /// The sound to play when the notification is displayed, which isn't played if it's held
+    pub sound: Option<PathBuf>,
 }
 
+impl Notification {
+    /// The fraction of the notification's display time left at the given time, or None if it
+    /// doesn't expire
+    pub fn time_left(&self, now: DateTime<Local>) -> Option<f32> {
+        let expire_time = self.expire_time?;
+        let total = (expire_time - self.time).num_milliseconds();
+        if total <= 0 {
+            return Some(0.0);
+        }
+
+        let left = (expire_time - now).num_milliseconds();
+        Some((left as f32 / total as f32).clamp(0.0, 1.0))
+    }
+
+    /// Whether the notification has expired by the given time, or is older than the max age in
+    /// seconds, if it isn't 0 and the notification isn't from the history
+    pub fn is_expired(&self, now: DateTime<Local>, max_age: u64) -> bool {
+        self.expiry(max_age).is_some_and(|expiry| now > expiry)
+    }
+
+    /// When the notification expires, or becomes older than the max age in seconds, whichever is
+    /// first, if ever
+    pub fn expiry(&self, max_age: u64) -> Option<DateTime<Local>> {
+        let too_old = TimeDelta::try_seconds(max_age as i64)
+            .filter(|_| max_age > 0 && !self.historical)
+            .and_then(|max_age| self.time.checked_add_signed(max_age));
+        match (too_old, self.expire_time) {
+            (Some(too_old), Some(expire_time)) => Some(too_old.min(expire_time)),
+            (too_old, expire_time) => too_old.or(expire_time),
+        }
+    }
+
+    /// Push the expire time back by the time between `since` and `now`, like while the
+    /// notification was hovered
+    pub fn delay_expiry(&mut self, since: DateTime<Local>, now: DateTime<Local>) {
+        if let Some(expire_time) = &mut self.expire_time {
+            *expire_time += now - since;
+        }
+    }
+
+    /// The app, summary, and body, which duplicates have in common
+    pub fn content(&self) -> Content<'_> {
+        (
+            &self.name,
+            &self.summary,
+            self.body.as_deref().map(Vec::as_slice),
+        )
+    }
+
+    /// A hash of the notification's content, the same for duplicates
+    pub fn content_hash(&self) -> u64 {
+        content_hash(self.content())
+    }
+
+    /// The color to border the notification with, the app's own over the icon's accent
+    pub fn border_accent(&self) -> Option<Color> {
+        self.border_color.or(self.accent)
+    }
+
+    /// Whether this has the same app, summary, and body as the other notification
+    pub fn is_duplicate_of(&self, other: &Notification) -> bool {
+        self.content() == other.content()
+    }
+
+    /// Merge in an exact duplicate, counting it and showing this for as long as it would be shown
+    pub fn merge(&mut self, duplicate: Notification) {
+        self.merged.push(duplicate.id);
+        self.time = duplicate.time;
+        self.expire_time = duplicate.expire_time;
+    }
+
+    /// The body as plain text, one line per body element and images as their alt text, or the
+    /// summary if there is no body
+    pub fn plain_text(&self) -> String {
+        let Some(body) = &self.body else {
+            return self.summary.clone();
+        };
+
+        body.iter()
+            .map(|element| match element {
+                BodyElement::RichText(spans) => spans
+                    .iter()
+                    .map(|RichTextSpan { text, .. }| text.as_str())
+                    .collect(),
+                BodyElement::Image { alt, .. } => alt.clone(),
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// The layout to show this in, compact for the urgencies chosen and for the apps given, but
+    /// never when critical
+    pub fn layout_kind(&self, compact: CompactUrgency, compact_apps: &[String]) -> LayoutKind {
+        let compact_app = self.urgency != Urgency::Critical && compact_apps.contains(&self.name);
+        if compact.includes(self.urgency) || compact_app {
+            LayoutKind::Compact
+        } else {
+            LayoutKind::Full
+        }
+    }
+
+    /// Whether the app registered an action for clicking the notification
+    pub fn has_default_action(&self) -> bool {
+        self.actions
+            .iter()
+            .flatten()
+            .any(|(key, _)| key == DEFAULT_ACTION)
+    }
+
+    /// The actions to show as buttons, which leave out the default action, invoked by clicking
+    /// the notification instead
+    pub fn button_actions(&self) -> impl Iterator<Item = &(String, String)> {
+        self.actions
+            .iter()
+            .flatten()
+            .filter(|(key, _)| key != DEFAULT_ACTION)
+    }
+
+    /// Whether to show the app name above the summary shown, which it only repeats when the same
+    pub fn shows_name(&self, summary: &str, show: ShowAppName) -> bool {
+        let name = self.name.trim();
+        match show {
+            ShowAppName::Always => true,
+            ShowAppName::Never => false,
+            ShowAppName::Auto => !name.is_empty() && name != summary.trim(),
+        }
+    }
+
+    /// The summary to show and the body to show under it. When the summary is empty and
+    /// `promote` is set, the first line of the body is shown as the summary instead, and left out
+    /// of the body along with any blank lines after it
+    pub fn summary_and_body(&self, promote: bool) -> (String, &[BodyElement]) {
+        let body = self.body.as_deref().map_or(&[][..], Vec::as_slice);
+        if !promote || !self.summary.trim().is_empty() {
+            return (self.summary.clone(), body);
+        }
+
+        match body.split_first() {
+            Some((BodyElement::RichText(spans), rest)) => {
+                let summary = spans
+                    .iter()
+                    .map(|RichTextSpan { text, .. }| text.as_str())
+                    .collect();
+                let blank_lines = rest
+                    .iter()
+                    .take_while(|element| {
+                        matches!(element, BodyElement::RichText(spans) if spans.is_empty())
+                    })
+                    .count();
+                (summary, &rest[blank_lines..])
+            }
+            _ => (self.summary.clone(), body),
+        }
+    }
+}
+
+/// The app name, summary, and body of a notification, which duplicates have in common
+pub type Content<'a> = (&'a str, &'a str, Option<&'a [BodyElement]>);
+
+/// A hash of a notification's content, the same for duplicates
+pub fn content_hash(content: Content) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+impl Notification {
+    /// A notification from "App" with the summary and the body parsed as markup, for tests
+    pub fn test(id: u32, summary: &str, body: Option<&str>) -> Self {
+        Notification {
+            id,
+            icon: PathBuf::new(),
+            name: "App".into(),
+            summary: summary.into(),
+            body: body.map(|body| Arc::new(crate::markup::markup(body.into(), 1))),
+            time: Local::now(),
+            expire_time: None,
+            actions: None,
+            urgency: Urgency::Normal,
+            accent: None,
+            border_color: None,
+            resident: false,
+            reply: None,
+            merged: Vec::new(),
+            historical: false,
+            sound: None,
+        }
+    }
+}
+
+/// How a notification is laid out
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LayoutKind {
+    /// A card with the icon, header, body, and actions
+    Full,
+    /// One row with a small icon, the app name, and the summary
+    Compact,
+}
+
+/// Shorten the text to at most `max_chars` characters, ending it with an ellipsis if shortened
+pub fn ellipsize(text: &str, max_chars: usize) -> Cow<'_, str> {
+    if text.chars().count() <= max_chars {
+        return Cow::Borrowed(text);
+    }
+
+    let kept: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+    Cow::Owned(format!("{}…", kept.trim_end()))
+}
+
+/// Find the first standalone run of 4 to 8 digits in the text, like a 2FA code
+pub fn find_code(text: &str) -> Option<&str> {
+    text.split(|c: char| !c.is_ascii_digit())
+        .find(|run| (4..=8).contains(&run.len()))
+}
+
+/// Ordered from least to most urgent
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
 pub(crate) enum Urgency {
     Low,
     Normal,
     Critical,
 }
 
-pub fn notification_time(time: &DateTime<Local>) -> String {
-    let since = Local::now().signed_duration_since(time);
+/// The notifications to show, in order, of at most `max_visible` notifications, or 0 for no
+/// limit. Critical notifications are always shown, and the rest of the slots go to the others in
+/// order, so a flood of notifications can't hide a critical one
+pub fn select_visible<'a>(
+    notifications: &[&'a Notification],
+    max_visible: usize,
+) -> Vec<&'a Notification> {
+    if max_visible == 0 || notifications.len() <= max_visible {
+        return notifications.to_vec();
+    }
+
+    let critical = notifications
+        .iter()
+        .filter(|notification| notification.urgency == Urgency::Critical)
+        .count();
+    let mut slots = max_visible.saturating_sub(critical);
+    notifications
+        .iter()
+        .copied()
+        .filter(|notification| {
+            if notification.urgency == Urgency::Critical {
+                true
+            } else if slots > 0 {
+                slots -= 1;
+                true
+            } else {
+                false
+            }
+        })
+        .collect()
+}
+
+/// Sort notifications from most to least urgent, keeping the order of equally urgent ones so
+/// they don't move around between redraws
+pub fn sort_by_urgency(notifications: &mut [&Notification]) {
+    notifications.sort_by_key(|notification| std::cmp::Reverse(notification.urgency));
+}
+
+pub fn notification_time(
+    time: &DateTime<Local>,
+    now: DateTime<Local>,
+    format: &TimeFormatConfig,
+) -> String {
+    let since = now.signed_duration_since(time);
 
     if since.num_seconds() < 30 {
         "now".into()
@@ -41,13 +326,373 @@ pub fn notification_time(time: &DateTime<Local>) -> String {
         "30s ago".into()
     } else if since.num_minutes() < 60 {
         format!("{}m ago", since.num_minutes())
-    } else if since.num_hours() < 4 {
+    } else if since.num_hours() < format.relative_hours {
         format!("{}h ago", since.num_hours())
     } else if since.num_days() < 1 {
-        time.format("%H:%S").to_string()
+        time.format(&format.time_format_day).to_string()
     } else if since.num_weeks() < 1 {
-        time.format("%a %H:%M").to_string()
+        time.format(&format.time_format_week).to_string()
     } else {
-        time.format("%a %h %e").to_string()
+        time.format(&format.time_format_older).to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::markup::markup;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_default_action() {
+        let notification = Notification {
+            actions: Some(vec![
+                ("default".into(), "Open".into()),
+                ("reply".into(), "Reply".into()),
+            ]),
+            ..Notification::test(1, "Summary", None)
+        };
+        assert!(notification.has_default_action());
+        let buttons: Vec<_> = notification.button_actions().collect();
+        assert_eq!(buttons, vec![&("reply".into(), "Reply".into())]);
+
+        let notification = Notification {
+            actions: None,
+            ..notification
+        };
+        assert!(!notification.has_default_action());
+        assert_eq!(notification.button_actions().count(), 0);
+    }
+
+    #[test]
+    fn test_shows_name() {
+        let notification = Notification::test(1, "Summary", None);
+        assert!(notification.shows_name("Summary", ShowAppName::Auto));
+        assert!(!notification.shows_name(" App ", ShowAppName::Auto));
+        assert!(notification.shows_name("App", ShowAppName::Always));
+        assert!(!notification.shows_name("Summary", ShowAppName::Never));
+
+        let notification = Notification {
+            name: String::new(),
+            ..notification
+        };
+        assert!(!notification.shows_name("Summary", ShowAppName::Auto));
+    }
+
+    #[test]
+    fn test_border_accent() {
+        let accent = Color::from_rgb8(0x45, 0x85, 0x88);
+        let border_color = Color::from_rgb8(0xd7, 0x99, 0x21);
+        let notification = Notification {
+            accent: Some(accent),
+            ..Notification::test(1, "Summary", None)
+        };
+        assert_eq!(notification.border_accent(), Some(accent));
+
+        let notification = Notification {
+            border_color: Some(border_color),
+            ..notification
+        };
+        assert_eq!(notification.border_accent(), Some(border_color));
+    }
+
+    #[test]
+    fn test_plain_text() {
+        assert_eq!(
+            Notification::test(1, "Summary", Some("<b>Your</b> code\nis 123456")).plain_text(),
+            "Your code\nis 123456"
+        );
+        assert_eq!(
+            Notification::test(1, "Summary", None).plain_text(),
+            "Summary"
+        );
+    }
+
+    #[test]
+    fn test_max_age() {
+        let notification = Notification::test(1, "Summary", None);
+        let later = notification.time + TimeDelta::seconds(61);
+
+        assert!(!notification.is_expired(later, 0));
+        assert!(!notification.is_expired(later, 120));
+        assert!(notification.is_expired(later, 60));
+
+        let historical = Notification {
+            historical: true,
+            ..notification.clone()
+        };
+        assert!(!historical.is_expired(later, 60));
+
+        let expiring = Notification {
+            expire_time: Some(notification.time + TimeDelta::seconds(5)),
+            ..notification
+        };
+        assert!(expiring.is_expired(later, 0));
+    }
+
+    #[test]
+    fn test_expiry() {
+        let notification = Notification::test(1, "Summary", None);
+        let soon = notification.time + TimeDelta::milliseconds(200);
+        let later = notification.time + TimeDelta::seconds(60);
+
+        assert_eq!(notification.expiry(0), None);
+        assert_eq!(notification.expiry(60), Some(later));
+
+        let expiring = Notification {
+            expire_time: Some(soon),
+            ..notification
+        };
+        assert_eq!(expiring.expiry(0), Some(soon));
+        assert_eq!(expiring.expiry(60), Some(soon));
+        assert_eq!(expiring.expiry(1), Some(soon));
+    }
+
+    #[test]
+    fn test_time_left() {
+        let notification = Notification::test(1, "Summary", None);
+        assert_eq!(notification.time_left(Local::now()), None);
+
+        let time = notification.time;
+        let expiring = Notification {
+            expire_time: Some(time + TimeDelta::seconds(4)),
+            ..notification
+        };
+        assert_eq!(expiring.time_left(time), Some(1.0));
+        assert_eq!(expiring.time_left(time + TimeDelta::seconds(1)), Some(0.75));
+        assert_eq!(expiring.time_left(time + TimeDelta::seconds(5)), Some(0.0));
+    }
+
+    #[test]
+    fn test_delay_expiry() {
+        let notification = Notification::test(1, "Summary", None);
+        let time = notification.time;
+        let mut expiring = Notification {
+            expire_time: Some(time + TimeDelta::seconds(5)),
+            ..notification
+        };
+
+        // Hovered from 4s to 10s, so it expires 6s later than it would have
+        expiring.delay_expiry(time + TimeDelta::seconds(4), time + TimeDelta::seconds(10));
+        assert_eq!(expiring.expire_time, Some(time + TimeDelta::seconds(11)));
+        assert!(!expiring.is_expired(time + TimeDelta::seconds(10), 0));
+        assert!(expiring.is_expired(time + TimeDelta::seconds(12), 0));
+
+        let mut never = Notification {
+            expire_time: None,
+            ..expiring
+        };
+        never.delay_expiry(time, time + TimeDelta::seconds(10));
+        assert_eq!(never.expire_time, None);
+    }
+
+    #[test]
+    fn test_merge_duplicate() {
+        let mut original = Notification::test(1, "Cron", Some("Job <b>failed</b>"));
+        let duplicate = Notification {
+            id: 2,
+            time: original.time + TimeDelta::seconds(10),
+            expire_time: Some(original.time + TimeDelta::seconds(15)),
+            ..Notification::test(1, "Cron", Some("Job <b>failed</b>"))
+        };
+        assert!(duplicate.is_duplicate_of(&original));
+        assert!(!Notification::test(1, "Cron", Some("Job succeeded")).is_duplicate_of(&original));
+
+        let (time, expire_time) = (duplicate.time, duplicate.expire_time);
+        original.merge(duplicate);
+        assert_eq!(original.merged, vec![2]);
+        assert_eq!(original.time, time);
+        assert_eq!(original.expire_time, expire_time);
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let original = Notification::test(1, "Cron", Some("Job <b>failed</b>"));
+        let duplicate = Notification {
+            id: 2,
+            time: original.time + TimeDelta::seconds(10),
+            ..Notification::test(1, "Cron", Some("Job <b>failed</b>"))
+        };
+        assert_eq!(original.content_hash(), duplicate.content_hash());
+        assert_ne!(
+            original.content_hash(),
+            Notification::test(1, "Cron", Some("Job succeeded")).content_hash()
+        );
+        assert_ne!(
+            original.content_hash(),
+            Notification::test(1, "Backup", Some("Job <b>failed</b>")).content_hash()
+        );
+        // Like for is_duplicate_of, the same text with different markup isn't the same content
+        let unstyled = Notification::test(1, "Cron", Some("Job failed"));
+        assert!(!unstyled.is_duplicate_of(&original));
+        assert_ne!(original.content_hash(), unstyled.content_hash());
+    }
+
+    #[test]
+    fn test_notification_time() {
+        let time = Local.with_ymd_and_hms(2025, 1, 6, 12, 0, 0).unwrap();
+        let format = TimeFormatConfig {
+            relative_hours: 1,
+            time_format_day: "%H:%M".into(),
+            time_format_week: "%A".into(),
+            time_format_older: "%d/%m".into(),
+        };
+        let after = |seconds| time + TimeDelta::seconds(seconds);
+
+        assert_eq!(notification_time(&time, after(10), &format), "now");
+        assert_eq!(notification_time(&time, after(30 * 60), &format), "30m ago");
+        assert_eq!(notification_time(&time, after(2 * 3600), &format), "12:00");
+        assert_eq!(
+            notification_time(&time, after(2 * 86400), &format),
+            "Monday"
+        );
+        assert_eq!(
+            notification_time(&time, after(10 * 86400), &format),
+            "06/01"
+        );
+        assert_eq!(
+            notification_time(&time, after(2 * 3600), &TimeFormatConfig::default()),
+            "2h ago"
+        );
+        // By default, the same day's times older than that show the hour and minute
+        let time = Local.with_ymd_and_hms(2025, 1, 6, 9, 34, 56).unwrap();
+        assert_eq!(
+            notification_time(
+                &time,
+                time + TimeDelta::hours(5),
+                &TimeFormatConfig::default()
+            ),
+            "09:34"
+        );
+    }
+
+    #[test]
+    fn test_find_code() {
+        assert_eq!(find_code("Your code is 123456."), Some("123456"));
+        assert_eq!(find_code("Code: 0042"), Some("0042"));
+        assert_eq!(find_code("Call 555 or 12345678901"), None);
+        assert_eq!(find_code("No code here"), None);
+        assert_eq!(find_code("Order 12 shipped, code 9876"), Some("9876"));
+    }
+
+    #[test]
+    fn test_summary_and_body() {
+        let empty = Notification::test(1, "", Some("First <b>line</b>\n\nSecond line"));
+        let (summary, body) = empty.summary_and_body(true);
+        assert_eq!(summary, "First line");
+        assert_eq!(body, &markup("Second line".into(), 1)[..]);
+
+        let (summary, body) = empty.summary_and_body(false);
+        assert_eq!(summary, "");
+        assert_eq!(body.len(), 3);
+
+        let single = Notification::test(1, " ", Some("Only line"));
+        assert_eq!(single.summary_and_body(true), ("Only line".into(), &[][..]));
+
+        let summarized = Notification::test(1, "Summary", Some("Body"));
+        assert_eq!(summarized.summary_and_body(true).0, "Summary");
+    }
+
+    #[test]
+    fn test_select_visible() {
+        // The critical notification is the oldest, so last when the newest are shown first
+        let notifications: Vec<Notification> = (1..=11)
+            .map(|id| Notification {
+                id,
+                urgency: if id == 11 {
+                    Urgency::Critical
+                } else {
+                    Urgency::Normal
+                },
+                ..Notification::test(1, "Summary", None)
+            })
+            .collect();
+        let notifications: Vec<&Notification> = notifications.iter().collect();
+
+        let ids = |visible: Vec<&Notification>| -> Vec<u32> {
+            visible.iter().map(|notification| notification.id).collect()
+        };
+        assert_eq!(ids(select_visible(&notifications, 3)), [1, 2, 11]);
+        assert_eq!(ids(select_visible(&notifications, 1)), [11]);
+        assert_eq!(select_visible(&notifications, 0).len(), 11);
+        assert_eq!(select_visible(&notifications[..3], 3).len(), 3);
+    }
+
+    #[test]
+    fn test_ellipsize() {
+        assert_eq!(ellipsize("Reply", 10), "Reply");
+        assert_eq!(ellipsize("Mark as read", 10), "Mark as r…");
+        assert_eq!(ellipsize("Open in browser", 8), "Open in…");
+        assert_eq!(ellipsize("日本語のテキスト", 4), "日本語…");
+    }
+
+    #[test]
+    fn test_layout_kind() {
+        let with_urgency = |urgency| Notification {
+            urgency,
+            ..Notification::test(1, "Summary", None)
+        };
+        let low = with_urgency(Urgency::Low);
+        let normal = with_urgency(Urgency::Normal);
+        let critical = with_urgency(Urgency::Critical);
+
+        assert_eq!(
+            low.layout_kind(CompactUrgency::Low, &[]),
+            LayoutKind::Compact
+        );
+        assert_eq!(
+            normal.layout_kind(CompactUrgency::Low, &[]),
+            LayoutKind::Full
+        );
+        assert_eq!(
+            normal.layout_kind(CompactUrgency::Normal, &[]),
+            LayoutKind::Compact
+        );
+        assert_eq!(
+            low.layout_kind(CompactUrgency::Never, &[]),
+            LayoutKind::Full
+        );
+
+        let apps = ["App".to_string()];
+        assert_eq!(
+            normal.layout_kind(CompactUrgency::Never, &apps),
+            LayoutKind::Compact
+        );
+        assert_eq!(
+            critical.layout_kind(CompactUrgency::Normal, &apps),
+            LayoutKind::Full
+        );
+    }
+
+    #[test]
+    fn test_sort_by_urgency() {
+        let urgencies = [
+            (1, Urgency::Normal),
+            (2, Urgency::Low),
+            (3, Urgency::Critical),
+            (4, Urgency::Normal),
+            (5, Urgency::Critical),
+            (6, Urgency::Low),
+        ];
+        let notifications: Vec<Notification> = urgencies
+            .into_iter()
+            .map(|(id, urgency)| Notification {
+                id,
+                urgency,
+                ..Notification::test(1, "Summary", None)
+            })
+            .collect();
+        let mut sorted: Vec<&Notification> = notifications.iter().collect();
+
+        sort_by_urgency(&mut sorted);
+        let ids: Vec<u32> = sorted.iter().map(|notification| notification.id).collect();
+        assert_eq!(ids, [3, 5, 1, 4, 2, 6]);
+
+        // Sorting again doesn't move equally urgent notifications
+        sort_by_urgency(&mut sorted);
+        let resorted: Vec<u32> = sorted.iter().map(|notification| notification.id).collect();
+        assert_eq!(resorted, ids);
+
+        assert!(Urgency::Critical > Urgency::Normal && Urgency::Normal > Urgency::Low);
     }
 }
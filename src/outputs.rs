@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+
+use iced::futures::channel::mpsc;
+use iced::futures::Stream;
+use log::{debug, warn};
+use wayland_client::globals::GlobalListContents;
+use wayland_client::protocol::wl_output::{self, WlOutput};
+use wayland_client::protocol::wl_registry::{self, WlRegistry};
+use wayland_client::{Connection, Dispatch, Proxy, QueueHandle};
+
+use crate::wayland;
+
+/// The first version of wl_output to send the output's name
+const NAME_VERSION: u32 = 4;
+
+/// The names of the connected outputs, like DP-1, sorted and sent each time they change. These are
+/// the names the layer shell's output is chosen by. Outputs from compositors with a wl_output too
+/// old to send names are left out
+pub fn outputs() -> impl Stream<Item = Vec<String>> {
+    wayland::watch(watch)
+}
+
+fn watch(sender: mpsc::UnboundedSender<Vec<String>>) {
+    let Some((globals, mut queue)) = wayland::connect::<Outputs>("outputs") else {
+        return;
+    };
+    let handle = queue.handle();
+
+    let mut outputs = Outputs {
+        sender,
+        outputs: HashMap::new(),
+        names: HashMap::new(),
+        reported: None,
+    };
+    globals.contents().with_list(|list| {
+        list.iter()
+            .filter(|global| global.interface == WlOutput::interface().name)
+            .for_each(|global| {
+                outputs.bind(globals.registry(), global.name, global.version, &handle)
+            });
+    });
+    // Wait for the outputs' names, so the first names sent are complete
+    if let Err(err) = queue.roundtrip(&mut outputs) {
+        warn!("Unable to get the output names with error {err}");
+        return;
+    }
+    outputs.report();
+
+    wayland::dispatch(queue, &mut outputs, "outputs", |outputs| {
+        outputs.sender.is_closed()
+    });
+}
+
+/// The outputs the compositor has told about, by their names in the registry
+struct Outputs {
+    sender: mpsc::UnboundedSender<Vec<String>>,
+    outputs: HashMap<u32, WlOutput>,
+    /// The name of each output, once the compositor has sent it
+    names: HashMap<u32, String>,
+    /// The names last sent
+    reported: Option<Vec<String>>,
+}
+
+impl Outputs {
+    /// Start listening to an output, if its version sends its name
+    fn bind(
+        &mut self,
+        registry: &WlRegistry,
+        global: u32,
+        version: u32,
+        handle: &QueueHandle<Self>,
+    ) {
+        if version < NAME_VERSION {
+            debug!("Ignoring output {global}, its wl_output version {version} doesn't send names");
+            return;
+        }
+
+        let output = registry.bind::<WlOutput, _, _>(global, NAME_VERSION, handle, global);
+        self.outputs.insert(global, output);
+    }
+
+    /// Forget an output that was unplugged
+    fn remove(&mut self, global: u32) {
+        if let Some(output) = self.outputs.remove(&global) {
+            output.release();
+        }
+        if self.names.remove(&global).is_some() {
+            self.report();
+        }
+    }
+
+    /// Send the names of the outputs, if they changed
+    fn report(&mut self) {
+        let mut names: Vec<String> = self.names.values().cloned().collect();
+        names.sort();
+        if self.reported.as_ref() != Some(&names) {
+            debug!("The outputs changed to {:?}", names);
+            self.reported = Some(names.clone());
+            let _ = self.sender.unbounded_send(names);
+        }
+    }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for Outputs {
+    fn event(
+        outputs: &mut Self,
+        registry: &WlRegistry,
+        event: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        handle: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_registry::Event::Global {
+                name,
+                interface,
+                version,
+            } if interface == WlOutput::interface().name => {
+                outputs.bind(registry, name, version, handle)
+            }
+            wl_registry::Event::GlobalRemove { name } => outputs.remove(name),
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlOutput, u32> for Outputs {
+    fn event(
+        outputs: &mut Self,
+        _: &WlOutput,
+        event: wl_output::Event,
+        global: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_output::Event::Name { name } => {
+                outputs.names.insert(*global, name);
+            }
+            // The name takes effect once the compositor is done sending the output's details
+            wl_output::Event::Done => outputs.report(),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let (sender, mut receiver) = mpsc::unbounded();
+        let mut outputs = Outputs {
+            sender,
+            outputs: HashMap::new(),
+            names: HashMap::from([(7, "eDP-1".to_string()), (9, "DP-2".to_string())]),
+            reported: None,
+        };
+
+        // Sorted, and only sent again once they change
+        outputs.report();
+        assert_eq!(
+            receiver.try_next().unwrap(),
+            Some(vec!["DP-2".into(), "eDP-1".into()])
+        );
+        outputs.report();
+        assert!(receiver.try_next().is_err());
+
+        outputs.remove(9);
+        assert_eq!(receiver.try_next().unwrap(), Some(vec!["eDP-1".into()]));
+        // Outputs without a name yet were never sent
+        outputs.remove(3);
+        assert!(receiver.try_next().is_err());
+    }
+}
@@ -0,0 +1,60 @@
+use std::env;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use log::warn;
+
+/// The directory for state like the log and history, `$XDG_STATE_HOME` or else ~/.local/state
+pub fn state_dir() -> PathBuf {
+    xdg_dir(env::var_os("XDG_STATE_HOME"), || {
+        dirs::home_dir()
+            .expect("Unable to get the home dir")
+            .join(".local/state")
+    })
+}
+
+/// The directory for temporary files like saved images, `$XDG_RUNTIME_DIR` or else /tmp
+pub fn runtime_dir() -> PathBuf {
+    xdg_dir(env::var_os("XDG_RUNTIME_DIR"), || PathBuf::from("/tmp"))
+}
+
+/// The directory for the images saved from notifications, named after the package in the runtime
+/// directory so they can be told apart from other files
+pub fn image_dir() -> PathBuf {
+    runtime_dir().join(env!("CARGO_PKG_NAME"))
+}
+
+/// A file in the state directory named after the package, like the log, creating the directory if
+/// it doesn't exist
+pub fn state_file(extension: &str) -> PathBuf {
+    let dir = state_dir();
+    if let Err(err) = std::fs::create_dir_all(&dir) {
+        warn!("Unable to create {} with error {}", dir.display(), err);
+    }
+
+    dir.join(env!("CARGO_PKG_NAME")).with_extension(extension)
+}
+
+/// The directory from an XDG environment variable, which is ignored unless it is an absolute path
+fn xdg_dir(var: Option<OsString>, default: impl FnOnce() -> PathBuf) -> PathBuf {
+    var.map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .unwrap_or_else(default)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_xdg_dir() {
+        let default = || PathBuf::from("/home/user/.local/state");
+        assert_eq!(
+            xdg_dir(Some("/xdg/state".into()), default),
+            PathBuf::from("/xdg/state")
+        );
+        assert_eq!(xdg_dir(None, default), default());
+        assert_eq!(xdg_dir(Some("".into()), default), default());
+        assert_eq!(xdg_dir(Some("relative/state".into()), default), default());
+    }
+}
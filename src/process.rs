@@ -0,0 +1,61 @@
+use std::process::{Child, Command};
+use std::sync::Mutex;
+
+use log::{debug, warn};
+
+/// The commands started in the background that haven't been seen exiting yet, with what each is
+/// for
+static RUNNING: Mutex<Vec<(String, Child)>> = Mutex::new(Vec::new());
+
+/// Start a command in the background without waiting for it. The commands that exited since the
+/// last one was started are reaped then, rather than each having a thread waiting for it
+pub fn spawn(purpose: String, command: &mut Command) {
+    let mut running = RUNNING.lock().expect("Running commands poisoned");
+    reap(&mut running);
+    match command.spawn() {
+        Ok(child) => running.push((purpose, child)),
+        Err(err) => warn!("Failed to {} with error {}", purpose, err),
+    }
+}
+
+/// Forget the commands that exited, so they don't linger as zombies
+fn reap(running: &mut Vec<(String, Child)>) {
+    running.retain_mut(|(purpose, child)| match child.try_wait() {
+        Ok(Some(status)) => {
+            if !status.success() {
+                debug!("Command to {} exited with {}", purpose, status);
+            }
+            false
+        }
+        Ok(None) => true,
+        Err(err) => {
+            warn!(
+                "Unable to wait for the command to {} with error {}",
+                purpose, err
+            );
+            false
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_spawn() {
+        spawn("exit".into(), &mut Command::new("true"));
+        spawn("fail".into(), &mut Command::new("/nonexistent/command"));
+
+        for _ in 0..100 {
+            let mut running = RUNNING.lock().expect("Running commands poisoned");
+            reap(&mut running);
+            if running.is_empty() {
+                return;
+            }
+            drop(running);
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        panic!("The command was never reaped");
+    }
+}
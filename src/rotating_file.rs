@@ -0,0 +1,75 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// A log file that is moved to `<path>.old` once it grows past a maximum size, so that at most
+/// two files of the maximum size are kept
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+}
+
+impl RotatingFile {
+    pub fn open(path: PathBuf, max_size: u64) -> io::Result<Self> {
+        let file = File::options().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            file,
+            size,
+            max_size,
+        })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let mut old_path = self.path.clone().into_os_string();
+        old_path.push(".old");
+        std::fs::rename(&self.path, old_path)?;
+
+        self.file = File::options().create(true).append(true).open(&self.path)?;
+        self.size = 0;
+
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rotate() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        let path = dir.join("test.log");
+
+        let mut file = RotatingFile::open(path.clone(), 10).unwrap();
+        file.write_all(b"12345678\n").unwrap();
+        file.write_all(b"abcdefgh\n").unwrap();
+        file.write_all(b"ABCDEFGH\n").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "ABCDEFGH\n");
+        assert_eq!(
+            std::fs::read_to_string(dir.join("test.log.old")).unwrap(),
+            "abcdefgh\n"
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use iced::advanced::layout;
+use iced::advanced::mouse;
+use iced::advanced::overlay;
+use iced::advanced::renderer;
+use iced::advanced::widget;
+use iced::advanced::Layout;
+use iced::advanced::Shell;
+use iced::{Element, Length, Size};
+use iced::{Rectangle, Vector};
+
+/// A container sliding its contents sideways by an offset while taking up the same space, so
+/// they can be dragged away from where they are without moving anything around them. The
+/// contents are clipped to where they would be
+pub struct SlidingContainer<'a, Message, Theme, Renderer> {
+    child: Element<'a, Message, Theme, Renderer>,
+    offset: f32,
+}
+
+impl<'a, Message, Theme, Renderer> SlidingContainer<'a, Message, Theme, Renderer> {
+    pub fn new(child: impl Into<Element<'a, Message, Theme, Renderer>>, offset: f32) -> Self {
+        Self {
+            child: child.into(),
+            offset,
+        }
+    }
+}
+
+impl<'a, Message, Theme, Renderer> widget::Widget<Message, Theme, Renderer>
+    for SlidingContainer<'a, Message, Theme, Renderer>
+where
+    Renderer: renderer::Renderer,
+{
+    fn update(
+        &mut self,
+        tree: &mut widget::Tree,
+        event: &iced::Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &Renderer,
+        clipboard: &mut dyn iced::advanced::Clipboard,
+        shell: &mut Shell<'_, Message>,
+        viewport: &Rectangle,
+    ) {
+        // The contents' layout is already slid, so the cursor lands on them where they are drawn
+        self.child.as_widget_mut().update(
+            &mut tree.children[0],
+            event,
+            layout.children().next().unwrap(),
+            cursor,
+            renderer,
+            clipboard,
+            shell,
+            viewport,
+        );
+    }
+
+    fn operate(
+        &self,
+        tree: &mut widget::Tree,
+        layout: Layout<'_>,
+        renderer: &Renderer,
+        operation: &mut dyn widget::Operation,
+    ) {
+        self.child.as_widget().operate(
+            &mut tree.children[0],
+            layout.children().next().unwrap(),
+            renderer,
+            operation,
+        );
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &widget::Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+        renderer: &Renderer,
+    ) -> mouse::Interaction {
+        self.child.as_widget().mouse_interaction(
+            &tree.children[0],
+            layout.children().next().unwrap(),
+            cursor,
+            viewport,
+            renderer,
+        )
+    }
+
+    fn overlay<'b>(
+        &'b mut self,
+        tree: &'b mut widget::Tree,
+        layout: Layout<'b>,
+        renderer: &Renderer,
+        viewport: &Rectangle,
+        translation: Vector,
+    ) -> Option<overlay::Element<'b, Message, Theme, Renderer>> {
+        self.child.as_widget_mut().overlay(
+            &mut tree.children[0],
+            layout.children().next().unwrap(),
+            renderer,
+            viewport,
+            translation,
+        )
+    }
+
+    fn size(&self) -> Size<Length> {
+        self.child.as_widget().size()
+    }
+
+    fn children(&self) -> Vec<widget::Tree> {
+        vec![widget::Tree::new(&self.child)]
+    }
+
+    fn diff(&self, tree: &mut widget::Tree) {
+        tree.diff_children(&[&self.child]);
+    }
+
+    fn layout(
+        &self,
+        tree: &mut widget::Tree,
+        renderer: &Renderer,
+        limits: &layout::Limits,
+    ) -> layout::Node {
+        let contents = self
+            .child
+            .as_widget()
+            .layout(&mut tree.children[0], renderer, limits);
+
+        layout::Node::with_children(
+            contents.size(),
+            vec![contents.translate(Vector::new(self.offset, 0.0))],
+        )
+    }
+
+    fn draw(
+        &self,
+        state: &widget::Tree,
+        renderer: &mut Renderer,
+        theme: &Theme,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+    ) {
+        let contents_layout = layout.children().next().unwrap();
+
+        renderer.with_layer(layout.bounds(), |renderer| {
+            self.child.as_widget().draw(
+                &state.children[0],
+                renderer,
+                theme,
+                style,
+                contents_layout,
+                cursor,
+                viewport,
+            )
+        });
+    }
+}
+
+impl<'a, Message, Theme, Renderer> From<SlidingContainer<'a, Message, Theme, Renderer>>
+    for Element<'a, Message, Theme, Renderer>
+where
+    Message: 'a,
+    Theme: 'a,
+    Renderer: renderer::Renderer + 'a,
+{
+    fn from(widget: SlidingContainer<'a, Message, Theme, Renderer>) -> Self {
+        Self::new(widget)
+    }
+}
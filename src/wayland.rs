@@ -0,0 +1,59 @@
+use iced::futures::channel::mpsc;
+use iced::futures::{SinkExt, Stream, StreamExt};
+use log::warn;
+use wayland_client::globals::{registry_queue_init, GlobalList, GlobalListContents};
+use wayland_client::protocol::wl_registry::WlRegistry;
+use wayland_client::{Connection, Dispatch, EventQueue};
+
+/// What `watch` sends while watching the compositor for something, which it does on a thread of
+/// its own
+pub fn watch<T: Send + 'static>(
+    watch: impl FnOnce(mpsc::UnboundedSender<T>) + Send + 'static,
+) -> impl Stream<Item = T> {
+    iced::stream::channel(10, async move |mut output| {
+        let (sender, mut receiver) = mpsc::unbounded();
+        // Dispatching the Wayland events blocks, so watch them on their own thread
+        std::thread::spawn(move || watch(sender));
+
+        while let Some(item) = receiver.next().await {
+            let _ = output.send(item).await;
+        }
+    })
+}
+
+/// Connect to the compositor and list its globals, for watching something on a thread of its own
+pub fn connect<State>(watching: &str) -> Option<(GlobalList, EventQueue<State>)>
+where
+    State: Dispatch<WlRegistry, GlobalListContents> + 'static,
+{
+    let connection = match Connection::connect_to_env() {
+        Ok(connection) => connection,
+        Err(err) => {
+            warn!("Unable to connect to Wayland to watch for {watching}: {err}");
+            return None;
+        }
+    };
+    match registry_queue_init::<State>(&connection) {
+        Ok(globals) => Some(globals),
+        Err(err) => {
+            warn!("Unable to list the Wayland globals with error {err}");
+            None
+        }
+    }
+}
+
+/// Dispatch the compositor's events, which blocks, until nothing is listening anymore or the
+/// connection fails
+pub fn dispatch<State>(
+    mut queue: EventQueue<State>,
+    state: &mut State,
+    watching: &str,
+    is_closed: impl Fn(&State) -> bool,
+) {
+    while !is_closed(state) {
+        if let Err(err) = queue.blocking_dispatch(state) {
+            warn!("Stopped watching for {watching} with error {err}");
+            return;
+        }
+    }
+}